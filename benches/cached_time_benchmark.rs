@@ -37,11 +37,7 @@ fn main() {
     for i in 0..5 {
         let cached_time = time_provider.current_millis();
         let system_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
-        let diff = if system_time > cached_time { 
-            system_time - cached_time 
-        } else { 
-            cached_time - system_time 
-        };
+        let diff = system_time.abs_diff(cached_time);
         
         println!("第{}次 - 缓存时间: {}, 系统时间: {}, 差异: {} ms", 
             i + 1, cached_time, system_time, diff);
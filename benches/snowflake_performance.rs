@@ -1,5 +1,28 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
-use snowflake_generator::Snowflake;
+use snowflake_generator::snowflake_core::SEQUENCE_MASK;
+use snowflake_generator::{Snowflake, TimeProvider, EPOCH};
+
+/// 每读取 `SEQUENCE_MASK + 1` 次前进1毫秒的时钟
+/// 
+/// 每次生成恰好读取一次时钟，序列号永远不会耗尽，测得的只是生成路径本身的开销，
+/// 而不是等待下一毫秒的时间。
+struct TickingClock {
+    reads: AtomicU64,
+}
+
+impl TickingClock {
+    fn new() -> Arc<Self> {
+        Arc::new(TickingClock { reads: AtomicU64::new(0) })
+    }
+}
+
+impl TimeProvider for TickingClock {
+    fn current_millis(&self) -> u64 {
+        EPOCH + 1_000 + self.reads.fetch_add(1, Ordering::Relaxed) / (SEQUENCE_MASK + 1)
+    }
+}
 
 fn main() {
     println!("=== 雪花算法性能测试（使用CachedTimeProvider）===");
@@ -52,5 +75,44 @@ fn main() {
         }
     }
     
+    // 紧密循环：大部分调用落在同一毫秒内，走 next_id 的快速路径
+    println!("\n--- 同一毫秒紧密循环测试 ---");
+    let iterations = 1_000_000;
+    let mut tight_ids = Vec::with_capacity(iterations);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        tight_ids.push(snowflake.next_id().unwrap());
+    }
+    let duration = start.elapsed();
+    println!("耗时: {:?} ({:.2} ns/ID)", duration, duration.as_nanos() as f64 / iterations as f64);
+
+    assert!(tight_ids.windows(2).all(|w| w[0] < w[1]), "紧密循环中ID未严格递增");
+    println!("✓ 紧密循环中所有ID严格递增且唯一");
+
+    // 快速路径前后对照：两个生成器使用同样的时钟，都不会等待下一毫秒。
+    // `next_id_at` 不走快速路径，每次都加锁并经过完整的回拨与序列号耗尽检查，
+    // 即引入快速路径之前 `next_id` 的路径；时钟在循环中读取，读取次数相同。
+    let mut with_fast_path = Snowflake::with_time_provider(1, 1, TickingClock::new());
+    let mut after_ids = Vec::with_capacity(iterations);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        after_ids.push(with_fast_path.next_id().unwrap());
+    }
+    let after = start.elapsed();
+
+    let clock = TickingClock::new();
+    let mut without_fast_path = Snowflake::with_time_provider(1, 1, Arc::clone(&clock));
+    let mut before_ids = Vec::with_capacity(iterations);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        before_ids.push(without_fast_path.next_id_at(clock.current_millis()).unwrap());
+    }
+    let before = start.elapsed();
+
+    assert_eq!(after_ids, before_ids, "快速路径改变了生成结果");
+    println!("无快速路径: {:?} ({:.2} ns/ID)", before, before.as_nanos() as f64 / iterations as f64);
+    println!("快速路径:   {:?} ({:.2} ns/ID)", after, after.as_nanos() as f64 / iterations as f64);
+    println!("✓ 两者生成的ID完全相同，快速路径加速 {:.2}x", before.as_secs_f64() / after.as_secs_f64());
+
     println!("\n=== 性能测试完成 ===");
 }
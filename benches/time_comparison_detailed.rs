@@ -4,16 +4,8 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
 use std::time::Duration;
 
-/// 雪花算法常量
-const EPOCH: u64 = 1609459200000; // 2021-01-01 00:00:00 UTC
-const WORKER_ID_BITS: u64 = 5;
-const DATACENTER_ID_BITS: u64 = 5;
-const SEQUENCE_BITS: u64 = 12;
-
-const WORKER_ID_SHIFT: u64 = SEQUENCE_BITS;
-const DATACENTER_ID_SHIFT: u64 = SEQUENCE_BITS + WORKER_ID_BITS;
-const TIMESTAMP_SHIFT: u64 = SEQUENCE_BITS + WORKER_ID_BITS + DATACENTER_ID_BITS;
-const SEQUENCE_MASK: u64 = (1 << SEQUENCE_BITS) - 1;
+use snowflake_generator::snowflake_core::{DATACENTER_ID_SHIFT, SEQUENCE_MASK, TIMESTAMP_SHIFT, WORKER_ID_SHIFT};
+use snowflake_generator::{Snowflake, EPOCH};
 
 /// 系统时间版本的雪花算法
 pub struct SystemTimeSnowflake {
@@ -328,8 +320,8 @@ fn main() {
             println!("🏆 最佳方案: 缓存时间");
         }
         
-        // 简化的ID唯一性验证（生成少量ID进行验证）
-        println!("\n--- ID唯一性验证 (测试100个ID) ---");
+        // 简化的ID唯一性与结构验证（生成少量ID进行验证）
+        println!("\n--- ID唯一性与结构验证 (测试100个ID) ---");
         
         let mut system_test = SystemTimeSnowflake::new(1, 1);
         let mut relative_test = RelativeTimeSnowflake::new(1, 1);
@@ -346,16 +338,18 @@ fn main() {
             cached_test_ids.push(cached_test.next_id());
         }
         
-        let all_unique = |ids: &Vec<u64>| {
+        // 唯一且每个ID都能解码回生成器的 worker/datacenter（1/1）
+        let all_valid = |ids: &Vec<u64>| {
             let mut sorted = ids.clone();
             sorted.sort();
             sorted.dedup();
             sorted.len() == ids.len()
+                && ids.iter().map(|&id| Snowflake::parse_id(id)).all(|info| info.worker_id == 1 && info.datacenter_id == 1)
         };
         
-        println!("系统时间版本: {} ({}个ID)", if all_unique(&system_test_ids) { "✓" } else { "✗" }, system_test_ids.len());
-        println!("相对时间版本: {} ({}个ID)", if all_unique(&relative_test_ids) { "✓" } else { "✗" }, relative_test_ids.len());
-        println!("缓存时间版本: {} ({}个ID)", if all_unique(&cached_test_ids) { "✓" } else { "✗" }, cached_test_ids.len());
+        for (name, ids) in [("系统时间版本", &system_test_ids), ("相对时间版本", &relative_test_ids), ("缓存时间版本", &cached_test_ids)] {
+            println!("{}: {} ({}个ID)", name, if all_valid(ids) { "✓" } else { "✗" }, ids.len());
+        }
     }
     
     println!("\n{}", "=".repeat(70));
@@ -20,7 +20,7 @@ fn main() {
         println!("    工作ID: {}", info.worker_id);
         println!("    序列号: {}", info.sequence);
         println!("    十六进制: {}", info.id_as_hex());
-        println!("    二进制: {}...{}", &info.id_as_binary()[..32], &info.id_as_binary()[32..]);
+        println!("    二进制: {}", info.id_as_binary_grouped());
         println!();
     }
     
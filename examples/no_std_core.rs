@@ -0,0 +1,23 @@
+//! 只使用 `snowflake_core` 中不依赖 `std` 的部分
+//!
+//! 以 `cargo build --no-default-features --examples` 构建，确认这些函数在关闭 `std` feature 后仍可调用。
+
+use snowflake_generator::{
+    build_snowflake_id, validate_ids, validate_snowflake_id_at, IdRangeError, SnowflakeParts, EPOCH,
+};
+
+fn main() {
+    let (worker_id, datacenter_id) = (5, 3);
+    validate_ids(worker_id, datacenter_id).unwrap();
+    assert_eq!(validate_ids(32, datacenter_id), Err(IdRangeError::WorkerId(32)));
+
+    // 毫秒时钟由调用方提供
+    let now_millis = EPOCH + 1_000;
+    let id = build_snowflake_id(now_millis, datacenter_id, worker_id, 0);
+    validate_snowflake_id_at(id, EPOCH, now_millis).unwrap();
+    let parts = SnowflakeParts::decode(id);
+    println!("ID: {}", parts.id);
+    println!("Timestamp: {}", parts.timestamp);
+    println!("Datacenter ID: {}", parts.datacenter_id);
+    println!("Worker ID: {}", parts.worker_id);
+}
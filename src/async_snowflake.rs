@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, Stream};
+
+use crate::snowflake::Snowflake;
+use crate::worker_manager::WorkerError;
+
+/// 需要等待时钟时让出执行器的间隔
+const EXHAUSTED_BACKOFF: Duration = Duration::from_millis(1);
+
+/// 面向异步管道的雪花ID生成器
+/// 
+/// 通过 `id_stream` 以 `futures::Stream` 的形式持续产出ID。
+/// 当前毫秒序列号耗尽、或 `WaitUpTo` 策略下时钟回拨时异步等待，而不是在执行器线程上忙等或休眠；
+/// 等待时长与同步路径的上限相同，设置了停滞回退（`set_stall_fallback`）时到时借用下一毫秒。
+pub struct AsyncSnowflake {
+    inner: Snowflake,
+}
+
+impl AsyncSnowflake {
+    pub fn new(worker_id: u64, datacenter_id: u64) -> Self {
+        AsyncSnowflake {
+            inner: Snowflake::new(worker_id, datacenter_id),
+        }
+    }
+
+    /// 返回一个无限的ID流，可配合 `take(n)` / `for_each` 等组合子使用
+    pub fn id_stream(&mut self) -> impl Stream<Item = Result<u64, WorkerError>> + '_ {
+        stream::unfold(&mut self.inner, |snowflake| async move {
+            let start = Instant::now();
+            loop {
+                match snowflake.poll_next_id(start.elapsed()) {
+                    Ok(Some(id)) => return Some((Ok(id), snowflake)),
+                    Ok(None) => tokio::time::sleep(EXHAUSTED_BACKOFF).await,
+                    Err(err) => return Some((Err(err), snowflake)),
+                }
+            }
+        })
+    }
+
+    pub fn into_inner(self) -> Snowflake {
+        self.inner
+    }
+}
+
+impl From<Snowflake> for AsyncSnowflake {
+    fn from(inner: Snowflake) -> Self {
+        AsyncSnowflake { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snowflake::ClockBackwardsPolicy;
+    use crate::snowflake_core::{build_snowflake_id, extract_sequence, extract_timestamp, SEQUENCE_MASK};
+    use crate::time_provider::{CachedTimeProvider, TimeProvider};
+    use futures::StreamExt;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    /// 接管一个时间戳比本机时钟晚一小时、序列号已满的ID，模拟耗尽后时钟大幅回拨
+    fn exhausted_ahead_of_clock(policy: ClockBackwardsPolicy) -> AsyncSnowflake {
+        let clock = CachedTimeProvider::new(1);
+        let last_id = build_snowflake_id(clock.current_millis() + 3_600_000, 1, 1, SEQUENCE_MASK);
+        let mut snowflake = Snowflake::from_existing_id(last_id, clock).unwrap();
+        snowflake.set_clock_policy(policy);
+        snowflake.set_max_backwards_wait(Duration::from_millis(20));
+        AsyncSnowflake::from(snowflake)
+    }
+
+    #[tokio::test]
+    async fn test_id_stream_unique() {
+        let mut generator = AsyncSnowflake::new(1, 1);
+        let ids: Vec<u64> = generator
+            .id_stream()
+            .take(10_000)
+            .map(|id| id.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(ids.len(), 10_000);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_id_stream_applies_clock_backwards_policy() {
+        for policy in [
+            ClockBackwardsPolicy::Error,
+            ClockBackwardsPolicy::WaitUpTo(Duration::from_millis(20)),
+            ClockBackwardsPolicy::RefuseAndReuseLast,
+        ] {
+            let mut generator = exhausted_ahead_of_clock(policy);
+            let first: Vec<_> = tokio::time::timeout(Duration::from_secs(5), generator.id_stream().take(1).collect())
+                .await
+                .expect("id_stream kept waiting for a clock an hour behind");
+            assert!(
+                matches!(first.as_slice(), [Err(WorkerError::ClockBackwardsError(_))]),
+                "{:?}: {:?}", policy, first
+            );
+            assert!(generator.into_inner().health_report().last_error.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_id_stream_waits_for_backwards_clock_without_blocking() {
+        // 时间戳领先时钟50ms、序列号尚未用完；同步路径会在执行器线程上休眠到时钟追上
+        let clock = CachedTimeProvider::new(1);
+        let last = clock.current_millis() + 50;
+        let mut snowflake = Snowflake::from_existing_id(build_snowflake_id(last, 1, 1, 0), clock).unwrap();
+        snowflake.set_clock_policy(ClockBackwardsPolicy::WaitUpTo(Duration::from_secs(2)));
+        let mut generator = AsyncSnowflake::from(snowflake);
+
+        // 单线程执行器上，等待期间其他任务仍能运行
+        let ticks = Arc::new(AtomicU64::new(0));
+        let ticker = tokio::spawn({
+            let ticks = Arc::clone(&ticks);
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+        let first: Vec<_> = generator.id_stream().take(1).collect().await;
+        ticker.abort();
+
+        let [Ok(id)] = first.as_slice() else { panic!("expected one id, got {:?}", first) };
+        assert!(extract_timestamp(*id) >= last);
+        assert!(ticks.load(Ordering::Relaxed) >= 5, "executor was blocked while waiting");
+    }
+
+    #[tokio::test]
+    async fn test_id_stream_respects_stall_fallback() {
+        // 更新间隔一分钟的缓存时钟在测试期间停住不动，当前毫秒的序列号已用完
+        let clock = CachedTimeProvider::new(60_000);
+        let now = clock.current_millis();
+        let exhausted = build_snowflake_id(now, 1, 1, SEQUENCE_MASK);
+
+        let mut snowflake = Snowflake::from_existing_id(exhausted, Arc::clone(&clock)).unwrap();
+        snowflake.set_stall_fallback(Some(Duration::from_millis(10)));
+        let mut generator = AsyncSnowflake::from(snowflake);
+        let first: Vec<_> = tokio::time::timeout(Duration::from_secs(5), generator.id_stream().take(1).collect())
+            .await
+            .expect("id_stream ignored the stall fallback");
+        let [Ok(id)] = first.as_slice() else { panic!("expected one id, got {:?}", first) };
+        assert_eq!((extract_timestamp(*id), extract_sequence(*id)), (now + 1, 0));
+
+        // 未设置停滞回退时与同步路径一样在 `max_backwards_wait` 后返回错误
+        let mut snowflake = Snowflake::from_existing_id(exhausted, clock).unwrap();
+        snowflake.set_max_backwards_wait(Duration::from_millis(20));
+        let mut generator = AsyncSnowflake::from(snowflake);
+        let first: Vec<_> = tokio::time::timeout(Duration::from_secs(5), generator.id_stream().take(1).collect())
+            .await
+            .expect("id_stream kept waiting for a frozen clock");
+        assert!(matches!(first.as_slice(), [Err(WorkerError::ClockBackwardsError(_))]), "{:?}", first);
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::snowflake_core::*;
+use crate::time_provider::{CachedTimeProvider, TimeProvider};
+use crate::worker_manager::WorkerError;
+
+/// 无锁的雪花ID生成器，适合单实例、多线程共享的高吞吐场景
+/// 
+/// 将相对纪元的时间戳与序列号打包进同一个 `AtomicU64`，
+/// `next_id(&self)` 通过 CAS 循环推进状态，多个线程可共享一个
+/// `Arc<AtomicSnowflake>` 而无需互斥锁。不支持配置文件持久化与回拨等待策略，
+/// 检测到时钟回拨时直接返回错误。
+pub struct AtomicSnowflake {
+    worker_id: u64,
+    datacenter_id: u64,
+    /// 高位为 `timestamp - EPOCH`，低 `SEQUENCE_BITS` 位为序列号
+    state: AtomicU64,
+    time_provider: Arc<CachedTimeProvider>,
+}
+
+impl AtomicSnowflake {
+    /// 创建无锁生成器
+    /// 
+    /// # Panics
+    /// `worker_id` 或 `datacenter_id` 超出范围时 panic
+    pub fn new(worker_id: u64, datacenter_id: u64) -> Self {
+        Self::with_time_provider(worker_id, datacenter_id, CachedTimeProvider::new(1))
+    }
+
+    /// 使用给定的（可与其他生成器共享的）时间提供者创建无锁生成器
+    pub fn with_time_provider(worker_id: u64, datacenter_id: u64, time_provider: Arc<CachedTimeProvider>) -> Self {
+        validate_ids(worker_id, datacenter_id).expect("Invalid worker_id or datacenter_id");
+
+        AtomicSnowflake {
+            worker_id,
+            datacenter_id,
+            state: AtomicU64::new(0),
+            time_provider,
+        }
+    }
+
+    /// 生成下一个雪花ID
+    /// 
+    /// 同一毫秒内序列号耗尽时自旋等待下一毫秒；时钟回拨时返回 `ClockBackwardsError`。
+    pub fn next_id(&self) -> Result<u64, WorkerError> {
+        let mut current = self.state.load(Ordering::Acquire);
+        loop {
+            let last_timestamp = current >> SEQUENCE_BITS;
+            let sequence = current & SEQUENCE_MASK;
+            let now = self.relative_millis();
+
+            let next = if now > last_timestamp {
+                now << SEQUENCE_BITS
+            } else if now < last_timestamp {
+                return Err(WorkerError::ClockBackwardsError(format!(
+                    "Clock moved backwards. Last: {}, Current: {}",
+                    last_timestamp + EPOCH, now + EPOCH
+                )));
+            } else if sequence < SEQUENCE_MASK {
+                current + 1
+            } else {
+                while self.relative_millis() <= last_timestamp {
+                    std::hint::spin_loop();
+                }
+                current = self.state.load(Ordering::Acquire);
+                continue;
+            };
+
+            match self.state.compare_exchange_weak(current, next, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => {
+                    return Ok(build_snowflake_id(
+                        (next >> SEQUENCE_BITS) + EPOCH,
+                        self.datacenter_id,
+                        self.worker_id,
+                        next & SEQUENCE_MASK,
+                    ));
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn get_worker_id(&self) -> u64 {
+        self.worker_id
+    }
+
+    pub fn get_datacenter_id(&self) -> u64 {
+        self.datacenter_id
+    }
+
+    /// 最后一个ID使用的毫秒时间戳，尚未生成过ID时为纪元
+    pub fn get_last_timestamp(&self) -> u64 {
+        (self.state.load(Ordering::Acquire) >> SEQUENCE_BITS) + EPOCH
+    }
+
+    fn relative_millis(&self) -> u64 {
+        self.time_provider.current_millis().saturating_sub(EPOCH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::thread;
+
+    #[test]
+    fn test_sequential_ids_increase() {
+        let generator = AtomicSnowflake::new(3, 2);
+        let ids: Vec<u64> = (0..10_000).map(|_| generator.next_id().unwrap()).collect();
+
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.iter().all(|&id| extract_worker_id(id) == 3 && extract_datacenter_id(id) == 2));
+    }
+
+    #[test]
+    fn test_concurrent_ids_unique() {
+        const THREADS: usize = 16;
+        const PER_THREAD: usize = 100_000;
+
+        let generator = Arc::new(AtomicSnowflake::new(1, 1));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let generator = Arc::clone(&generator);
+                thread::spawn(move || {
+                    (0..PER_THREAD).map(|_| generator.next_id().unwrap()).collect::<Vec<u64>>()
+                })
+            })
+            .collect();
+
+        let mut seen = HashSet::with_capacity(THREADS * PER_THREAD);
+        for handle in handles {
+            let ids = handle.join().unwrap();
+            // 单个线程内看到的ID严格递增
+            assert!(ids.windows(2).all(|w| w[0] < w[1]));
+            seen.extend(ids);
+        }
+        assert_eq!(seen.len(), THREADS * PER_THREAD);
+    }
+}
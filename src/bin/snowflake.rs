@@ -0,0 +1,59 @@
+use clap::{Parser, Subcommand};
+
+use snowflake_generator::{Snowflake, WorkerError, EPOCH, TIMESTAMP_SHIFT};
+
+/// Snowflake ID command line tool
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate snowflake IDs, one per line
+    Generate {
+        /// Number of IDs to generate
+        #[arg(short, long, default_value_t = 1)]
+        count: usize,
+
+        /// Worker ID (0-31)
+        #[arg(short, long, default_value_t = 1)]
+        worker: u64,
+
+        /// Datacenter ID (0-31)
+        #[arg(short, long, default_value_t = 1)]
+        datacenter: u64,
+    },
+    /// Parse a snowflake ID and print its components
+    Parse {
+        /// Snowflake ID to parse
+        id: u64,
+
+        /// Epoch in milliseconds the ID was generated against
+        #[arg(short, long, default_value_t = EPOCH)]
+        epoch: u64,
+    },
+}
+
+fn main() -> Result<(), WorkerError> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Generate { count, worker, datacenter } => {
+            snowflake_generator::validate_ids(worker, datacenter)?;
+            let mut snowflake = Snowflake::new(worker, datacenter);
+            for id in snowflake.next_ids(count)? {
+                println!("{}", id);
+            }
+        }
+        Command::Parse { id, epoch } => {
+            let mut info = Snowflake::parse_id(id);
+            info.timestamp = (id >> TIMESTAMP_SHIFT) + epoch;
+            println!("{}", info.format_details());
+        }
+    }
+
+    Ok(())
+}
@@ -1,19 +1,24 @@
 use axum::{
     extract::{Query, State, Path},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::Json,
-    routing::get,
+    routing::{get, post},
     Router,
 };
 use clap::Parser;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::time::{Duration, Instant};
 use tower::ServiceBuilder;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{info, warn};
-use tracing_subscriber;
+use tracing::{error, info, warn};
 
-use snowflake_generator::Snowflake;
+use snowflake_generator::{
+    build_snowflake_id_with_epoch, encode_base58, encode_base62, extract_timestamp_with_epoch, fnv1a_hash,
+    validate_snowflake_id, HealthReport, HealthStatus, IdFilter, Snowflake, SnowflakeInfo, WorkerError, EPOCH,
+    MAX_TYPE_TAG,
+};
+use snowflake_generator::snowflake::DEFAULT_PERSIST_INTERVAL_MS;
 
 /// Snowflake ID Generator HTTP Server
 #[derive(Parser, Debug)]
@@ -38,22 +43,229 @@ struct Args {
     /// Use configuration file for worker management
     #[arg(short, long)]
     config_file: Option<String>,
+
+    /// Worker config file persistence interval in milliseconds (0 = every ID)
+    #[arg(long, default_value_t = DEFAULT_PERSIST_INTERVAL_MS)]
+    persist_interval_ms: u64,
+
+    /// Custom epoch in milliseconds since the Unix epoch (must not be in the future)
+    #[arg(long, default_value_t = EPOCH)]
+    epoch_millis: u64,
+
+    /// Maximum number of ids accepted by a single POST /parse/batch request
+    #[arg(long, default_value_t = MAX_BATCH_SIZE)]
+    max_parse_batch: usize,
+
+    /// Warn when the last issued id's timestamp lags the system clock by more
+    /// than this many milliseconds while ids are being generated (0 = disabled)
+    #[arg(long, default_value_t = DEFAULT_DRIFT_ALARM_MS)]
+    drift_alarm_ms: u64,
+
+    /// Derive the type-tag bits of generated ids from the W3C `traceparent`
+    /// header so all ids minted within one trace share them. Requests without a
+    /// valid trace header get ordinary untyped ids. POST /reserve is disabled,
+    /// since a reserved range cannot make room for typed ids.
+    #[arg(long)]
+    trace_routing: bool,
+
+    /// Only accept ids from these datacenters on the parse endpoints
+    /// (repeatable; default: any datacenter)
+    #[arg(long = "allow-datacenter", value_name = "ID")]
+    allow_datacenters: Vec<u64>,
+
+    /// Only accept ids from these workers on the parse endpoints
+    /// (repeatable; default: any worker)
+    #[arg(long = "allow-worker", value_name = "ID")]
+    allow_workers: Vec<u64>,
 }
 
+/// Maximum number of IDs generated per batch request
+const MAX_BATCH_SIZE: usize = 1000;
+
+/// Default threshold for the id timestamp drift alarm
+const DEFAULT_DRIFT_ALARM_MS: u64 = 5000;
+
+/// How often the drift alarm compares id timestamps against the system clock
+const DRIFT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Callback invoked with every id generation failure
+type GenerationErrorHook = Arc<dyn Fn(&WorkerError) + Send + Sync>;
+
 /// Application state shared across handlers
 #[derive(Clone)]
 struct AppState {
     snowflake: Arc<Mutex<Snowflake>>,
     stats: Arc<Mutex<ServerStats>>,
+    on_generation_error: Option<GenerationErrorHook>,
+    epoch_millis: u64,
+    max_parse_batch: usize,
+    trace_routing: bool,
+    id_filter: IdFilter,
 }
 
+impl AppState {
+    fn new(snowflake: Snowflake) -> Self {
+        Self {
+            epoch_millis: snowflake.get_epoch_millis(),
+            snowflake: Arc::new(Mutex::new(snowflake)),
+            stats: Arc::new(Mutex::new(ServerStats::new())),
+            on_generation_error: None,
+            max_parse_batch: MAX_BATCH_SIZE,
+            trace_routing: false,
+            id_filter: IdFilter::default(),
+        }
+    }
+
+    /// Reject ids from unexpected sources on the parse endpoints
+    fn with_id_filter(mut self, filter: IdFilter) -> Self {
+        self.id_filter = filter;
+        self
+    }
+
+    /// Mint typed ids whose tag is derived from the request's trace context
+    fn with_trace_routing(mut self, enabled: bool) -> Self {
+        self.trace_routing = enabled;
+        self
+    }
+
+    /// Type tag to mint ids with, or `None` when trace routing is disabled or
+    /// the request carries no valid trace header
+    fn routing_tag(&self, headers: &HeaderMap) -> Option<u8> {
+        if self.trace_routing {
+            trace_tag(headers)
+        } else {
+            None
+        }
+    }
+
+    /// Limit the number of ids a single batch parse request may decode
+    fn with_max_parse_batch(mut self, max: usize) -> Self {
+        self.max_parse_batch = max;
+        self
+    }
+
+    /// Forward generation failures to `hook`, e.g. to push them to an alerting pipeline
+    fn with_on_generation_error(mut self, hook: impl Fn(&WorkerError) + Send + Sync + 'static) -> Self {
+        self.on_generation_error = Some(Arc::new(hook));
+        self
+    }
+
+    /// Return the current id timestamp drift if it exceeds `threshold_ms`
+    ///
+    /// Only ids generated since the previous check count: an idle server's
+    /// drift grows naturally and is not a sign of a stuck clock.
+    fn check_drift(&self, threshold_ms: u64, last_generations: &mut u64) -> Option<i64> {
+        let generations = self.stats.lock().unwrap().successful_generations;
+        if generations == *last_generations {
+            return None;
+        }
+        *last_generations = generations;
+
+        let drift = self.snowflake.lock().unwrap().last_id_timestamp_drift_ms()?;
+        (drift.unsigned_abs() > threshold_ms).then_some(drift)
+    }
+
+    /// Run `generate` against the locked generator, counting the sequence
+    /// exhaustions (waits for the next millisecond) it caused and whether
+    /// another request was holding the generator
+    fn generate<R>(&self, generate: impl FnOnce(&mut Snowflake) -> R) -> R {
+        let (mut snowflake, contended) = match self.snowflake.try_lock() {
+            Ok(snowflake) => (snowflake, false),
+            Err(TryLockError::WouldBlock) => (self.snowflake.lock().unwrap(), true),
+            Err(TryLockError::Poisoned(err)) => panic!("generator mutex poisoned: {}", err),
+        };
+        let rollovers_before = snowflake.get_rollover_count();
+        let result = generate(&mut snowflake);
+        let exhaustions = snowflake.get_rollover_count() - rollovers_before;
+        drop(snowflake);
+
+        if exhaustions > 0 || contended {
+            let mut stats = self.stats.lock().unwrap();
+            stats.sequence_exhaustions += exhaustions;
+            stats.lock_contentions += contended as u64;
+        }
+        result
+    }
+
+    /// Persist the generator's progress and log the final statistics
+    ///
+    /// Called once the server has stopped accepting requests, so the last
+    /// issued timestamp reaches the config file and a restart cannot replay it.
+    fn shutdown(&self) -> Result<(), WorkerError> {
+        let flushed = self.snowflake.lock().unwrap().flush();
+
+        let stats = self.stats.lock().unwrap();
+        info!(
+            "Final stats: {} requests, {} ids generated, {} failed, {} sequence exhaustions, uptime {} s",
+            stats.total_requests,
+            stats.successful_generations,
+            stats.failed_generations,
+            stats.sequence_exhaustions,
+            stats.start_time.elapsed().as_secs()
+        );
+        flushed
+    }
+
+    /// Count a failed generation and hand the error to the configured hook
+    fn record_generation_error(&self, err: &WorkerError, failed: u64) {
+        self.stats.lock().unwrap().failed_generations += failed;
+        if let Some(hook) = &self.on_generation_error {
+            hook(err);
+        }
+    }
+}
+
+/// Derive a type tag from the trace id of a valid W3C `traceparent` header
+///
+/// Format: `version-traceid-parentid-flags`, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`. Malformed
+/// headers and all-zero ids are ignored as the spec requires.
+fn trace_tag(headers: &HeaderMap) -> Option<u8> {
+    let value = headers.get("traceparent")?.to_str().ok()?.trim();
+    let parts: Vec<&str> = value.split('-').collect();
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+
+    let (version, trace_id, parent_id, flags) = match parts.as_slice() {
+        [version, trace_id, parent_id, flags] => (*version, *trace_id, *parent_id, *flags),
+        // Later versions may append fields; version 00 must have exactly four
+        [version, trace_id, parent_id, flags, ..] if *version != "00" => (*version, *trace_id, *parent_id, *flags),
+        _ => return None,
+    };
+    if !is_hex(version, 2) || version == "ff" || !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+    if trace_id.bytes().all(|b| b == b'0') || parent_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    Some((fnv1a_hash(trace_id.as_bytes()) & MAX_TYPE_TAG) as u8)
+}
+
+/// Mint ids into `ids`, as typed ids carrying `tag` when one is given
+fn mint_into(snowflake: &mut Snowflake, tag: Option<u8>, count: usize, ids: &mut Vec<u64>) -> Result<(), WorkerError> {
+    match tag {
+        Some(tag) => (0..count).try_for_each(|_| {
+            ids.push(snowflake.next_id_of_type(tag)?);
+            Ok(())
+        }),
+        None => snowflake.generate_into(count, ids),
+    }
+}
+
+/// Number of most recent generation latencies kept for percentile reporting
+const LATENCY_WINDOW: usize = 10_000;
+
 /// Server statistics
 #[derive(Debug, Clone)]
 struct ServerStats {
     total_requests: u64,
     successful_generations: u64,
     failed_generations: u64,
+    /// Times the per-millisecond sequence ran out and generation moved on to the next millisecond
+    sequence_exhaustions: u64,
+    /// Times a request found the generator locked by another request and had to wait
+    lock_contentions: u64,
     start_time: std::time::Instant,
+    latencies: LatencyHistogram,
 }
 
 impl ServerStats {
@@ -62,18 +274,123 @@ impl ServerStats {
             total_requests: 0,
             successful_generations: 0,
             failed_generations: 0,
+            sequence_exhaustions: 0,
+            lock_contentions: 0,
             start_time: std::time::Instant::now(),
+            latencies: LatencyHistogram::new(LATENCY_WINDOW),
+        }
+    }
+}
+
+/// Sliding window of id generation latencies in nanoseconds
+#[derive(Debug, Clone)]
+struct LatencyHistogram {
+    samples: Vec<u64>,
+    capacity: usize,
+    next: usize,
+}
+
+impl LatencyHistogram {
+    fn new(capacity: usize) -> Self {
+        Self {
+            samples: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+        }
+    }
+
+    /// Record a sample, overwriting the oldest once the window is full
+    fn record(&mut self, latency: Duration) {
+        let nanos = latency.as_nanos().min(u64::MAX as u128) as u64;
+        if self.samples.len() < self.capacity {
+            self.samples.push(nanos);
+        } else {
+            self.samples[self.next] = nanos;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    /// Nearest-rank percentiles, `None` until the first sample is recorded
+    fn summary(&self) -> LatencySummary {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let percentile = |p: f64| {
+            if sorted.is_empty() {
+                return None;
+            }
+            let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+            Some(sorted[rank - 1])
+        };
+
+        LatencySummary {
+            samples: sorted.len(),
+            p50_ns: percentile(0.50),
+            p99_ns: percentile(0.99),
+            p999_ns: percentile(0.999),
+        }
+    }
+}
+
+/// Id generation latency percentiles over the recent window
+#[derive(Serialize)]
+struct LatencySummary {
+    samples: usize,
+    p50_ns: Option<u64>,
+    p99_ns: Option<u64>,
+    p999_ns: Option<u64>,
+}
+
+/// Output representation of a generated id
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum IdFormat {
+    /// JSON number (default, loses precision in JavaScript above 2^53)
+    #[default]
+    Number,
+    /// Decimal string
+    String,
+    /// `0x`-prefixed hex string
+    Hex,
+    /// Base62 string
+    Base62,
+}
+
+/// Generated id rendered as a JSON number or string
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum IdValue {
+    Number(u64),
+    Text(String),
+}
+
+impl IdValue {
+    fn format(id: u64, format: IdFormat) -> Self {
+        match format {
+            IdFormat::Number => IdValue::Number(id),
+            IdFormat::String => IdValue::Text(id.to_string()),
+            IdFormat::Hex => IdValue::Text(format!("0x{:016x}", id)),
+            IdFormat::Base62 => IdValue::Text(encode_base62(id)),
         }
     }
 }
 
+/// Query parameters for single ID generation
+#[derive(Deserialize, Default)]
+struct IdQuery {
+    #[serde(rename = "as")]
+    format: Option<IdFormat>,
+}
+
 /// Response for single ID generation
 #[derive(Serialize)]
 struct IdResponse {
-    id: u64,
+    id: IdValue,
     worker_id: u64,
     datacenter_id: u64,
     timestamp: u64,
+    /// Type tag derived from the trace context (only with `--trace-routing` and a valid `traceparent` header)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_tag: Option<u8>,
 }
 
 /// Response for batch ID generation
@@ -85,10 +402,74 @@ struct BatchIdResponse {
     datacenter_id: u64,
 }
 
+/// Alphabet used by the `/encode` endpoint
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum Alphabet {
+    #[default]
+    Base62,
+    /// Bitcoin-style alphabet without 0, O, I and l
+    Base58,
+}
+
+/// Query parameters for id encoding
+#[derive(Deserialize, Default)]
+struct EncodeQuery {
+    alphabet: Option<Alphabet>,
+}
+
+/// Response for id encoding
+#[derive(Serialize)]
+struct EncodeResponse {
+    id: u64,
+    alphabet: Alphabet,
+    encoded: String,
+}
+
 /// Query parameters for batch generation
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct BatchQuery {
     count: Option<usize>,
+    /// Reject counts above `MAX_BATCH_SIZE` instead of clamping them
+    #[serde(default)]
+    strict: bool,
+}
+
+impl BatchQuery {
+    /// Requested count (or `default`), clamped to `MAX_BATCH_SIZE` unless strict
+    fn resolve_count(&self, default: usize) -> Result<usize, String> {
+        let count = self.count.unwrap_or(default);
+        if self.strict && count > MAX_BATCH_SIZE {
+            return Err(format!("count {} exceeds the maximum batch size of {}", count, MAX_BATCH_SIZE));
+        }
+        Ok(count.min(MAX_BATCH_SIZE))
+    }
+}
+
+/// Query parameters for id range reservation
+#[derive(Deserialize)]
+struct ReserveQuery {
+    count: Option<usize>,
+}
+
+/// Response for id range reservation
+#[derive(Serialize)]
+struct ReserveResponse {
+    start: u64,
+    end: u64,
+    count: u64,
+    worker_id: u64,
+    datacenter_id: u64,
+    timestamp: u64,
+}
+
+/// Self-verification result
+#[derive(Serialize)]
+struct VerifyResponse {
+    ok: bool,
+    generated: usize,
+    duplicates: Vec<u64>,
+    non_monotonic: usize,
 }
 
 /// Server statistics response
@@ -100,6 +481,12 @@ struct StatsResponse {
     success_rate: f64,
     uptime_seconds: u64,
     requests_per_second: f64,
+    latency: LatencySummary,
+    /// System time minus the last issued id's timestamp, in milliseconds
+    last_id_drift_ms: Option<i64>,
+    /// Times generation had to wait for the next millisecond because the
+    /// sequence was exhausted; a steadily rising count suggests adding workers
+    sequence_exhaustions: u64,
 }
 
 /// Snowflake ID parse response
@@ -115,42 +502,119 @@ struct ParseResponse {
     details: String,
 }
 
+/// One entry of a batch parse response: the decoded id, or why it is not a valid snowflake
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ParseBatchItem {
+    Parsed(ParseResponse),
+    Invalid { id: u64, error: String },
+}
+
+impl From<SnowflakeInfo> for ParseResponse {
+    fn from(info: SnowflakeInfo) -> Self {
+        Self {
+            id: info.id,
+            id_hex: info.id_as_hex(),
+            timestamp: info.timestamp,
+            timestamp_formatted: info.timestamp_as_string(),
+            datacenter_id: info.datacenter_id,
+            worker_id: info.worker_id,
+            sequence: info.sequence,
+            details: info.format_details(),
+        }
+    }
+}
+
+/// Detailed health report
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    rollover_count: u64,
+    /// Generation requests that waited for the generator held by another
+    /// request; reported for capacity planning, it does not affect `status`
+    lock_contentions: u64,
+    last_id_drift_ms: Option<i64>,
+    clock_lag_ms: Option<i64>,
+    saturation: f64,
+    last_error: Option<String>,
+}
+
+impl From<HealthReport> for HealthResponse {
+    fn from(report: HealthReport) -> Self {
+        Self {
+            status: match report.status {
+                HealthStatus::Healthy => "healthy",
+                HealthStatus::Degraded => "degraded",
+                HealthStatus::Unhealthy => "unhealthy",
+            },
+            rollover_count: report.rollover_count,
+            lock_contentions: 0,
+            last_id_drift_ms: report.last_id_drift_ms,
+            clock_lag_ms: report.clock_lag_ms,
+            saturation: report.saturation,
+            last_error: report.last_error,
+        }
+    }
+}
+
 /// Health check handler
 async fn health() -> &'static str {
     "OK"
 }
 
+/// Aggregate generator diagnostics; responds 503 when unhealthy so probes can act on it
+async fn health_detailed(State(state): State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let report = state.snowflake.lock().unwrap().health_report();
+    let code = if report.status == HealthStatus::Unhealthy {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    let lock_contentions = state.stats.lock().unwrap().lock_contentions;
+    (code, Json(HealthResponse { lock_contentions, ..HealthResponse::from(report) }))
+}
+
 /// Generate a single snowflake ID
-async fn generate_id(State(state): State<AppState>) -> Result<Json<IdResponse>, StatusCode> {
+async fn generate_id(
+    headers: HeaderMap,
+    Query(params): Query<IdQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<IdResponse>, StatusCode> {
     let mut stats = state.stats.lock().unwrap();
     stats.total_requests += 1;
     drop(stats);
 
-    let mut snowflake = state.snowflake.lock().unwrap();
-    match snowflake.next_id() {
-        Ok(id) => {
-            let worker_id = snowflake.get_worker_id();
-            let datacenter_id = snowflake.get_datacenter_id();
-            drop(snowflake);
-
+    let tag = state.routing_tag(&headers);
+    let (result, worker_id, datacenter_id) = state.generate(|snowflake| {
+        let result = match tag {
+            Some(tag) => {
+                let start = Instant::now();
+                snowflake.next_id_of_type(tag).map(|id| (id, start.elapsed()))
+            }
+            None => snowflake.next_id_timed(),
+        };
+        (result, snowflake.get_worker_id(), snowflake.get_datacenter_id())
+    });
+    match result {
+        Ok((id, latency)) => {
             let mut stats = state.stats.lock().unwrap();
             stats.successful_generations += 1;
+            stats.latencies.record(latency);
             drop(stats);
 
-            // Extract timestamp from ID (first 41 bits after shifting)
-            let timestamp = (id >> 22) + 1609459200000; // Add epoch back
+            let timestamp = extract_timestamp_with_epoch(id, state.epoch_millis);
 
             Ok(Json(IdResponse {
-                id,
+                id: IdValue::format(id, params.format.unwrap_or_default()),
                 worker_id,
                 datacenter_id,
                 timestamp,
+                trace_tag: tag,
             }))
         }
         Err(err) => {
             warn!("Failed to generate ID: {}", err);
-            let mut stats = state.stats.lock().unwrap();
-            stats.failed_generations += 1;
+            state.record_generation_error(&err, 1);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
@@ -158,43 +622,41 @@ async fn generate_id(State(state): State<AppState>) -> Result<Json<IdResponse>,
 
 /// Generate batch of snowflake IDs
 async fn generate_batch(
+    headers: HeaderMap,
     Query(params): Query<BatchQuery>,
     State(state): State<AppState>,
-) -> Result<Json<BatchIdResponse>, StatusCode> {
-    let count = params.count.unwrap_or(10).min(1000); // Limit to 1000 IDs per request
-
+) -> Result<Json<BatchIdResponse>, (StatusCode, String)> {
     let mut stats = state.stats.lock().unwrap();
     stats.total_requests += 1;
     drop(stats);
 
-    let mut snowflake = state.snowflake.lock().unwrap();
-    let worker_id = snowflake.get_worker_id();
-    let datacenter_id = snowflake.get_datacenter_id();
+    let count = params.resolve_count(10).map_err(|msg| {
+        warn!("Rejected batch request: {}", msg);
+        (StatusCode::BAD_REQUEST, msg)
+    })?;
 
+    let tag = state.routing_tag(&headers);
+
+    // Hold the generator mutex for the whole batch; untyped batches claim each
+    // millisecond's remaining sequence numbers at once, typed ids are minted one
+    // by one. Ids produced before a failure are kept
     let mut ids = Vec::with_capacity(count);
-    let mut success_count = 0;
+    let (worker_id, datacenter_id, failure) = state.generate(|snowflake| {
+        let failure = mint_into(snowflake, tag, count, &mut ids).err();
+        (snowflake.get_worker_id(), snowflake.get_datacenter_id(), failure)
+    });
 
-    for _ in 0..count {
-        match snowflake.next_id() {
-            Ok(id) => {
-                ids.push(id);
-                success_count += 1;
-            }
-            Err(err) => {
-                warn!("Failed to generate ID in batch: {}", err);
-                break;
-            }
-        }
+    // Generation stops at the first failure, so it never yields more ids than requested
+    let success_count = ids.len();
+    debug_assert!(success_count <= count, "batch produced {} ids for count {}", success_count, count);
+    state.stats.lock().unwrap().successful_generations += success_count as u64;
+    if let Some(err) = failure {
+        warn!("Failed to generate ID in batch: {}", err);
+        state.record_generation_error(&err, count.saturating_sub(success_count) as u64);
     }
-    drop(snowflake);
-
-    let mut stats = state.stats.lock().unwrap();
-    stats.successful_generations += success_count as u64;
-    stats.failed_generations += (count - success_count) as u64;
-    drop(stats);
 
     if ids.is_empty() {
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "failed to generate ids".to_string()));
     }
 
     Ok(Json(BatchIdResponse {
@@ -205,8 +667,100 @@ async fn generate_batch(
     }))
 }
 
+/// Generate a batch internally and verify it is strictly increasing and unique
+async fn verify(
+    Query(params): Query<BatchQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<VerifyResponse>, StatusCode> {
+    let mut stats = state.stats.lock().unwrap();
+    stats.total_requests += 1;
+    drop(stats);
+
+    let count = params.resolve_count(100).map_err(|msg| {
+        warn!("Rejected verify request: {}", msg);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    let tag = state.trace_routing.then_some(0);
+    let mut ids = Vec::with_capacity(count);
+    let result = state.generate(|snowflake| mint_into(snowflake, tag, count, &mut ids));
+    match result {
+        Ok(()) => {}
+        Err(err) => {
+            warn!("Failed to generate IDs for verification: {}", err);
+            state.record_generation_error(&err, 1);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    state.stats.lock().unwrap().successful_generations += ids.len() as u64;
+
+    let non_monotonic = ids.windows(2).filter(|w| w[0] >= w[1]).count();
+    let mut seen = std::collections::HashSet::with_capacity(ids.len());
+    let mut duplicates: Vec<u64> = ids.iter().copied().filter(|id| !seen.insert(*id)).collect();
+    duplicates.sort_unstable();
+    duplicates.dedup();
+
+    if !duplicates.is_empty() || non_monotonic > 0 {
+        warn!(
+            "Verification failed: {} duplicates, {} non-monotonic pairs",
+            duplicates.len(),
+            non_monotonic
+        );
+    }
+
+    Ok(Json(VerifyResponse {
+        ok: duplicates.is_empty() && non_monotonic == 0,
+        generated: ids.len(),
+        duplicates,
+        non_monotonic,
+    }))
+}
+
+/// Reserve a contiguous id range that the server will never reissue
+async fn reserve_ids(
+    Query(params): Query<ReserveQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<ReserveResponse>, StatusCode> {
+    let count = params.count.unwrap_or(1);
+
+    let mut stats = state.stats.lock().unwrap();
+    stats.total_requests += 1;
+    drop(stats);
+
+    // A reservation hands out raw sequence numbers that would overlap typed ids
+    if state.trace_routing {
+        warn!("Rejected reservation request: reservations are disabled with --trace-routing");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let result = state.generate(|snowflake| snowflake.reserve_ids(count));
+    match result {
+        Ok(reservation) => {
+            state.stats.lock().unwrap().successful_generations += reservation.count;
+            Ok(Json(ReserveResponse {
+                start: reservation.start(),
+                end: reservation.end(),
+                count: reservation.count,
+                worker_id: reservation.worker_id,
+                datacenter_id: reservation.datacenter_id,
+                timestamp: reservation.timestamp,
+            }))
+        }
+        Err(WorkerError::InvalidArgument(msg)) => {
+            warn!("Rejected reservation request: {}", msg);
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(err) => {
+            warn!("Failed to reserve IDs: {}", err);
+            state.record_generation_error(&err, 1);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 /// Get server statistics
 async fn get_stats(State(state): State<AppState>) -> Json<StatsResponse> {
+    let last_id_drift_ms = state.snowflake.lock().unwrap().last_id_timestamp_drift_ms();
     let stats = state.stats.lock().unwrap();
     let uptime = stats.start_time.elapsed().as_secs();
     let success_rate = if stats.total_requests > 0 {
@@ -227,23 +781,191 @@ async fn get_stats(State(state): State<AppState>) -> Json<StatsResponse> {
         success_rate,
         uptime_seconds: uptime,
         requests_per_second: rps,
+        latency: stats.latencies.summary(),
+        last_id_drift_ms,
+        sequence_exhaustions: stats.sequence_exhaustions,
     })
 }
 
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Expose server counters in the Prometheus text exposition format
+async fn metrics(State(state): State<AppState>) -> ([(header::HeaderName, &'static str); 1], String) {
+    let (worker_id, datacenter_id) = {
+        let snowflake = state.snowflake.lock().unwrap();
+        (snowflake.get_worker_id(), snowflake.get_datacenter_id())
+    };
+    let stats = state.stats.lock().unwrap();
+    let labels = format!("worker_id=\"{}\",datacenter_id=\"{}\"", worker_id, datacenter_id);
+
+    let mut body = String::new();
+    for (name, kind, help, value) in [
+        ("snowflake_requests_total", "counter", "Total id generation requests", stats.total_requests.to_string()),
+        ("snowflake_generations_success_total", "counter", "Ids generated successfully", stats.successful_generations.to_string()),
+        ("snowflake_generations_failed_total", "counter", "Failed id generations", stats.failed_generations.to_string()),
+        ("snowflake_uptime_seconds", "gauge", "Seconds since the server started", stats.start_time.elapsed().as_secs_f64().to_string()),
+    ] {
+        body.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name}{{{labels}}} {value}\n"));
+    }
+
+    ([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], body)
+}
+
 /// Parse a snowflake ID and return its components
-async fn parse_id(Path(id): Path<u64>) -> Result<Json<ParseResponse>, StatusCode> {
-    let info = Snowflake::parse_id(id);
-    
-    Ok(Json(ParseResponse {
-        id: info.id,
-        id_hex: info.id_as_hex(),
-        timestamp: info.timestamp,
-        timestamp_formatted: info.timestamp_as_string(),
-        datacenter_id: info.datacenter_id,
-        worker_id: info.worker_id,
-        sequence: info.sequence,
-        details: info.format_details(),
-    }))
+/// Accepts decimal, `0x`-prefixed hex, or binary notation
+async fn parse_id(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<ParseResponse>, StatusCode> {
+    let id = id.parse::<SnowflakeInfo>().map_err(|_| StatusCode::BAD_REQUEST)?.id;
+    let info = Snowflake::try_parse_id_with_epoch(id, state.epoch_millis, &state.id_filter)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    Ok(Json(ParseResponse::from(info)))
+}
+
+/// Encode a snowflake ID as a compact URL-friendly string
+async fn encode_id(
+    Path(id): Path<String>,
+    Query(query): Query<EncodeQuery>,
+) -> Result<Json<EncodeResponse>, StatusCode> {
+    let info = id.parse::<SnowflakeInfo>().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let alphabet = query.alphabet.unwrap_or_default();
+    let encoded = match alphabet {
+        Alphabet::Base62 => info.id_as_base62(),
+        Alphabet::Base58 => encode_base58(info.id),
+    };
+    Ok(Json(EncodeResponse { id: info.id, alphabet, encoded }))
+}
+
+/// Parse a JSON array of snowflake IDs, preserving input order
+///
+/// Each id is validated like the single parse endpoint; an invalid id gets an
+/// `{ id, error }` entry in its place instead of failing the whole batch.
+/// Ids from sources outside the allowlist still reject the whole batch.
+async fn parse_batch(
+    State(state): State<AppState>,
+    Json(ids): Json<Vec<u64>>,
+) -> Result<Json<Vec<ParseBatchItem>>, StatusCode> {
+    if ids.len() > state.max_parse_batch {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let parsed = Snowflake::parse_ids_with_epoch(&ids, state.epoch_millis);
+    if parsed.iter().any(|info| state.id_filter.check(info).is_err()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let items = parsed
+        .into_iter()
+        .map(|info| match validate_snowflake_id(info.id, state.epoch_millis) {
+            Ok(()) => ParseBatchItem::Parsed(ParseResponse::from(info)),
+            Err(err) => ParseBatchItem::Invalid { id: info.id, error: err.to_string() },
+        })
+        .collect();
+    Ok(Json(items))
+}
+
+/// Build the parse endpoints' id filter from the allowlist flags
+fn id_filter_from_args(args: &Args) -> IdFilter {
+    let mut filter = IdFilter::default();
+    if !args.allow_datacenters.is_empty() {
+        info!("Parse endpoints accept datacenters: {:?}", args.allow_datacenters);
+        filter = filter.allow_datacenters(args.allow_datacenters.iter().copied());
+    }
+    if !args.allow_workers.is_empty() {
+        info!("Parse endpoints accept workers: {:?}", args.allow_workers);
+        filter = filter.allow_workers(args.allow_workers.iter().copied());
+    }
+    filter
+}
+
+/// Create snowflake generator based on command line arguments
+fn create_snowflake(args: &Args) -> Result<Snowflake, WorkerError> {
+    let snowflake = if let Some(ref config_file) = args.config_file {
+        info!("Using configuration file: {}", config_file);
+        match Snowflake::new_with_config(config_file, args.datacenter_id) {
+            Ok(mut sf) => {
+                info!("Persist interval: {} ms", args.persist_interval_ms);
+                sf.set_persist_interval_ms(args.persist_interval_ms);
+                sf
+            }
+            // Another process owns this worker ID, or the file holds one this build cannot read;
+            // falling back would risk duplicate ids
+            Err(e @ (WorkerError::AlreadyLocked(_) | WorkerError::UnsupportedFormat(_))) => return Err(e),
+            Err(e) => {
+                warn!("Failed to load config file, falling back to default: {}", e);
+                Snowflake::new(args.worker_id, args.datacenter_id)
+            }
+        }
+    } else {
+        Snowflake::new(args.worker_id, args.datacenter_id)
+    };
+    snowflake.with_epoch(args.epoch_millis)
+}
+
+/// Resolve when the process receives Ctrl-C or (on Unix) SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl-C: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!("Failed to listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C, shutting down"),
+        _ = terminate => info!("Received SIGTERM, shutting down"),
+    }
+}
+
+/// Periodically warn when the last issued id's timestamp drifts from the system clock,
+/// which means the time provider is stuck or the clock jumped
+fn spawn_drift_alarm(state: AppState, threshold_ms: u64) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(DRIFT_CHECK_INTERVAL);
+        let mut last_generations = 0;
+        loop {
+            ticker.tick().await;
+            if let Some(drift) = state.check_drift(threshold_ms, &mut last_generations) {
+                warn!(
+                    "Last issued ID timestamp drifts {} ms from the system clock (threshold {} ms)",
+                    drift, threshold_ms
+                );
+            }
+        }
+    });
+}
+
+/// Warn once at startup when ids issued now would lose precision as JSON
+/// numbers in JavaScript clients
+fn warn_if_ids_exceed_js_safe_integer(epoch_millis: u64) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let smallest_id = build_snowflake_id_with_epoch(now.max(epoch_millis), epoch_millis, 0, 0, 0);
+    if Snowflake::exceeds_js_safe_integer(smallest_id) {
+        warn!(
+            "Generated IDs exceed JavaScript's safe integer range (2^53 - 1); \
+             JS clients should request string serialization, e.g. GET /id?as=string"
+        );
+    }
 }
 
 #[tokio::main]
@@ -263,38 +985,43 @@ async fn main() -> anyhow::Result<()> {
     );
 
     // Create snowflake generator based on configuration
-    let snowflake = if let Some(config_file) = args.config_file {
-        info!("Using configuration file: {}", config_file);
-        match Snowflake::new_with_config(&config_file, args.datacenter_id) {
-            Ok(sf) => sf,
-            Err(e) => {
-                warn!("Failed to load config file, falling back to default: {}", e);
-                Snowflake::new(args.worker_id, args.datacenter_id)
-            }
-        }
-    } else {
-        Snowflake::new(args.worker_id, args.datacenter_id)
-    };
+    let snowflake = create_snowflake(&args)?;
+    info!("Epoch: {} ms", snowflake.get_epoch_millis());
+    warn_if_ids_exceed_js_safe_integer(snowflake.get_epoch_millis());
 
-    // Create application state
-    let state = AppState {
-        snowflake: Arc::new(Mutex::new(snowflake)),
-        stats: Arc::new(Mutex::new(ServerStats::new())),
-    };
+    // Create application state; generation failures are also emitted on a
+    // dedicated tracing target so they can be routed to alerting
+    let state = AppState::new(snowflake)
+        .with_max_parse_batch(args.max_parse_batch)
+        .with_trace_routing(args.trace_routing)
+        .with_id_filter(id_filter_from_args(&args))
+        .with_on_generation_error(|err| {
+            error!(target: "snowflake_server::generation_errors", "ID generation failed: {}", err);
+        });
+
+    if args.drift_alarm_ms > 0 {
+        spawn_drift_alarm(state.clone(), args.drift_alarm_ms);
+    }
 
     // Build our application with routes
     let app = Router::new()
         .route("/health", get(health))
+        .route("/health/detailed", get(health_detailed))
         .route("/id", get(generate_id))
         .route("/batch", get(generate_batch))
+        .route("/reserve", post(reserve_ids))
+        .route("/verify", get(verify))
         .route("/stats", get(get_stats))
+        .route("/metrics", get(metrics))
+        .route("/parse/batch", post(parse_batch))
         .route("/parse/:id", get(parse_id))
+        .route("/encode/:id", get(encode_id))
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
                 .layer(CorsLayer::permissive()),
         )
-        .with_state(state);
+        .with_state(state.clone());
 
     // Create listener
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", args.host, args.port)).await?;
@@ -302,13 +1029,496 @@ async fn main() -> anyhow::Result<()> {
     info!("Server running on http://{}:{}", args.host, args.port);
     info!("Available endpoints:");
     info!("  GET /health - Health check");
-    info!("  GET /id - Generate single snowflake ID");
-    info!("  GET /batch?count=N - Generate batch of IDs (max 1000)");
+    info!("  GET /health/detailed - Aggregated generator diagnostics");
+    info!("  GET /id?as=number|string|hex|base62 - Generate single snowflake ID");
+    info!("  GET /batch?count=N&strict=true - Generate batch of IDs (max 1000; strict rejects larger counts)");
+    info!("  POST /reserve?count=N - Reserve a contiguous ID range (max 4096)");
+    info!("  GET /verify?count=N - Generate and self-verify a batch (max 1000)");
     info!("  GET /stats - Server statistics");
+    info!("  GET /metrics - Prometheus metrics");
     info!("  GET /parse/:id - Parse snowflake ID");
+    info!("  POST /parse/batch - Parse a JSON array of IDs (max {})", args.max_parse_batch);
+    info!("  GET /encode/:id?alphabet=base62|base58 - Encode ID as a compact string");
 
-    // Start the server
-    axum::serve(listener, app).await?;
+    // Start the server; in-flight requests finish before it returns
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    if let Err(err) = state.shutdown() {
+        error!("Failed to persist worker state on shutdown: {}", err);
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snowflake_generator::{
+        build_snowflake_id, decode_base58, decode_base62, CachedTimeProvider, IdQuota, TimeProvider,
+    };
+
+    #[tokio::test]
+    async fn test_generation_error_callback() {
+        // The last issued id is an hour ahead, so generation hits clock-backwards detection
+        let provider = CachedTimeProvider::new(1);
+        let future = build_snowflake_id(provider.current_millis() + 3_600_000, 1, 1, 0);
+        let snowflake = Snowflake::from_existing_id(future, provider).unwrap();
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let sink = errors.clone();
+        let state = AppState::new(snowflake)
+            .with_on_generation_error(move |err| sink.lock().unwrap().push(err.to_string()));
+
+        let result = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await;
+        assert_eq!(result.err(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+        let result = generate_batch(HeaderMap::new(), Query(BatchQuery { count: Some(5), ..Default::default() }), State(state.clone())).await;
+        assert_eq!(result.err().map(|(code, _)| code), Some(StatusCode::INTERNAL_SERVER_ERROR));
+
+        let (code, Json(report)) = health_detailed(State(state.clone())).await;
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report.status, "unhealthy");
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|msg| msg.starts_with("Clock backwards error")));
+        assert_eq!(state.stats.lock().unwrap().failed_generations, 6);
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_generator_lock_contention() {
+        let state = AppState::new(Snowflake::new(1, 1));
+        assert!(generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await.is_ok());
+        let (_, Json(report)) = health_detailed(State(state.clone())).await;
+        assert_eq!(report.lock_contentions, 0);
+
+        // Another request holds the generator while this one asks for an id
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let holder = {
+            let snowflake = state.snowflake.clone();
+            std::thread::spawn(move || {
+                let _guard = snowflake.lock().unwrap();
+                locked_tx.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(50));
+            })
+        };
+        locked_rx.recv().unwrap();
+        assert!(generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await.is_ok());
+        holder.join().unwrap();
+
+        let (code, Json(report)) = health_detailed(State(state)).await;
+        assert_eq!(report.lock_contentions, 1);
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(report.status, "healthy");
+    }
+
+    #[tokio::test]
+    async fn test_strict_batch_rejects_oversized_count() {
+        let state = AppState::new(Snowflake::new(1, 1));
+        let oversized = MAX_BATCH_SIZE + 1;
+
+        // Clamping stays the default
+        let query = BatchQuery { count: Some(oversized), ..Default::default() };
+        let Json(batch) = generate_batch(HeaderMap::new(), Query(query), State(state.clone())).await.unwrap();
+        assert_eq!(batch.count, MAX_BATCH_SIZE);
+
+        let query = BatchQuery { count: Some(oversized), strict: true };
+        let (code, message) = generate_batch(HeaderMap::new(), Query(query), State(state.clone())).await.err().unwrap();
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+        assert!(message.contains(&oversized.to_string()));
+
+        let query = BatchQuery { count: Some(MAX_BATCH_SIZE), strict: true };
+        assert!(generate_batch(HeaderMap::new(), Query(query), State(state)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_batch_partial_failure_stats() {
+        // The quota runs out after 4 ids, so the batch fails midway
+        let mut snowflake = Snowflake::new(1, 1);
+        snowflake.set_quota(Some(IdQuota::lifetime(4)));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let sink = errors.clone();
+        let state = AppState::new(snowflake).with_on_generation_error(move |err| sink.lock().unwrap().push(err.to_string()));
+
+        let Json(batch) = generate_batch(HeaderMap::new(), Query(BatchQuery { count: Some(10), ..Default::default() }), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(batch.count, 4);
+
+        // Nothing left at all: the whole batch fails
+        let result = generate_batch(HeaderMap::new(), Query(BatchQuery { count: Some(3), ..Default::default() }), State(state.clone())).await;
+        assert_eq!(result.err().map(|(code, _)| code), Some(StatusCode::INTERNAL_SERVER_ERROR));
+
+        let stats = state.stats.lock().unwrap();
+        assert_eq!(stats.total_requests, 2);
+        assert_eq!(stats.successful_generations, 4);
+        assert_eq!(stats.failed_generations, 6 + 3);
+        assert_eq!(errors.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_prometheus_format() {
+        let state = AppState::new(Snowflake::new(3, 2));
+        for _ in 0..3 {
+            let _ = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await.unwrap();
+        }
+
+        let ([(name, content_type)], body) = metrics(State(state)).await;
+        assert_eq!(name, header::CONTENT_TYPE);
+        assert_eq!(content_type, PROMETHEUS_CONTENT_TYPE);
+        assert!(body.contains("# TYPE snowflake_requests_total counter\n"));
+        assert!(body.contains("snowflake_requests_total{worker_id=\"3\",datacenter_id=\"2\"} 3\n"));
+        assert!(body.contains("snowflake_generations_success_total{worker_id=\"3\",datacenter_id=\"2\"} 3\n"));
+        assert!(body.contains("snowflake_generations_failed_total{worker_id=\"3\",datacenter_id=\"2\"} 0\n"));
+        assert!(body.contains("# TYPE snowflake_uptime_seconds gauge\n"));
+        // Every sample line carries both labels
+        assert_eq!(body.lines().filter(|line| !line.starts_with('#')).count(), 4);
+        assert!(body.lines().filter(|line| !line.starts_with('#')).all(|line| line.contains("{worker_id=\"3\",datacenter_id=\"2\"} ")));
+    }
+
+    #[tokio::test]
+    async fn test_stats_latency_percentiles() {
+        let state = AppState::new(Snowflake::new(1, 1));
+
+        let Json(stats) = get_stats(State(state.clone())).await;
+        assert_eq!(stats.latency.samples, 0);
+        assert!(stats.latency.p50_ns.is_none());
+
+        for _ in 0..5000 {
+            let _ = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await.unwrap();
+        }
+
+        let Json(stats) = get_stats(State(state)).await;
+        assert_eq!(stats.latency.samples, 5000);
+        let p50 = stats.latency.p50_ns.unwrap();
+        let p99 = stats.latency.p99_ns.unwrap();
+        let p999 = stats.latency.p999_ns.unwrap();
+        assert!(p50 <= p99 && p99 <= p999, "p50={} p99={} p999={}", p50, p99, p999);
+    }
+
+    #[test]
+    fn test_latency_histogram_window() {
+        let mut histogram = LatencyHistogram::new(3);
+        for nanos in [1, 2, 3, 100] {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+        let summary = histogram.summary();
+        assert_eq!(summary.samples, 3);
+        assert_eq!(summary.p50_ns, Some(3));
+        assert_eq!(summary.p999_ns, Some(100));
+    }
+
+    #[test]
+    fn test_persist_interval_arg() {
+        let args = Args::try_parse_from(["snowflake_server"]).unwrap();
+        assert_eq!(args.persist_interval_ms, DEFAULT_PERSIST_INTERVAL_MS);
+
+        let test_file = "test_server_persist.conf";
+        let _ = std::fs::remove_file(test_file);
+
+        let args = Args::try_parse_from([
+            "snowflake_server",
+            "--config-file",
+            test_file,
+            "--persist-interval-ms",
+            "250",
+        ])
+        .unwrap();
+        assert_eq!(args.persist_interval_ms, 250);
+
+        let snowflake = create_snowflake(&args).unwrap();
+        assert_eq!(snowflake.get_persist_interval_ms(), 250);
+
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_last_timestamp() {
+        let test_file = "test_server_shutdown.conf";
+        let _ = std::fs::remove_file(test_file);
+
+        let args = Args::try_parse_from([
+            "snowflake_server",
+            "--config-file",
+            test_file,
+            "--persist-interval-ms",
+            "3600000",
+        ])
+        .unwrap();
+        let state = AppState::new(create_snowflake(&args).unwrap());
+        let _ = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await.unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        let Json(response) = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone()))
+            .await
+            .unwrap();
+
+        // The second id falls inside the persistence interval and is not on disk yet
+        let read_info = || snowflake_generator::WorkerInfo::from_file_content(&std::fs::read_to_string(test_file).unwrap()).unwrap();
+        assert!(read_info().last_timestamp < response.timestamp);
+
+        state.shutdown().unwrap();
+        assert_eq!(read_info().last_timestamp, response.timestamp);
+
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[tokio::test]
+    async fn test_id_as_string() {
+        let state = AppState::new(Snowflake::new(1, 1));
+        let uri: axum::http::Uri = "http://localhost/id?as=string".parse().unwrap();
+        let query = Query::<IdQuery>::try_from_uri(&uri).unwrap();
+
+        let Json(response) = generate_id(HeaderMap::new(), query, State(state.clone())).await.unwrap();
+        let json = serde_json::to_value(&response).unwrap();
+        let id_text = json["id"].as_str().expect("id should be a JSON string");
+        let id: u64 = id_text.parse().unwrap();
+        assert_eq!(json["timestamp"].as_u64().unwrap(), Snowflake::parse_id(id).timestamp);
+
+        let Json(response) = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state)).await.unwrap();
+        let json = serde_json::to_value(&response).unwrap();
+        assert!(json["id"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_custom_epoch() {
+        let args = Args::try_parse_from(["snowflake_server", "--epoch-millis", "1420070400000"]).unwrap();
+        let state = AppState::new(create_snowflake(&args).unwrap());
+        assert_eq!(state.epoch_millis, 1420070400000);
+
+        let Json(generated) = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await.unwrap();
+        let id = serde_json::to_value(&generated).unwrap()["id"].as_u64().unwrap();
+        let Json(parsed) = parse_id(Path(id.to_string()), State(state)).await.unwrap();
+        assert_eq!(parsed.timestamp, generated.timestamp);
+        assert_eq!(parsed.timestamp, Snowflake::parse_id_with_epoch(id, 1420070400000).timestamp);
+
+        let args = Args::try_parse_from(["snowflake_server", "--epoch-millis", "99999999999999"]).unwrap();
+        assert!(create_snowflake(&args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_batch_matches_single_parse() {
+        let mut snowflake = Snowflake::new(4, 2);
+        let ids = snowflake.next_ids(5).unwrap();
+        let state = AppState::new(snowflake).with_max_parse_batch(5);
+
+        let Json(parsed) = parse_batch(State(state.clone()), Json(ids.clone())).await.unwrap();
+        assert_eq!(parsed.len(), ids.len());
+        for (item, &id) in parsed.iter().zip(&ids) {
+            let Json(single) = parse_id(Path(id.to_string()), State(state.clone())).await.unwrap();
+            assert_eq!(serde_json::to_value(item).unwrap(), serde_json::to_value(&single).unwrap());
+            let ParseBatchItem::Parsed(response) = item else { panic!("expected a parsed id") };
+            assert_eq!((response.worker_id, response.datacenter_id), (4, 2));
+        }
+
+        let mut oversized = ids.clone();
+        oversized.push(ids[0]);
+        let status = parse_batch(State(state), Json(oversized)).await.err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_parse_batch_reports_invalid_ids_per_item() {
+        let state = AppState::new(Snowflake::new(1, 1));
+        let valid = Snowflake::new(1, 1).next_id().unwrap();
+        let signed = u64::MAX;
+
+        let Json(parsed) = parse_batch(State(state), Json(vec![valid, signed])).await.unwrap();
+        assert!(matches!(&parsed[0], ParseBatchItem::Parsed(response) if response.id == valid));
+        let json = serde_json::to_value(&parsed[1]).unwrap();
+        assert_eq!(json["id"].as_u64(), Some(signed));
+        assert!(json["error"].as_str().unwrap().contains("sign bit"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_endpoints_reject_disallowed_datacenter() {
+        let allowed = Snowflake::new(1, 2).next_id().unwrap();
+        let foreign = Snowflake::new(1, 9).next_id().unwrap();
+        let state = AppState::new(Snowflake::new(1, 2))
+            .with_id_filter(IdFilter::default().allow_datacenters([2]));
+
+        let Json(parsed) = parse_id(Path(allowed.to_string()), State(state.clone())).await.unwrap();
+        assert_eq!(parsed.datacenter_id, 2);
+        let status = parse_id(Path(foreign.to_string()), State(state.clone())).await.err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+
+        assert!(parse_batch(State(state.clone()), Json(vec![allowed])).await.is_ok());
+        let status = parse_batch(State(state), Json(vec![allowed, foreign])).await.err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_parse_id_notations() {
+        let state = AppState::new(Snowflake::new(1, 1));
+        let id: u64 = 596623079686410240;
+        for text in [id.to_string(), format!("0x{:x}", id), format!("{:064b}", id)] {
+            let Json(parsed) = parse_id(Path(text), State(state.clone())).await.unwrap();
+            assert_eq!(parsed.id, id);
+        }
+
+        let status = parse_id(Path("not-an-id".to_string()), State(state.clone())).await.err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+
+        // Well-formed notation but not a valid snowflake: sign bit set
+        let status = parse_id(Path(format!("0x{:x}", u64::MAX)), State(state)).await.err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_encode_id_alphabets() {
+        let id: u64 = 596623079686410240;
+        let Json(response) = encode_id(Path(id.to_string()), Query(EncodeQuery::default())).await.unwrap();
+        assert_eq!(response.alphabet, Alphabet::Base62);
+        assert_eq!(decode_base62(&response.encoded), Some(id));
+
+        let query = EncodeQuery { alphabet: Some(Alphabet::Base58) };
+        let Json(response) = encode_id(Path(id.to_string()), Query(query)).await.unwrap();
+        assert_eq!(decode_base58(&response.encoded), Some(id));
+
+        let status = encode_id(Path("not-an-id".to_string()), Query(EncodeQuery::default())).await.err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_drift_alarm_with_stalled_clock() {
+        // An hour-long refresh interval leaves the cached clock stuck at its creation time
+        let stalled = CachedTimeProvider::new(3_600_000);
+        let stalled_millis = stalled.current_millis();
+        let state = AppState::new(Snowflake::with_time_provider(1, 1, stalled.clone()));
+        let mut last_generations = 0;
+
+        // Nothing generated yet, so no alarm
+        assert_eq!(state.check_drift(50, &mut last_generations), None);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let Json(response) = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone())).await.unwrap();
+        assert_eq!(response.timestamp, stalled_millis);
+        let drift = state.check_drift(50, &mut last_generations).unwrap();
+        assert!(drift >= 100);
+        assert_eq!(state.check_drift(50, &mut last_generations), None);
+
+        let Json(stats) = get_stats(State(state)).await;
+        assert!(stats.last_id_drift_ms.unwrap() >= drift);
+    }
+
+    #[tokio::test]
+    async fn test_verify_ok() {
+        let state = AppState::new(Snowflake::new(1, 1));
+        let Json(response) = verify(Query(BatchQuery { count: Some(500), ..Default::default() }), State(state.clone()))
+            .await
+            .unwrap();
+
+        assert!(response.ok);
+        assert_eq!(response.generated, 500);
+        assert!(response.duplicates.is_empty());
+        assert_eq!(response.non_monotonic, 0);
+        assert_eq!(state.stats.lock().unwrap().successful_generations, 500);
+    }
+
+    fn traceparent(trace_id: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", format!("00-{}-00f067aa0ba902b7-01", trace_id).parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_trace_tag_parsing() {
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let expected = (fnv1a_hash(trace_id.as_bytes()) & MAX_TYPE_TAG) as u8;
+        assert_eq!(trace_tag(&traceparent(trace_id)), Some(expected));
+
+        assert_eq!(trace_tag(&HeaderMap::new()), None);
+        assert_eq!(trace_tag(&traceparent("00000000000000000000000000000000")), None);
+        assert_eq!(trace_tag(&traceparent("4BF92F3577B34DA6A3CE929D0E0E4736")), None);
+        assert_eq!(trace_tag(&traceparent("4bf92f3577b34da6")), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", format!("01-{}-00f067aa0ba902b7-01-extra", trace_id).parse().unwrap());
+        assert_eq!(trace_tag(&headers), Some(expected));
+        headers.insert("traceparent", format!("00-{}-00f067aa0ba902b7-01-extra", trace_id).parse().unwrap());
+        assert_eq!(trace_tag(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_trace_routing_ids_share_trace_bits() {
+        // Two traces whose ids hash to different tags
+        let (trace_a, trace_b) = ("4bf92f3577b34da6a3ce929d0e0e4736", "0af7651916cd43dd8448eb211c80319c");
+        let tag_a = trace_tag(&traceparent(trace_a)).unwrap();
+        let tag_b = trace_tag(&traceparent(trace_b)).unwrap();
+        assert_ne!(tag_a, tag_b);
+
+        let state = AppState::new(Snowflake::new(1, 1)).with_trace_routing(true);
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..3 {
+            for (trace, tag) in [(trace_a, tag_a), (trace_b, tag_b)] {
+                let Json(response) = generate_id(traceparent(trace), Query(IdQuery::default()), State(state.clone()))
+                    .await
+                    .unwrap();
+                let IdValue::Number(id) = response.id else { panic!("expected a numeric id") };
+                assert_eq!(response.trace_tag, Some(tag));
+                assert_eq!(Snowflake::parse_id(id).kind(), tag);
+                assert!(seen.insert(id));
+            }
+        }
+
+        let Json(batch) = generate_batch(traceparent(trace_a), Query(BatchQuery { count: Some(50), ..Default::default() }), State(state.clone()))
+            .await
+            .unwrap();
+        assert!(batch.ids.iter().all(|&id| Snowflake::parse_id(id).kind() == tag_a));
+        assert!(batch.ids.iter().all(|&id| seen.insert(id)));
+
+        // Without a trace header ids are untyped and still never collide with typed ones
+        let Json(response) = generate_id(HeaderMap::new(), Query(IdQuery::default()), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.trace_tag, None);
+        let IdValue::Number(id) = response.id else { panic!("expected a numeric id") };
+        assert!(seen.insert(id));
+
+        let status = reserve_ids(Query(ReserveQuery { count: Some(10) }), State(state)).await.err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_sequence_exhaustions() {
+        let state = AppState::new(Snowflake::new(1, 1));
+        let Json(stats) = get_stats(State(state.clone())).await;
+        assert_eq!(stats.sequence_exhaustions, 0);
+
+        // Typed ids only have 512 sequence values per millisecond, so a batch
+        // of 1000 is guaranteed to wait for the next millisecond at least once
+        let state = state.with_trace_routing(true);
+        let headers = traceparent("4bf92f3577b34da6a3ce929d0e0e4736");
+        let Json(batch) = generate_batch(headers, Query(BatchQuery { count: Some(1000), ..Default::default() }), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(batch.count, 1000);
+
+        let exhaustions = state.snowflake.lock().unwrap().get_rollover_count();
+        assert!(exhaustions >= 1);
+        let Json(stats) = get_stats(State(state)).await;
+        assert_eq!(stats.sequence_exhaustions, exhaustions);
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["sequence_exhaustions"].as_u64(), Some(exhaustions));
+    }
+
+    #[tokio::test]
+    async fn test_reserve_ranges_do_not_overlap() {
+        let state = AppState::new(Snowflake::new(1, 1));
+
+        let Json(first) = reserve_ids(Query(ReserveQuery { count: Some(100) }), State(state.clone()))
+            .await
+            .unwrap();
+        let Json(second) = reserve_ids(Query(ReserveQuery { count: Some(100) }), State(state.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(first.end - first.start + 1, 100);
+        assert!(second.start > first.end);
+
+        let status = reserve_ids(Query(ReserveQuery { count: Some(5000) }), State(state))
+            .await
+            .err();
+        assert_eq!(status, Some(StatusCode::BAD_REQUEST));
+    }
+}
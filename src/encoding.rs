@@ -0,0 +1,138 @@
+//! 雪花ID的字符串编码
+//! 
+//! 十进制的雪花ID长达19位，放在URL里既长又难看；这里提供更紧凑的编码。
+//! 另提供带布局标签的二进制信封，供不了解发送方位布局的接收方使用。
+
+use crate::worker_manager::WorkerError;
+
+const BASE62_ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// 比特币风格的 base58 字母表，去掉了易混淆的 0、O、I、l
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// 将ID编码为 base62 字符串（0-9A-Za-z）
+pub fn encode_base62(id: u64) -> String {
+    encode_with_alphabet(id, BASE62_ALPHABET)
+}
+
+/// 解码 base62 字符串；为空、含字母表外字符或超出 `u64` 范围时返回 `None`
+pub fn decode_base62(s: &str) -> Option<u64> {
+    decode_with_alphabet(s, BASE62_ALPHABET)
+}
+
+/// 将ID编码为比特币字母表的 base58 字符串
+pub fn encode_base58(id: u64) -> String {
+    encode_with_alphabet(id, BASE58_ALPHABET)
+}
+
+/// 解码 base58 字符串；为空、含字母表外字符或超出 `u64` 范围时返回 `None`
+pub fn decode_base58(s: &str) -> Option<u64> {
+    decode_with_alphabet(s, BASE58_ALPHABET)
+}
+
+fn encode_with_alphabet(mut value: u64, alphabet: &[u8]) -> String {
+    let base = alphabet.len() as u64;
+    if value == 0 {
+        return (alphabet[0] as char).to_string();
+    }
+
+    let mut digits = Vec::new();
+    while value > 0 {
+        digits.push(alphabet[(value % base) as usize]);
+        value /= base;
+    }
+    digits.reverse();
+    String::from_utf8(digits).expect("alphabet is ASCII")
+}
+
+fn decode_with_alphabet(s: &str, alphabet: &[u8]) -> Option<u64> {
+    if s.is_empty() {
+        return None;
+    }
+
+    let base = alphabet.len() as u64;
+    s.bytes().try_fold(0u64, |value, byte| {
+        let digit = alphabet.iter().position(|&c| c == byte)? as u64;
+        value.checked_mul(base)?.checked_add(digit)
+    })
+}
+
+/// 二进制信封长度：1字节布局标签 + 8字节大端序ID
+pub const WIRE_LEN: usize = 9;
+
+/// 二进制信封中的布局标签，接收方据此查找发送方使用的位布局与纪元
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayoutTag(pub u8);
+
+impl LayoutTag {
+    /// 本库默认的 41/5/5/12 布局与 `EPOCH`
+    pub const DEFAULT: LayoutTag = LayoutTag(0);
+}
+
+/// 将ID与布局标签编码为 `WIRE_LEN` 字节的二进制信封
+pub fn encode_wire(id: u64, layout_tag: LayoutTag) -> [u8; WIRE_LEN] {
+    let mut buf = [0u8; WIRE_LEN];
+    buf[0] = layout_tag.0;
+    buf[1..].copy_from_slice(&id.to_be_bytes());
+    buf
+}
+
+/// 解码 `encode_wire` 生成的二进制信封，长度不为 `WIRE_LEN` 时返回 `ParseError`
+pub fn decode_wire(buf: &[u8]) -> Result<(u64, LayoutTag), WorkerError> {
+    let buf: &[u8; WIRE_LEN] = buf.try_into().map_err(|_| {
+        WorkerError::ParseError(format!("wire id must be {} bytes, got {}", WIRE_LEN, buf.len()))
+    })?;
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&buf[1..]);
+    Ok((u64::from_be_bytes(id), LayoutTag(buf[0])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_base62() {
+        assert_eq!(encode_base62(0), "0");
+        assert_eq!(encode_base62(61), "z");
+        assert_eq!(encode_base62(62), "10");
+        assert_eq!(encode_base62(u64::MAX), "LygHa16AHYF");
+    }
+
+    #[test]
+    fn test_base62_base58_round_trip() {
+        let samples = [0, 1, 57, 58, 61, 62, 596623079686410240, u64::MAX - 1, u64::MAX];
+        for id in samples {
+            assert_eq!(decode_base62(&encode_base62(id)), Some(id));
+            assert_eq!(decode_base58(&encode_base58(id)), Some(id));
+        }
+        assert_eq!(encode_base58(0), "1");
+        assert_eq!(encode_base58(58), "21");
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_input() {
+        assert_eq!(decode_base62(""), None);
+        assert_eq!(decode_base62("abc-1"), None);
+        assert_eq!(decode_base58("0"), None);
+        assert_eq!(decode_base58("OIl"), None);
+        // 比 u64::MAX 多一位
+        assert_eq!(decode_base62("LygHa16AHYG"), None);
+        assert_eq!(decode_base62("zzzzzzzzzzzz"), None);
+    }
+
+    #[test]
+    fn test_wire_round_trip() {
+        let id = 596623079686410240;
+        let buf = encode_wire(id, LayoutTag(7));
+        assert_eq!(buf[0], 7);
+        assert_eq!(decode_wire(&buf).unwrap(), (id, LayoutTag(7)));
+        assert_eq!(decode_wire(&encode_wire(u64::MAX, LayoutTag::DEFAULT)).unwrap(), (u64::MAX, LayoutTag::DEFAULT));
+    }
+
+    #[test]
+    fn test_wire_truncated() {
+        let buf = encode_wire(42, LayoutTag::DEFAULT);
+        assert!(matches!(decode_wire(&buf[..WIRE_LEN - 1]), Err(WorkerError::ParseError(_))));
+        assert!(decode_wire(&[]).is_err());
+    }
+}
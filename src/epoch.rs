@@ -0,0 +1,84 @@
+//! 多纪元识别
+//!
+//! 不同服务可能使用不同的纪元（EPOCH）签发ID，混合导入时需要判断每个ID属于哪个纪元。
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::snowflake::{Snowflake, SnowflakeInfo};
+
+/// 候选纪元注册表
+/// 
+/// 对给定ID按每个候选纪元解码，只保留时间戳不晚于当前时间的结果，
+/// 再从中选出时间戳最晚（即最接近当前）的一个。
+/// 这是启发式判断：纪元相近或ID很旧时可能误判。
+#[derive(Debug, Clone, Default)]
+pub struct EpochRegistry {
+    epochs: Vec<u64>,
+}
+
+impl EpochRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册一个候选纪元（毫秒时间戳），重复注册会被忽略
+    pub fn register(&mut self, epoch: u64) -> &mut Self {
+        if !self.epochs.contains(&epoch) {
+            self.epochs.push(epoch);
+        }
+        self
+    }
+
+    pub fn epochs(&self) -> &[u64] {
+        &self.epochs
+    }
+
+    /// 以当前系统时间为上限识别ID所属的纪元
+    pub fn decode(&self, id: u64) -> Option<(u64, SnowflakeInfo)> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.decode_at(id, now)
+    }
+
+    /// 以 `now_millis` 为上限识别ID所属的纪元，没有合理候选时返回 `None`
+    pub fn decode_at(&self, id: u64, now_millis: u64) -> Option<(u64, SnowflakeInfo)> {
+        self.epochs
+            .iter()
+            .map(|&epoch| (epoch, Snowflake::parse_id_with_epoch(id, epoch)))
+            .filter(|(_, info)| info.timestamp <= now_millis)
+            .max_by_key(|(_, info)| info.timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snowflake_core::*;
+
+    const TWITTER_EPOCH: u64 = 1288834974657;
+
+    #[test]
+    fn test_attributes_ids_to_their_epoch() {
+        let mut registry = EpochRegistry::new();
+        registry.register(EPOCH).register(TWITTER_EPOCH);
+
+        let mut sf = Snowflake::new(1, 1);
+        let ours = sf.next_id().unwrap();
+        let (epoch, info) = registry.decode(ours).unwrap();
+        assert_eq!(epoch, EPOCH);
+        assert_eq!(info.timestamp, extract_timestamp(ours));
+
+        let now = info.timestamp;
+        let theirs = ((now - TWITTER_EPOCH) << TIMESTAMP_SHIFT) | (3 << DATACENTER_ID_SHIFT) | 7;
+        let (epoch, info) = registry.decode_at(theirs, now).unwrap();
+        assert_eq!(epoch, TWITTER_EPOCH);
+        assert_eq!(info.timestamp, now);
+        assert_eq!(info.datacenter_id, 3);
+        assert_eq!(info.sequence, 7);
+
+        // 任何纪元下都落在未来的ID无法识别
+        assert!(registry.decode_at(u64::MAX >> 1, now).is_none());
+    }
+}
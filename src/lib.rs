@@ -1,9 +1,67 @@
+//! 雪花算法ID生成器
+//!
+//! 默认启用的 `std` feature 提供生成器、worker 管理与时间提供者等全部功能；
+//! 以 `default-features = false` 引入时只保留 `snowflake_core` 中的常量、位运算函数与
+//! `SnowflakeParts`，可在 `no_std` 且无 `alloc` 的环境中自行提供毫秒时钟生成ID。
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
 pub mod snowflake_core;
+#[cfg(feature = "std")]
 pub mod worker_manager;
+#[cfg(feature = "std")]
 pub mod snowflake;
+#[cfg(feature = "std")]
 pub mod time_provider;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod standby;
+#[cfg(feature = "std")]
+pub mod encoding;
+#[cfg(feature = "std")]
+pub mod epoch;
+#[cfg(feature = "std")]
+pub mod atomic;
+#[cfg(feature = "std")]
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod snowflake128;
+#[cfg(feature = "async")]
+pub mod async_snowflake;
+#[cfg(feature = "spsc")]
+pub mod spsc;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use snowflake_core::*;
-pub use worker_manager::{WorkerManager, WorkerError, WorkerInfo};
-pub use snowflake::{Snowflake, SnowflakeInfo};
-pub use time_provider::{CachedTimeProvider, TimeProvider, SystemTimeProvider, RelativeTimeProvider};
+#[cfg(feature = "std")]
+pub use worker_manager::{WorkerManager, WorkerError, WorkerInfo, WorkerIdHasher, WorkerIdStrategy, fnv1a_hash, mac_worker_id, derive_worker_id, parse_statefulset_ordinal};
+#[cfg(feature = "std")]
+pub use snowflake::{Snowflake, DefaultSnowflake, SnowflakeIter, SnowflakeInfo, ClockConsistency, SmoothingConfig, SnowflakeTimeRange, ClockBackwardsPolicy, IdReservation, IdQuota, IdFilter, IdPredicate, GenerationResult, GenerationCounter, HealthReport, HealthStatus, HealthThresholds, SpinStrategy, LayoutBenchmark, StorageComparison};
+#[cfg(feature = "std")]
+pub use time_provider::{CachedTimeProvider, TimeProvider, SystemTimeProvider, RelativeTimeProvider, SteppingTimeProvider, measure_resolution_gap};
+#[cfg(feature = "std")]
+pub use registry::DatacenterRegistry;
+#[cfg(feature = "std")]
+pub use standby::StandbySnowflake;
+#[cfg(feature = "std")]
+pub use encoding::{encode_base62, decode_base62, encode_base58, decode_base58, encode_wire, decode_wire, LayoutTag};
+#[cfg(feature = "std")]
+pub use epoch::EpochRegistry;
+#[cfg(feature = "std")]
+pub use atomic::AtomicSnowflake;
+#[cfg(feature = "std")]
+pub use shared::SharedSnowflake;
+#[cfg(feature = "std")]
+pub use profile::{FormatProfile, PROFILE_DIR};
+#[cfg(feature = "std")]
+pub use snowflake128::{Snowflake128, SnowflakeInfo128, Layout128, EPOCH_MICROS};
+#[cfg(feature = "async")]
+pub use async_snowflake::AsyncSnowflake;
+#[cfg(feature = "spsc")]
+pub use spsc::SpscIdReceiver;
+#[cfg(feature = "testing")]
+pub use testing::FaultInjectingSnowflake;
@@ -0,0 +1,184 @@
+//! 命名的ID格式预设（profile）
+//!
+//! 多个服务共用同一套位布局与纪元时，可将其集中定义在 `profiles/<name>.toml` 中，
+//! 按名称加载而无需重新编译。
+
+use std::fs;
+use std::path::Path;
+
+use crate::snowflake::{Snowflake, SnowflakeInfo};
+use crate::snowflake_core::SnowflakeLayout;
+use crate::worker_manager::WorkerError;
+
+/// `FormatProfile::load` 查找预设文件的默认目录
+pub const PROFILE_DIR: &str = "profiles";
+
+/// 从预设文件加载的纪元与位布局
+/// 
+/// 文件为TOML的一个扁平子集：每行 `key = value`，支持 `#` 注释与整数中的 `_` 分隔符。
+/// `epoch_millis` 必填；`timestamp_bits`/`datacenter_bits`/`worker_bits`/`sequence_bits`/`reserved_bits`
+/// 缺省时取默认布局的值，合计必须为63位；`description` 为可选说明，未知键视为错误以便发现拼写错误。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatProfile {
+    pub name: String,
+    pub description: Option<String>,
+    pub epoch_millis: u64,
+    pub layout: SnowflakeLayout,
+}
+
+impl FormatProfile {
+    /// 从 `PROFILE_DIR` 加载名为 `name` 的预设
+    /// 
+    /// `PROFILE_DIR` 是相对路径，按进程的当前工作目录解析；工作目录不固定时
+    /// （如作为系统服务运行）改用 `load_from_dir` 传入绝对路径。
+    pub fn load(name: &str) -> Result<Self, WorkerError> {
+        Self::load_from_dir(PROFILE_DIR, name)
+    }
+
+    /// 从 `dir/<name>.toml` 加载预设；名称不能包含路径分隔符
+    pub fn load_from_dir(dir: impl AsRef<Path>, name: &str) -> Result<Self, WorkerError> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(WorkerError::InvalidArgument(format!("invalid profile name '{}'", name)));
+        }
+        let path = dir.as_ref().join(format!("{}.toml", name));
+        let content = fs::read_to_string(&path)?;
+        Self::from_toml_str(name, &content)
+            .map_err(|e| WorkerError::ParseError(format!("{}: {}", path.display(), e)))
+    }
+
+    /// 解析预设文件内容
+    pub fn from_toml_str(name: &str, content: &str) -> Result<Self, String> {
+        let mut description = None;
+        let mut epoch_millis = None;
+        let mut layout = SnowflakeLayout::DEFAULT;
+
+        for (index, raw) in content.lines().enumerate() {
+            let line_no = index + 1;
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", line_no))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            if key == "description" {
+                let text = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .ok_or_else(|| format!("line {}: description must be a quoted string", line_no))?;
+                description = Some(text.to_string());
+                continue;
+            }
+
+            let number: u64 = value
+                .replace('_', "")
+                .parse()
+                .map_err(|_| format!("line {}: '{}' is not an unsigned integer", line_no, value))?;
+            match key {
+                "epoch_millis" => epoch_millis = Some(number),
+                "timestamp_bits" => layout.timestamp_bits = number,
+                "datacenter_bits" => layout.datacenter_bits = number,
+                "worker_bits" => layout.worker_bits = number,
+                "sequence_bits" => layout.sequence_bits = number,
+                "reserved_bits" => layout.reserved_bits = number,
+                _ => return Err(format!("line {}: unknown key '{}'", line_no, key)),
+            }
+        }
+
+        let epoch_millis = epoch_millis.ok_or("missing required key 'epoch_millis'")?;
+        if !layout.is_valid() {
+            return Err(format!("layout {:?} does not add up to 63 bits", layout));
+        }
+
+        Ok(FormatProfile { name: name.to_string(), description, epoch_millis, layout })
+    }
+
+    /// 按本预设的纪元与布局解析ID
+    pub fn parse_id(&self, id: u64) -> SnowflakeInfo {
+        Snowflake::parse_id_with_layout(id, self.layout, self.epoch_millis)
+    }
+}
+
+/// 去掉 `#` 开始的注释（引号内的 `#` 保留）
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snowflake_core::DISCORD_EPOCH;
+
+    #[test]
+    fn test_load_profile_from_file() {
+        let dir = "test_profiles_load";
+        fs::create_dir_all(dir).unwrap();
+        fs::write(
+            format!("{}/compact.toml", dir),
+            "# 43位时间戳、无数据中心\n\
+             description = \"compact # layout\"\n\
+             epoch_millis = 1_609_459_200_000\n\
+             timestamp_bits = 43  # 约278年\n\
+             datacenter_bits = 0\n\
+             worker_bits = 8\n\
+             sequence_bits = 12\n",
+        )
+        .unwrap();
+
+        let profile = FormatProfile::load_from_dir(dir, "compact").unwrap();
+        assert_eq!(profile.name, "compact");
+        assert_eq!(profile.description.as_deref(), Some("compact # layout"));
+        assert_eq!(profile.epoch_millis, 1_609_459_200_000);
+        assert_eq!(profile.layout, SnowflakeLayout::new(43, 0, 8, 12));
+
+        assert!(matches!(FormatProfile::load_from_dir(dir, "missing"), Err(WorkerError::IoError(_))));
+        assert!(matches!(FormatProfile::load_from_dir(dir, "../compact"), Err(WorkerError::InvalidArgument(_))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_malformed_profile() {
+        let dir = "test_profiles_malformed";
+        fs::create_dir_all(dir).unwrap();
+        // 位数之和溢出 u64，不能在校验布局时 panic
+        fs::write(
+            format!("{}/overflow.toml", dir),
+            "epoch_millis = 0\ntimestamp_bits = 18446744073709551615\n",
+        )
+        .unwrap();
+
+        match FormatProfile::load_from_dir(dir, "overflow") {
+            Err(WorkerError::ParseError(msg)) => assert!(msg.contains("63 bits"), "{}", msg),
+            other => panic!("expected ParseError, got {:?}", other),
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_shipped_discord_profile() {
+        let profile = FormatProfile::load("discord").unwrap();
+        assert_eq!(profile.epoch_millis, DISCORD_EPOCH);
+        assert_eq!(profile.layout, SnowflakeLayout::DEFAULT);
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        assert!(FormatProfile::from_toml_str("p", "timestamp_bits = 41").unwrap_err().contains("epoch_millis"));
+        assert!(FormatProfile::from_toml_str("p", "epoch_millis = 0\nworker_bits = 6").unwrap_err().contains("63 bits"));
+        assert!(FormatProfile::from_toml_str("p", "epoch_millis = 0\nworkers = 5").unwrap_err().contains("unknown key"));
+        assert!(FormatProfile::from_toml_str("p", "epoch_millis = -1").unwrap_err().contains("line 1"));
+        assert!(FormatProfile::from_toml_str("p", "[layout]").unwrap_err().contains("key = value"));
+    }
+}
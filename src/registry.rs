@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::snowflake::Snowflake;
+use crate::snowflake_core::validate_ids;
+use crate::worker_manager::WorkerError;
+
+/// 共享的单个生成器
+pub type SharedGenerator = Arc<Mutex<Snowflake>>;
+
+/// 按 (datacenter_id, worker_id) 惰性创建并缓存生成器的注册表
+/// 
+/// 适用于多租户应用：首次请求某个组合时才创建对应的 `Snowflake`，
+/// 之后复用同一实例。注册表本身只在查找时短暂加锁，
+/// 不同组合的生成互不阻塞。
+#[derive(Default)]
+pub struct DatacenterRegistry {
+    generators: Mutex<HashMap<(u64, u64), SharedGenerator>>,
+}
+
+impl DatacenterRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用指定数据中心和worker的生成器生成下一个ID
+    pub fn next_id(&self, datacenter_id: u64, worker_id: u64) -> Result<u64, WorkerError> {
+        let generator = self.get_or_create(datacenter_id, worker_id)?;
+        let mut snowflake = generator.lock().unwrap();
+        snowflake.next_id()
+    }
+
+    /// 获取（必要时创建）指定组合的生成器
+    pub fn get_or_create(&self, datacenter_id: u64, worker_id: u64) -> Result<SharedGenerator, WorkerError> {
+        validate_ids(worker_id, datacenter_id)?;
+
+        let mut generators = self.generators.lock().unwrap();
+        let generator = generators
+            .entry((datacenter_id, worker_id))
+            .or_insert_with(|| Arc::new(Mutex::new(Snowflake::new(worker_id, datacenter_id))));
+        Ok(generator.clone())
+    }
+
+    /// 已创建的生成器数量
+    pub fn len(&self) -> usize {
+        self.generators.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snowflake_core::{extract_datacenter_id, extract_worker_id};
+
+    #[test]
+    fn test_registry_per_datacenter() {
+        let registry = DatacenterRegistry::new();
+        assert!(registry.is_empty());
+
+        let id_a = registry.next_id(1, 5).unwrap();
+        let id_b = registry.next_id(2, 5).unwrap();
+        let id_a2 = registry.next_id(1, 5).unwrap();
+
+        assert_eq!(extract_datacenter_id(id_a), 1);
+        assert_eq!(extract_datacenter_id(id_b), 2);
+        assert_eq!(extract_worker_id(id_b), 5);
+        assert!(id_a2 > id_a);
+        assert_eq!(registry.len(), 2);
+
+        assert!(matches!(registry.next_id(32, 0), Err(WorkerError::InvalidId(_))));
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn test_registry_concurrent_access() {
+        let registry = Arc::new(DatacenterRegistry::new());
+        let handles: Vec<_> = (0..4)
+            .map(|dc| {
+                let registry = registry.clone();
+                std::thread::spawn(move || {
+                    (0..1000).map(|_| registry.next_id(dc, 1).unwrap()).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let mut all: Vec<u64> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        let total = all.len();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), total);
+        assert_eq!(registry.len(), 4);
+    }
+}
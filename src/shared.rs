@@ -0,0 +1,87 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::snowflake::Snowflake;
+use crate::worker_manager::WorkerError;
+
+/// 可在线程/任务间克隆共享的生成器句柄
+/// 
+/// 内部持有 `Arc<Mutex<Snowflake>>`，调用方无需自行加锁；克隆只复制 `Arc`，
+/// 所有句柄共享同一个生成器，因此签发的ID全局唯一且单调递增。
+/// 
+/// 每次 `next_id` 都要获取同一把互斥锁，临界区只有一次ID生成（通常几十纳秒），
+/// 少量线程共享时开销可以忽略；线程数很多且都在高频取号时锁竞争会成为瓶颈，
+/// 此时优先用 `next_ids` 一次取一批，或改用无锁的 `AtomicSnowflake`。
+#[derive(Clone)]
+pub struct SharedSnowflake {
+    inner: Arc<Mutex<Snowflake>>,
+}
+
+impl SharedSnowflake {
+    /// 创建共享生成器
+    /// 
+    /// # Panics
+    /// `worker_id` 或 `datacenter_id` 超出范围时 panic
+    pub fn new(worker_id: u64, datacenter_id: u64) -> Self {
+        Self::from(Snowflake::new(worker_id, datacenter_id))
+    }
+
+    /// 生成下一个ID
+    pub fn next_id(&self) -> Result<u64, WorkerError> {
+        self.lock().next_id()
+    }
+
+    /// 在一次加锁内连续生成 `count` 个ID
+    pub fn next_ids(&self, count: usize) -> Result<Vec<u64>, WorkerError> {
+        self.lock().next_ids(count)
+    }
+
+    /// 获取内部生成器的锁，用于调用其他 `&mut self` 方法
+    /// 
+    /// 持有守卫期间其他句柄的取号会被阻塞，应尽快释放。
+    pub fn lock(&self) -> MutexGuard<'_, Snowflake> {
+        self.inner.lock().unwrap()
+    }
+}
+
+impl From<Snowflake> for SharedSnowflake {
+    fn from(snowflake: Snowflake) -> Self {
+        SharedSnowflake {
+            inner: Arc::new(Mutex::new(snowflake)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_shared_snowflake_is_send_sync() {
+        assert_send_sync::<SharedSnowflake>();
+    }
+
+    #[test]
+    fn test_cloned_handles_generate_unique_ids() {
+        let shared = SharedSnowflake::new(1, 1);
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let mut ids: Vec<u64> = (0..1000).map(|_| shared.next_id().unwrap()).collect();
+                    ids.extend(shared.next_ids(100 * (i + 1)).unwrap());
+                    ids
+                })
+            })
+            .collect();
+
+        let mut all: Vec<u64> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        let total = all.len();
+        assert_eq!(total, 8 * 1000 + 100 * (1..=8).sum::<usize>());
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), total);
+        assert_eq!(shared.lock().get_worker_id(), 1);
+    }
+}
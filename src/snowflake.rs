@@ -1,8 +1,423 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::ops::{Bound, Range, RangeBounds};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Mutex, Arc};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::worker_manager::{WorkerManager, WorkerError};
-use crate::time_provider::{CachedTimeProvider, TimeProvider};
+use crate::worker_manager::{current_hostname, parse_statefulset_ordinal, WorkerIdStrategy, WorkerManager, WorkerError, WorkerInfo, RESTORE_GRACE_MS};
+use crate::time_provider::{measure_resolution_gap, CachedTimeProvider, RelativeTimeProvider, SteppingTimeProvider, TimeProvider};
 use crate::snowflake_core::*;
+use crate::encoding::encode_base62;
+use crate::profile::FormatProfile;
+
+/// 默认的 worker 配置文件持久化间隔（毫秒）
+pub const DEFAULT_PERSIST_INTERVAL_MS: u64 = 1000;
+
+/// JavaScript `Number` 能精确表示的最大整数（`Number.MAX_SAFE_INTEGER`，即 2^53 - 1）
+pub const JS_MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// 默认的时钟回拨最长等待时间，也是 `set_max_backwards_wait` 的默认值
+pub const DEFAULT_MAX_BACKWARDS_WAIT: Duration = Duration::from_secs(1);
+
+/// `Snowflake::new_after` 等待屏障时间戳的最长时间
+pub const MAX_BARRIER_WAIT: Duration = Duration::from_secs(60);
+
+/// 检测到时钟回拨时的处理策略
+/// 
+/// 不论哪种策略，上次时间戳领先于时钟且该毫秒序列号已用完时，等待时钟越过它最多
+/// `max_backwards_wait`（见 `Snowflake::set_max_backwards_wait`），超时返回 `ClockBackwardsError`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClockBackwardsPolicy {
+    /// 立即返回 `ClockBackwardsError`
+    #[default]
+    Error,
+    /// 等待时钟追上上次时间戳，超过给定时长仍未追上则返回错误
+    /// 
+    /// 等待期间持有生成器，回拨多少毫秒就阻塞多少毫秒，这段时间内吞吐为零；
+    /// 适合几毫秒级的NTP微调，不适合大幅回拨。
+    WaitUpTo(Duration),
+    /// 不采用回拨后的时间，继续沿用上次时间戳并递增序列号
+    /// 
+    /// 不阻塞也不报错，但整个回拨期间共享上次时间戳那一毫秒的序列号容量，
+    /// 用完后需等待时钟追上（最多 `max_backwards_wait`），吞吐相应下降；签发的时间戳可能略晚于真实时间。
+    RefuseAndReuseLast,
+}
+
+/// 序列号耗尽后等待下一毫秒的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpinStrategy {
+    /// 纯忙等，延迟最低但会占满一个核心
+    #[default]
+    Busy,
+    /// 每次检查之间休眠给定时长，以少量延迟换取显著更低的CPU占用
+    Sleep(Duration),
+    /// 先忙等 `HYBRID_BUSY_SPINS` 次，仍未进入下一毫秒再改为短暂休眠
+    Hybrid,
+}
+
+/// `SpinStrategy::Hybrid` 切换为休眠前的忙等次数
+pub const HYBRID_BUSY_SPINS: u64 = 100;
+
+/// `SpinStrategy::Hybrid` 忙等结束后每次休眠的时长
+const HYBRID_SLEEP: Duration = Duration::from_micros(50);
+
+/// 按 `strategy` 反复读取 `now` 直到越过 `last_timestamp`，返回最后读到的时间戳与重试次数
+/// 
+/// 超过 `max_wait` 仍未越过时提前返回，此时返回的时间戳不大于 `last_timestamp`。
+/// 64位与128位生成器共用，时间戳单位由 `now` 决定。
+pub(crate) fn wait_past(
+    last_timestamp: u64,
+    max_wait: Option<Duration>,
+    strategy: SpinStrategy,
+    now: impl Fn() -> u64,
+) -> (u64, u64) {
+    let start = Instant::now();
+    let mut spins = 0;
+    let mut ts = now();
+    while ts <= last_timestamp {
+        if max_wait.is_some_and(|max_wait| start.elapsed() >= max_wait) {
+            break;
+        }
+        match strategy {
+            SpinStrategy::Busy => {}
+            SpinStrategy::Sleep(nap) => std::thread::sleep(nap),
+            SpinStrategy::Hybrid if spins >= HYBRID_BUSY_SPINS => std::thread::sleep(HYBRID_SLEEP),
+            SpinStrategy::Hybrid => {}
+        }
+        ts = now();
+        spins += 1;
+    }
+    (ts, spins)
+}
+
+/// 检查 worker ID 与数据中心ID是否在 `layout` 的位宽范围内，超出时返回 `InvalidId`
+fn validate_layout_ids(layout: SnowflakeLayout, worker_id: u64, datacenter_id: u64) -> Result<(), WorkerError> {
+    let max_worker_id = layout.max_worker_id();
+    if worker_id > max_worker_id {
+        return Err(WorkerError::InvalidId(format!("worker_id {} exceeds maximum {}", worker_id, max_worker_id)));
+    }
+    let max_datacenter_id = layout.max_datacenter_id();
+    if datacenter_id > max_datacenter_id {
+        return Err(WorkerError::InvalidId(format!(
+            "datacenter_id {} exceeds maximum {}", datacenter_id, max_datacenter_id
+        )));
+    }
+    Ok(())
+}
+
+/// 按回拨策略处理读到的 `timestamp` 早于 `last_timestamp` 的情况，返回可用的时间戳
+/// 
+/// `WaitUpTo` 下每隔 `poll` 重新读取 `now`，最长等待给定时长；
+/// `unit` 附在错误信息中的时间戳之后（如 `" us"`）。64位与128位生成器共用。
+pub(crate) fn resolve_clock_backwards(
+    policy: ClockBackwardsPolicy,
+    last_timestamp: u64,
+    timestamp: u64,
+    poll: Duration,
+    unit: &str,
+    now: impl Fn() -> u64,
+) -> Result<u64, WorkerError> {
+    match policy {
+        ClockBackwardsPolicy::Error => Err(WorkerError::ClockBackwardsError(format!(
+            "Clock moved backwards. Last: {}{unit}, Current: {}{unit}", last_timestamp, timestamp
+        ))),
+        ClockBackwardsPolicy::RefuseAndReuseLast => Ok(last_timestamp),
+        ClockBackwardsPolicy::WaitUpTo(max_wait) => {
+            let start = Instant::now();
+            let mut ts = timestamp;
+            while ts < last_timestamp {
+                let elapsed = start.elapsed();
+                if elapsed >= max_wait {
+                    return Err(WorkerError::ClockBackwardsError(format!(
+                        "Clock did not catch up within {:?}. Last: {}{unit}, Current: {}{unit}",
+                        max_wait, last_timestamp, ts
+                    )));
+                }
+                std::thread::sleep((max_wait - elapsed).min(poll));
+                ts = now();
+            }
+            Ok(ts)
+        }
+    }
+}
+
+/// `Snowflake::benchmark_layouts` 模拟的时长（毫秒）
+pub const LAYOUT_SIMULATION_MILLIS: u64 = 1000;
+
+/// 可接受的序列号耗尽（需等待下一毫秒）毫秒占比上限
+pub const MAX_ROLLOVER_RATIO: f64 = 0.01;
+
+/// 单个候选位布局的模拟结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutBenchmark {
+    pub layout: SnowflakeLayout,
+    /// 节点数是否在该布局可区分的范围内
+    pub fits_nodes: bool,
+    /// 每个节点每毫秒的平均需求
+    pub demand_per_ms: f64,
+    /// 序列号耗尽、请求被推迟到后续毫秒的毫秒占比
+    pub rollover_ratio: f64,
+    /// 模拟结束时仍积压未签发的请求数
+    pub backlog: u64,
+    /// 时间戳字段可用的年数
+    pub lifetime_years: f64,
+    /// 是否能在可接受的自旋代价下维持目标速率
+    pub sustains: bool,
+}
+
+/// 雪花ID的字节数
+pub const SNOWFLAKE_KEY_BYTES: u64 = 8;
+/// UUID的字节数
+pub const UUID_KEY_BYTES: u64 = 16;
+/// 每个索引条目除主键外的开销（行指针与条目头）
+pub const INDEX_ENTRY_OVERHEAD_BYTES: u64 = 8;
+/// 顺序插入时索引叶子页的填充率（百分比）
+pub const SEQUENTIAL_FILL_PERCENT: u64 = 90;
+/// 随机插入时索引叶子页的稳态填充率（百分比）
+pub const RANDOM_FILL_PERCENT: u64 = 69;
+
+/// 雪花ID主键相对UUID的存储估算
+/// 
+/// 索引按B树叶子页估算：每个条目为主键加 `INDEX_ENTRY_OVERHEAD_BYTES` 的行指针/条目头，
+/// 再按页填充率折算。雪花ID随时间递增，插入总在最右侧叶子页，按 `SEQUENTIAL_FILL_PERCENT` 计；
+/// 随机UUID（v4）插入位置分散、频繁页分裂，稳态填充率约为 ln 2，按 `RANDOM_FILL_PERCENT` 计。
+/// 结果为数量级参考，实际大小取决于数据库的页格式与维护策略。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageComparison {
+    pub row_count: u64,
+    /// 雪花ID主键列的字节数（每行8字节）
+    pub snowflake_key_bytes: u64,
+    /// UUID主键列的字节数（每行16字节）
+    pub uuid_key_bytes: u64,
+    /// 雪花ID主键索引的估算字节数
+    pub snowflake_index_bytes: u64,
+    /// UUID主键索引的估算字节数
+    pub uuid_index_bytes: u64,
+}
+
+impl StorageComparison {
+    /// 主键列节省的字节数
+    pub fn key_bytes_saved(&self) -> u64 {
+        self.uuid_key_bytes - self.snowflake_key_bytes
+    }
+
+    /// 主键索引节省的字节数
+    pub fn index_bytes_saved(&self) -> u64 {
+        self.uuid_index_bytes.saturating_sub(self.snowflake_index_bytes)
+    }
+
+    /// 主键列与索引合计节省的字节数
+    pub fn total_bytes_saved(&self) -> u64 {
+        self.key_bytes_saved() + self.index_bytes_saved()
+    }
+}
+
+/// 防突发平滑配置
+/// 
+/// 同一毫秒内序列号用量达到 `threshold` 后，每次生成前暂停 `pause`
+/// （为零时仅 `yield_now`），把突发请求摊到后续毫秒，
+/// 以少量吞吐换取更低的尾延迟和更少的序列号耗尽自旋。
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothingConfig {
+    pub threshold: u64,
+    pub pause: Duration,
+}
+
+/// 签发ID的配额：每个窗口内最多签发 `limit` 个ID，`window` 为 `None` 时为终身配额
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdQuota {
+    pub limit: u64,
+    pub window: Option<Duration>,
+}
+
+impl IdQuota {
+    /// 生成器整个生命周期内最多签发 `limit` 个ID
+    pub fn lifetime(limit: u64) -> Self {
+        IdQuota { limit, window: None }
+    }
+
+    /// 每个 `window` 时长内最多签发 `limit` 个ID
+    pub fn per_window(limit: u64, window: Duration) -> Self {
+        IdQuota { limit, window: Some(window) }
+    }
+}
+
+/// 自定义的ID来源判定函数，返回 `true` 表示接受
+pub type IdPredicate = Arc<dyn Fn(&SnowflakeInfo) -> bool + Send + Sync>;
+
+/// 解析ID时的来源过滤器，用于拒绝其他环境泄漏进来的ID
+/// 
+/// 数据中心与 worker 白名单为 `None` 时不限制；同时设置的条件须全部满足。
+/// 默认值接受所有ID。
+#[derive(Clone, Default)]
+pub struct IdFilter {
+    datacenters: Option<BTreeSet<u64>>,
+    workers: Option<BTreeSet<u64>>,
+    predicate: Option<IdPredicate>,
+}
+
+impl IdFilter {
+    /// 只接受来自 `datacenter_ids` 的ID
+    pub fn allow_datacenters(mut self, datacenter_ids: impl IntoIterator<Item = u64>) -> Self {
+        self.datacenters = Some(datacenter_ids.into_iter().collect());
+        self
+    }
+
+    /// 只接受来自 `worker_ids` 的ID
+    pub fn allow_workers(mut self, worker_ids: impl IntoIterator<Item = u64>) -> Self {
+        self.workers = Some(worker_ids.into_iter().collect());
+        self
+    }
+
+    /// 额外用自定义函数判定，返回 `false` 的ID被拒绝
+    pub fn with_predicate(mut self, predicate: impl Fn(&SnowflakeInfo) -> bool + Send + Sync + 'static) -> Self {
+        self.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// 是否未设置任何条件（接受所有ID）
+    pub fn is_allow_all(&self) -> bool {
+        self.datacenters.is_none() && self.workers.is_none() && self.predicate.is_none()
+    }
+
+    /// 检查已解析的ID，未通过时返回 `RejectedId`
+    pub fn check(&self, info: &SnowflakeInfo) -> Result<(), WorkerError> {
+        if let Some(allowed) = &self.datacenters {
+            if !allowed.contains(&info.datacenter_id) {
+                return Err(WorkerError::RejectedId(format!(
+                    "id {} comes from datacenter {}, allowed: {:?}", info.id, info.datacenter_id, allowed
+                )));
+            }
+        }
+        if let Some(allowed) = &self.workers {
+            if !allowed.contains(&info.worker_id) {
+                return Err(WorkerError::RejectedId(format!(
+                    "id {} comes from worker {}, allowed: {:?}", info.id, info.worker_id, allowed
+                )));
+            }
+        }
+        if let Some(predicate) = &self.predicate {
+            if !predicate(info) {
+                return Err(WorkerError::RejectedId(format!("id {} rejected by filter", info.id)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for IdFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdFilter")
+            .field("datacenters", &self.datacenters)
+            .field("workers", &self.workers)
+            .field("predicate", &self.predicate.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// 预留的一段连续ID
+/// 
+/// 预留范围位于同一毫秒、同一 worker 内，`start()..=end()` 之间的每个整数
+/// 都是合法且不会被生成器再次签发的ID，客户端可在其中离线自行分配。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdReservation {
+    pub timestamp: u64,
+    pub datacenter_id: u64,
+    pub worker_id: u64,
+    pub start_sequence: u64,
+    pub count: u64,
+    pub epoch_millis: u64,
+}
+
+impl IdReservation {
+    /// 预留范围内的第一个ID
+    pub fn start(&self) -> u64 {
+        build_snowflake_id_with_epoch(self.timestamp, self.epoch_millis, self.datacenter_id, self.worker_id, self.start_sequence)
+    }
+
+    /// 预留范围内的最后一个ID（包含）
+    pub fn end(&self) -> u64 {
+        self.start() + self.count - 1
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        (self.start()..=self.end()).contains(&id)
+    }
+}
+
+/// `Snowflake::next_id_detailed` 的返回值，附带单次生成的诊断信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationResult {
+    pub id: u64,
+    /// 本毫秒序列号已耗尽，需等待到下一毫秒
+    pub spun: bool,
+    /// 等待下一毫秒耗费的时间，未等待时为零
+    pub spin_duration: Duration,
+    /// 该ID是否落在与上一个ID不同的毫秒
+    pub new_millisecond: bool,
+}
+
+/// 生成器累计签发ID数的只读句柄
+/// 
+/// 由 `Snowflake::generation_counter` 取得，可克隆后交给其他线程，
+/// 读取时无需锁住生成器（例如生成器位于 `Mutex` 中时供统计接口使用）。
+#[derive(Debug, Clone)]
+pub struct GenerationCounter(Arc<AtomicU64>);
+
+impl GenerationCounter {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 生成器的整体健康状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthStatus {
+    Healthy,
+    /// 仍能签发ID，但存在饱和、时钟滞后或锁竞争等风险信号
+    Degraded,
+    /// 最近一次生成失败，或时间提供者严重滞后
+    Unhealthy,
+}
+
+/// 由诊断信号推导 `HealthStatus` 的阈值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthThresholds {
+    /// 当前毫秒序列号使用比例达到该值视为 `Degraded`
+    pub saturation: f64,
+    /// 时间提供者落后系统时钟超过该毫秒数视为 `Degraded`
+    pub degraded_clock_lag_ms: u64,
+    /// 时间提供者落后系统时钟超过该毫秒数视为 `Unhealthy`
+    pub unhealthy_clock_lag_ms: u64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        HealthThresholds {
+            saturation: 0.9,
+            degraded_clock_lag_ms: 1000,
+            unhealthy_clock_lag_ms: 60_000,
+        }
+    }
+}
+
+/// `Snowflake::health_report` 的返回值，汇总全部诊断信号
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    /// 序列号耗尽后推进到下一毫秒的累计次数，见 `get_rollover_count`
+    pub rollover_count: u64,
+    /// 见 `last_id_timestamp_drift_ms`
+    pub last_id_drift_ms: Option<i64>,
+    /// 系统时钟减去时间提供者的当前时间（毫秒），计数器模式下为 `None`
+    pub clock_lag_ms: Option<i64>,
+    /// 当前毫秒已用序列号占容量的比例，时钟已进入新毫秒时为0
+    pub saturation: f64,
+    /// 最近一次生成失败的错误信息，之后成功生成也会保留
+    pub last_error: Option<String>,
+}
 
 /// 生产级雪花算法ID生成器
 /// 
@@ -11,28 +426,254 @@ use crate::snowflake_core::*;
 /// - 缓存时间提供者（性能优化）
 /// - 时钟回拨检测
 /// - 高并发支持
-pub struct Snowflake {
+/// 
+/// 时间来源可通过类型参数替换为任意 `TimeProvider`（见 `with_time_provider`），
+/// 默认使用后台线程更新的 `CachedTimeProvider`。
+pub struct Snowflake<T: TimeProvider = CachedTimeProvider> {
     worker_id: u64,
     datacenter_id: u64,
     sequence: u64,
     last_timestamp: u64,
     lock: Mutex<()>,
     worker_manager: Option<WorkerManager>,
-    time_provider: Arc<CachedTimeProvider>,
+    time_provider: Arc<T>,
+    persist_interval_ms: u64,
+    last_persist_millis: u64,
+    /// 自上次持久化以来是否签发过新ID
+    dirty: bool,
+    save_count: u64,
+    capacity_hint: usize,
+    smoothing: Option<SmoothingConfig>,
+    spin_count: u64,
+    spin_strategy: SpinStrategy,
+    clock_policy: ClockBackwardsPolicy,
+    max_backwards_wait: Duration,
+    counter_mode: bool,
+    epoch_millis: u64,
+    layout: SnowflakeLayout,
+    /// 最近签发的ID，容量为0时不记录
+    audit_log: VecDeque<u64>,
+    audit_capacity: usize,
+    /// 最近一次序列号耗尽时等待下一毫秒的耗时
+    last_spin: Option<Duration>,
+    /// 时钟停滞超过该时长时借用逻辑时间戳，`None` 表示一直等待
+    stall_fallback: Option<Duration>,
+    /// `last_timestamp` 是否为借用的逻辑时间戳（可能领先于时钟）
+    stall_borrowed: bool,
+    /// 最近一次签发带类型标签ID的毫秒
+    typed_timestamp: Option<u64>,
+    rollover_count: u64,
+    last_error: Option<String>,
+    /// 最近一次 `next_id` 调用是否失败
+    last_call_failed: bool,
+    health_thresholds: HealthThresholds,
+    quota: Option<IdQuota>,
+    /// 当前配额窗口内已签发的ID数
+    quota_used: u64,
+    quota_window_start: Instant,
+    /// 累计成功签发的ID数，可通过 `GenerationCounter` 无锁读取
+    total_generated: Arc<AtomicU64>,
+}
+
+/// 使用默认 `CachedTimeProvider` 的生成器
+pub type DefaultSnowflake = Snowflake<CachedTimeProvider>;
+
+/// `Snowflake::iter` 返回的无界ID迭代器，遇到第一个错误后不再产出
+pub struct SnowflakeIter<'a, T: TimeProvider = CachedTimeProvider> {
+    snowflake: &'a mut Snowflake<T>,
+    failed: bool,
+}
+
+impl<T: TimeProvider> Iterator for SnowflakeIter<'_, T> {
+    type Item = Result<u64, WorkerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.snowflake.next_id();
+        self.failed = result.is_err();
+        Some(result)
+    }
 }
 
+impl<T: TimeProvider> std::iter::FusedIterator for SnowflakeIter<'_, T> {}
+
 impl Snowflake {
+    /// 默认布局下单个生成器每毫秒最多可生成的ID数
+    pub const MAX_SEQUENCE_PER_MS: u64 = SEQUENCE_MASK + 1;
+
+    /// 默认布局下单个生成器每秒最多可生成的ID数
+    pub const MAX_IDS_PER_SECOND: u64 = Self::MAX_SEQUENCE_PER_MS * 1000;
+
     /// 创建新的雪花算法生成器
     /// 
     /// # 参数
     /// - `worker_id`: Worker ID (0-31)
     /// - `datacenter_id`: Datacenter ID (0-31)
     pub fn new(worker_id: u64, datacenter_id: u64) -> Self {
-        validate_ids(worker_id, datacenter_id).expect("Invalid worker_id or datacenter_id");
-        
         // 创建缓存时间提供者（每1毫秒更新一次）
-        let time_provider = CachedTimeProvider::new(1);
-        
+        Snowflake::with_time_provider(worker_id, datacenter_id, CachedTimeProvider::new(1))
+    }
+
+    /// 使用配置文件创建雪花算法生成器
+    /// 
+    /// # 参数
+    /// - `config_file`: 配置文件路径
+    /// - `default_datacenter_id`: 默认数据中心ID
+    pub fn new_with_config(config_file: &str, default_datacenter_id: u64) -> Result<Self, WorkerError> {
+        // 创建缓存时间提供者（每1毫秒更新一次）
+        Snowflake::with_config_and_time_provider(config_file, default_datacenter_id, CachedTimeProvider::new(1))
+    }
+
+    /// 阻塞到时钟不早于 `barrier_ms` 后再创建生成器
+    /// 
+    /// 用于集群协同启动或故障切换时的隔离：所有节点在同一屏障时间戳之后才开始发号，
+    /// 保证生成的ID时间戳都不早于 `barrier_ms`。屏障领先当前时间超过 `MAX_BARRIER_WAIT`
+    /// 时不等待，直接返回 `InvalidArgument`；ID 无效时返回 `InvalidId`。
+    pub fn new_after(worker_id: u64, datacenter_id: u64, barrier_ms: u64) -> Result<Self, WorkerError> {
+        validate_ids(worker_id, datacenter_id)?;
+
+        let mut snowflake = Snowflake::new(worker_id, datacenter_id);
+        let now = snowflake.system_millis();
+        if barrier_ms.saturating_sub(now) > MAX_BARRIER_WAIT.as_millis() as u64 {
+            return Err(WorkerError::InvalidArgument(format!(
+                "barrier {} is {} ms ahead of the clock, more than the {:?} limit",
+                barrier_ms, barrier_ms - now, MAX_BARRIER_WAIT
+            )));
+        }
+
+        while snowflake.system_millis() < barrier_ms {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        snowflake.time_provider.force_update();
+        snowflake.last_timestamp = snowflake.last_timestamp.max(barrier_ms.saturating_sub(1));
+        Ok(snowflake)
+    }
+
+    /// 以 Kubernetes StatefulSet 的 pod 序号作为 worker ID 创建生成器
+    /// 
+    /// 序号取自机器名（`HOSTNAME`）末尾的数字，如 `app-3` 对应 worker ID 3，
+    /// 超出 `MAX_WORKER_ID` 时返回 `InvalidId`。
+    pub fn from_statefulset_ordinal(datacenter_id: u64) -> Result<Self, WorkerError> {
+        Snowflake::from_hostname_ordinal(&current_hostname(), datacenter_id)
+    }
+
+    /// 从给定机器名解析 StatefulSet 序号作为 worker ID 创建生成器
+    pub fn from_hostname_ordinal(hostname: &str, datacenter_id: u64) -> Result<Self, WorkerError> {
+        let worker_id = parse_statefulset_ordinal(hostname)?;
+        validate_ids(worker_id, datacenter_id)?;
+        Ok(Snowflake::new(worker_id, datacenter_id))
+    }
+
+    fn system_millis(&self) -> u64 {
+        system_time_millis(SystemTime::now())
+    }
+
+    /// 使用自定义纪元创建生成器，用于兼容按其他纪元签发的既有ID空间
+    /// 
+    /// `epoch_millis` 晚于当前时间时返回 `InvalidArgument`。
+    /// 
+    /// # Panics
+    /// ID 无效时 panic
+    pub fn new_with_epoch(worker_id: u64, datacenter_id: u64, epoch_millis: u64) -> Result<Self, WorkerError> {
+        Snowflake::new(worker_id, datacenter_id).with_epoch(epoch_millis)
+    }
+
+    /// 按 `profiles/<name>.toml` 预设的纪元与位布局创建生成器，见 `from_format_profile`
+    /// 
+    /// `profiles` 相对于当前工作目录；需要指定目录时用 `FormatProfile::load_from_dir` 加载后调用 `from_format_profile`。
+    pub fn from_profile(name: &str, worker_id: u64, datacenter_id: u64) -> Result<Self, WorkerError> {
+        Snowflake::from_format_profile(&FormatProfile::load(name)?, worker_id, datacenter_id)
+    }
+
+    /// 按预设的纪元与位布局创建生成器，签发的ID可用 `FormatProfile::parse_id` 解码
+    /// 
+    /// ID超出预设布局的位宽时返回 `InvalidId`。非默认布局的生成器不支持带类型标签的ID
+    /// 与 `reserve_ids`（返回 `InvalidArgument`），按默认布局解析的 `parse_id` 等静态方法也不适用。
+    pub fn from_format_profile(profile: &FormatProfile, worker_id: u64, datacenter_id: u64) -> Result<Self, WorkerError> {
+        validate_layout_ids(profile.layout, worker_id, datacenter_id)?;
+        let mut snowflake = Snowflake::new(0, 0).with_epoch(profile.epoch_millis)?;
+        snowflake.worker_id = worker_id;
+        snowflake.datacenter_id = datacenter_id;
+        snowflake.layout = profile.layout;
+        Ok(snowflake)
+    }
+
+    /// 创建与 Discord 兼容的生成器，使用 `DISCORD_EPOCH`
+    /// 
+    /// Discord 的 worker ID 对应本库的 datacenter 字段，process ID 对应 worker 字段，
+    /// 生成的ID可直接与真实的 Discord 雪花ID比较先后。
+    pub fn discord(worker_id: u64, process_id: u64) -> Self {
+        let mut snowflake = Snowflake::new(process_id, worker_id);
+        snowflake.epoch_millis = DISCORD_EPOCH;
+        snowflake
+    }
+
+    /// 按 Discord 的纪元和布局解析ID
+    /// 
+    /// 返回值中 `datacenter_id` 为 Discord 的 worker ID，`worker_id` 为 process ID。
+    pub fn parse_discord_id(id: u64) -> SnowflakeInfo {
+        Snowflake::parse_id_with_epoch(id, DISCORD_EPOCH)
+    }
+
+    /// 按确定的顺序关闭生成器
+    /// 
+    /// 先把最后签发ID的时间戳写入配置文件，再在本生成器是时间提供者唯一
+    /// 使用者时停止并等待其后台线程退出；时间提供者被其他生成器共享时保持运行。
+    /// 无论保存是否成功都会完成关闭，保存失败时返回该错误。
+    pub fn shutdown(mut self) -> Result<(), WorkerError> {
+        let saved = self.flush();
+
+        if Arc::strong_count(&self.time_provider) == 1 {
+            self.time_provider.stop_and_join();
+        }
+        saved
+    }
+}
+
+impl Snowflake<SteppingTimeProvider> {
+    /// 创建时间戳可复现的生成器，用于测试
+    /// 
+    /// 使用从 `start_ms` 开始、每次读取前进1毫秒的步进时钟，
+    /// 第一个ID的时间戳正好是 `start_ms`，之后每个ID落在下一毫秒。
+    /// 
+    /// # Panics
+    /// ID 无效或 `start_ms` 早于纪元时 panic
+    pub fn with_initial_timestamp(worker_id: u64, datacenter_id: u64, start_ms: u64) -> Self {
+        assert!(start_ms >= EPOCH, "start_ms {} is before epoch {}", start_ms, EPOCH);
+
+        let time_provider = Arc::new(SteppingTimeProvider::new(start_ms, 1));
+        let mut snowflake = Snowflake::with_time_provider(worker_id, datacenter_id, time_provider);
+        snowflake.last_timestamp = start_ms - 1;
+        snowflake
+    }
+}
+
+impl Snowflake<RelativeTimeProvider> {
+    /// 创建基于单调时钟（`RelativeTimeProvider`）的生成器
+    /// 
+    /// 只在创建时读取一次系统时间，之后按 `Instant` 计时，不受 NTP 校时等系统时钟跳变影响。
+    /// 单调时钟不会回退，因此本生成器不会进入时钟回拨分支，`ClockBackwardsPolicy` 对其无效；
+    /// 代价是长期运行后时间戳可能与真实时间逐渐偏离。
+    /// 
+    /// # Panics
+    /// ID 无效时 panic
+    pub fn new_relative(worker_id: u64, datacenter_id: u64) -> Self {
+        Snowflake::with_time_provider(worker_id, datacenter_id, Arc::new(RelativeTimeProvider::new()))
+    }
+}
+
+impl<T: TimeProvider> Snowflake<T> {
+    /// 使用给定的时间提供者创建生成器
+    /// 
+    /// 可传入确定性的模拟时钟用于测试，或传入 `SystemTimeProvider` 以避免后台线程。
+    /// 
+    /// # Panics
+    /// ID 无效时 panic
+    pub fn with_time_provider(worker_id: u64, datacenter_id: u64, time_provider: Arc<T>) -> Self {
+        validate_ids(worker_id, datacenter_id).expect("Invalid worker_id or datacenter_id");
+
         Snowflake {
             worker_id,
             datacenter_id,
@@ -41,49 +682,167 @@ impl Snowflake {
             lock: Mutex::new(()),
             worker_manager: None,
             time_provider,
+            persist_interval_ms: DEFAULT_PERSIST_INTERVAL_MS,
+            last_persist_millis: 0,
+            dirty: false,
+            save_count: 0,
+            capacity_hint: 0,
+            smoothing: None,
+            spin_count: 0,
+            spin_strategy: SpinStrategy::default(),
+            clock_policy: ClockBackwardsPolicy::default(),
+            max_backwards_wait: DEFAULT_MAX_BACKWARDS_WAIT,
+            counter_mode: false,
+            epoch_millis: EPOCH,
+            layout: SnowflakeLayout::DEFAULT,
+            audit_log: VecDeque::new(),
+            audit_capacity: 0,
+            last_spin: None,
+            stall_fallback: None,
+            stall_borrowed: false,
+            typed_timestamp: None,
+            rollover_count: 0,
+            last_error: None,
+            last_call_failed: false,
+            health_thresholds: HealthThresholds::default(),
+            quota: None,
+            quota_used: 0,
+            quota_window_start: Instant::now(),
+            total_generated: Arc::new(AtomicU64::new(0)),
         }
     }
 
-    /// 使用配置文件创建雪花算法生成器
+    /// 使用配置文件和给定的时间提供者创建生成器
     /// 
-    /// # 参数
-    /// - `config_file`: 配置文件路径
-    /// - `default_datacenter_id`: 默认数据中心ID
-    pub fn new_with_config(config_file: &str, default_datacenter_id: u64) -> Result<Self, WorkerError> {
-        let worker_manager = WorkerManager::new(config_file, default_datacenter_id)?;
+    /// 与 `new_with_config` 相同，从配置文件分配 worker ID 并恢复检查点，
+    /// 时间取自 `time_provider`。
+    pub fn with_config_and_time_provider(config_file: &str, default_datacenter_id: u64, time_provider: Arc<T>) -> Result<Self, WorkerError> {
+        let worker_manager = WorkerManager::new(config_file, default_datacenter_id, WorkerIdStrategy::default())?;
         let worker_info = worker_manager.get_worker_info();
         
-        // 创建缓存时间提供者（每1毫秒更新一次）
-        let time_provider = CachedTimeProvider::new(1);
-        
-        let mut snowflake = Snowflake {
-            worker_id: worker_info.worker_id,
-            datacenter_id: worker_info.datacenter_id,
-            sequence: 0,
-            last_timestamp: worker_info.last_timestamp,
-            lock: Mutex::new(()),
-            worker_manager: Some(worker_manager),
-            time_provider,
-        };
+        // 从检查点恢复序列号，时钟未前进时在同一毫秒内接着递增，不会与重启前的ID冲突
+        let mut snowflake = Snowflake::with_time_provider(worker_info.worker_id, worker_info.datacenter_id, time_provider);
+        snowflake.sequence = worker_info.last_sequence;
+        snowflake.last_timestamp = worker_info.last_timestamp;
+        snowflake.worker_manager = Some(worker_manager);
+
+        // 恢复的时间戳可能因保存时的时钟抖动略微领先，等时钟追上后再继续
+        snowflake.rewind_safe_check()?;
 
         // 更新 worker manager 的时间戳
         if let Some(ref mut manager) = snowflake.worker_manager {
             manager.update_and_save()?;
+            snowflake.last_persist_millis = snowflake.current_millis();
+        }
+
+        Ok(snowflake)
+    }
+
+    /// 检查恢复的 `last_timestamp` 能否安全沿用
+    /// 
+    /// 领先当前时钟不超过 `RESTORE_GRACE_MS` 时等待时钟追上；领先更多，
+    /// 或时间提供者在 `2 * RESTORE_GRACE_MS` 内仍未追上，视为真正的时钟回拨。
+    fn rewind_safe_check(&self) -> Result<(), WorkerError> {
+        let now = self.current_millis();
+        if self.last_timestamp <= now {
+            return Ok(());
+        }
+        if self.last_timestamp - now > RESTORE_GRACE_MS {
+            return Err(WorkerError::ClockBackwardsError(format!(
+                "Restored timestamp is {} ms ahead of the clock. Last: {}, Current: {}",
+                self.last_timestamp - now, self.last_timestamp, now
+            )));
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(2 * RESTORE_GRACE_MS);
+        while self.current_millis() <= self.last_timestamp {
+            if Instant::now() >= deadline {
+                return Err(WorkerError::ClockBackwardsError(format!(
+                    "Clock did not catch up with the restored timestamp {} within {} ms",
+                    self.last_timestamp, 2 * RESTORE_GRACE_MS
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(1));
         }
+        Ok(())
+    }
+
+    /// 从另一个系统最后签发的ID接管生成
+    /// 
+    /// 从 `last_id` 中解析出 worker ID、数据中心ID、时间戳和序列号并沿用，
+    /// 之后生成的ID严格大于 `last_id`：同一毫秒内接着序列号递增，
+    /// 序列号已满则等到下一毫秒。若 `last_id` 的时间戳晚于本机时钟，
+    /// 按时钟回拨策略处理（默认返回错误而不是签发更小的ID）。
+    pub fn from_existing_id(last_id: u64, time_provider: Arc<T>) -> Result<Self, WorkerError> {
+        let worker_id = extract_worker_id(last_id);
+        let datacenter_id = extract_datacenter_id(last_id);
+        validate_ids(worker_id, datacenter_id)?;
 
+        let mut snowflake = Snowflake::with_time_provider(worker_id, datacenter_id, time_provider);
+        snowflake.last_timestamp = extract_timestamp(last_id);
+        snowflake.sequence = extract_sequence(last_id);
         Ok(snowflake)
     }
 
+    /// 设置批量生成的预期大小，批量接口据此一次性预分配 `Vec` 容量
+    /// 
+    /// 对于反复生成大批量ID的场景，可避免每批次多次扩容。
+    pub fn with_capacity_hint(mut self, n: usize) -> Self {
+        self.capacity_hint = n;
+        self
+    }
+
+    /// 改用自定义纪元，须在生成第一个ID之前调用
+    /// 
+    /// `epoch_millis` 晚于当前时间时返回 `InvalidArgument`。
+    pub fn with_epoch(mut self, epoch_millis: u64) -> Result<Self, WorkerError> {
+        let now = system_time_millis(SystemTime::now());
+        if epoch_millis > now {
+            return Err(WorkerError::InvalidArgument(format!(
+                "epoch {} is in the future (now {})", epoch_millis, now
+            )));
+        }
+        self.epoch_millis = epoch_millis;
+        Ok(self)
+    }
+
+    /// 生成器使用的纪元（毫秒时间戳）
+    pub fn get_epoch_millis(&self) -> u64 {
+        self.epoch_millis
+    }
+
+    fn build_id(&self, timestamp: u64, sequence: u64) -> u64 {
+        self.layout
+            .build_id(timestamp, self.epoch_millis, self.datacenter_id, self.worker_id, sequence)
+            .expect("generated timestamps are never before the epoch")
+    }
+
+    /// 本生成器布局下的最大序列号
+    fn sequence_mask(&self) -> u64 {
+        self.layout.sequence_capacity() - 1
+    }
+
     fn current_millis(&self) -> u64 {
         self.time_provider.current_millis()
     }
 
-    fn til_next_millis(&self, last_timestamp: u64) -> u64 {
-        let mut ts = self.current_millis();
-        while ts <= last_timestamp {
-            ts = self.current_millis();
-        }
-        ts
+    /// 按 `spin_strategy` 等待到下一毫秒，返回新时间戳和自旋次数
+    /// 
+    /// 给定 `max_wait` 时最多等待该时长，超时返回的时间戳不大于 `last_timestamp`。
+    fn til_next_millis(&self, last_timestamp: u64, max_wait: Option<Duration>) -> (u64, u64) {
+        wait_past(last_timestamp, max_wait, self.spin_strategy, || self.current_millis())
+    }
+
+    /// 按回拨策略处理时钟回拨，返回追上后的时间戳
+    fn handle_clock_backwards(&self, timestamp: u64) -> Result<u64, WorkerError> {
+        resolve_clock_backwards(
+            self.clock_policy,
+            self.last_timestamp,
+            timestamp,
+            Duration::from_millis(1),
+            "",
+            || self.current_millis(),
+        )
     }
 
     /// 生成下一个雪花ID
@@ -92,37 +851,492 @@ impl Snowflake {
     /// - `Ok(u64)`: 生成的雪花ID
     /// - `Err(WorkerError)`: 时钟回拨或其他错误
     pub fn next_id(&mut self) -> Result<u64, WorkerError> {
-        let _guard = self.lock.lock().unwrap();
-        let mut timestamp = self.current_millis();
-        
-        // 检查时钟回拨
-        if timestamp < self.last_timestamp {
-            return Err(WorkerError::ClockBackwardsError(
-                format!("Clock moved backwards. Last: {}, Current: {}", 
-                    self.last_timestamp, timestamp)
+        let result = self.generate_id();
+        self.record_outcome(result, 1)
+    }
+
+    /// 使用调用方给定的逻辑时间戳（毫秒）生成ID，不读取时间提供者
+    /// 
+    /// 用于可复现的单元测试和历史数据回填。仍保证单调：`timestamp_millis` 早于
+    /// 上次时间戳时返回 `ClockBackwardsError`；与上次相同时递增序列号，
+    /// 该毫秒序列号耗尽时返回 `InvalidArgument`（逻辑时钟无法等待，应传入更晚的时间戳）。
+    /// 早于纪元或超出时间戳位宽时返回 `InvalidArgument`；计数器模式下不可用。
+    pub fn next_id_at(&mut self, timestamp_millis: u64) -> Result<u64, WorkerError> {
+        let result = self.generate_id_at(timestamp_millis);
+        self.record_outcome(result, 1)
+    }
+
+    fn generate_id_at(&mut self, timestamp: u64) -> Result<u64, WorkerError> {
+        if let Some(quota) = self.quota {
+            self.check_quota(quota, 1)?;
+        }
+        if self.counter_mode {
+            return Err(WorkerError::InvalidArgument(
+                "explicit timestamps are not supported in counter mode".to_string()
             ));
         }
-        
-        if timestamp == self.last_timestamp {
-            self.sequence = (self.sequence + 1) & SEQUENCE_MASK;
-            if self.sequence == 0 {
-                timestamp = self.til_next_millis(self.last_timestamp);
-            }
-        } else {
-            self.sequence = 0;
+        let offset = timestamp.checked_sub(self.epoch_millis).ok_or_else(|| WorkerError::InvalidArgument(format!(
+            "timestamp {} is before epoch {}", timestamp, self.epoch_millis
+        )))?;
+        let max_offset = self.layout.lifetime_millis() - 1;
+        if offset > max_offset {
+            return Err(WorkerError::InvalidArgument(format!(
+                "timestamp {} exceeds the {} ms range after epoch {}", timestamp, max_offset, self.epoch_millis
+            )));
         }
-        
-        self.last_timestamp = timestamp;
-        
-        // 更新 worker manager 的时间戳（降低频率，避免频繁IO）
-        if let Some(ref mut manager) = self.worker_manager {
-            // 每1000个ID更新一次，减少IO操作
-            if self.sequence % 1000 == 0 {
-                manager.update_and_save()?;
-            }
+        if timestamp < self.last_timestamp {
+            return Err(WorkerError::ClockBackwardsError(format!(
+                "Timestamp moved backwards. Last: {}, Requested: {}", self.last_timestamp, timestamp
+            )));
         }
-        
-        Ok(build_snowflake_id(timestamp, self.datacenter_id, self.worker_id, self.sequence))
+        // 调用方指定了时间戳，不会等待下一毫秒，因此不计入 `rollover_count`
+        if timestamp == self.last_timestamp && self.sequence >= self.sequence_mask() {
+            return Err(WorkerError::InvalidArgument(format!(
+                "sequence exhausted at timestamp {}", timestamp
+            )));
+        }
+
+        // 已排除回拨与序列号耗尽，`advance` 不会读取时钟
+        let timestamp = self.advance(timestamp, self.sequence_mask())?;
+        Ok(self.build_id(timestamp, self.sequence))
+    }
+
+    /// 记录一次生成的结果：更新最近错误、累计签发数、配额用量与审计日志
+    /// 
+    /// `issued` 为本次签发的ID数，同时计入累计签发数与配额；预留整段ID时等于预留数量。
+    /// 审计日志只记录返回的那一个ID。
+    fn record_outcome(&mut self, result: Result<u64, WorkerError>, issued: u64) -> Result<u64, WorkerError> {
+        let id = match result {
+            Ok(id) => id,
+            Err(err) => {
+                self.last_error = Some(err.to_string());
+                self.last_call_failed = true;
+                return Err(err);
+            }
+        };
+        self.last_call_failed = false;
+        self.total_generated.fetch_add(issued, Ordering::Relaxed);
+        if self.quota.is_some() {
+            self.quota_used += issued;
+        }
+        if self.audit_capacity > 0 {
+            self.record_audit(id);
+        }
+        Ok(id)
+    }
+
+    fn generate_id(&mut self) -> Result<u64, WorkerError> {
+        if let Some(quota) = self.quota {
+            self.check_quota(quota, 1)?;
+        }
+        if self.counter_mode {
+            return self.next_counter_id();
+        }
+
+        // 本毫秒签发过带类型ID时，继续递增的序列号可能等于某个带类型ID的（标签|序列号），
+        // 视为本毫秒已用完，从下一毫秒开始
+        if self.typed_timestamp == Some(self.last_timestamp) {
+            self.sequence = self.sequence_mask();
+        }
+
+        let mut timestamp = self.current_millis();
+
+        // 防突发平滑：本毫秒用量超过阈值后让出CPU，把后续请求推向下一毫秒
+        if let Some(smoothing) = self.smoothing {
+            if timestamp == self.last_timestamp && self.sequence >= smoothing.threshold {
+                if smoothing.pause.is_zero() {
+                    std::thread::yield_now();
+                } else {
+                    std::thread::sleep(smoothing.pause);
+                }
+                timestamp = self.current_millis();
+            }
+        }
+
+        // 快速路径：仍在同一毫秒且序列号未耗尽时只需递增序列号。
+        // `&mut self` 已保证独占访问，同一毫秒内也不会到达持久化间隔（间隔为0时除外）。
+        if timestamp == self.last_timestamp
+            && self.sequence < self.sequence_mask()
+            && (self.persist_interval_ms > 0 || self.worker_manager.is_none())
+        {
+            self.sequence += 1;
+            self.dirty = true;
+            return Ok(self.build_id(timestamp, self.sequence));
+        }
+
+        let timestamp = self.advance(timestamp, self.sequence_mask())?;
+        Ok(self.build_id(timestamp, self.sequence))
+    }
+
+    /// 慢路径：处理时钟回拨与序列号耗尽，推进 `last_timestamp`/`sequence` 并按间隔持久化
+    /// 
+    /// `max_sequence` 为本次允许使用的最大序列号，返回本次使用的时间戳。
+    fn advance(&mut self, mut timestamp: u64, max_sequence: u64) -> Result<u64, WorkerError> {
+        let _guard = self.lock.lock().unwrap();
+        
+        // 检查时钟回拨；借用过的时间戳领先于时钟属于预期，沿用上次时间戳
+        if timestamp < self.last_timestamp {
+            timestamp = if self.stall_borrowed {
+                self.last_timestamp
+            } else {
+                self.handle_clock_backwards(timestamp)?
+            };
+        }
+        
+        if timestamp == self.last_timestamp {
+            if self.sequence >= max_sequence {
+                // 沿用的时间戳可能远远领先于时钟，等待必须有上限，不能持锁自旋到时钟追上
+                let max_wait = self.stall_fallback.unwrap_or(self.max_backwards_wait);
+                let spin_start = Instant::now();
+                let (next_timestamp, spins) = self.til_next_millis(self.last_timestamp, Some(max_wait));
+                self.last_spin = Some(spin_start.elapsed());
+                self.spin_count += spins;
+                timestamp = if next_timestamp > self.last_timestamp {
+                    next_timestamp
+                } else if self.stall_fallback.is_some() {
+                    // 时钟停滞超过阈值，借用下一个逻辑毫秒
+                    self.stall_borrowed = true;
+                    self.last_timestamp + 1
+                } else {
+                    return Err(WorkerError::ClockBackwardsError(format!(
+                        "Clock did not pass {} within {:?} after the sequence ran out. Current: {}",
+                        self.last_timestamp, max_wait, next_timestamp
+                    )));
+                };
+                // 只统计真正推进到下一毫秒（或借用下一毫秒）的耗尽，等待超时失败的不算
+                self.rollover_count += 1;
+                self.sequence = 0;
+            } else {
+                self.sequence += 1;
+            }
+        } else {
+            self.sequence = 0;
+            self.stall_borrowed = false;
+        }
+        
+        self.dirty = true;
+        self.last_timestamp = timestamp;
+        
+        // 持久化 (时间戳, 序列号) 检查点：每个间隔窗口内最多写一次文件，
+        // 与生成速率无关；窗口内未写入的进度由 `flush` 或 drop 时补写
+        if let Some(ref mut manager) = self.worker_manager {
+            if timestamp.saturating_sub(self.last_persist_millis) >= self.persist_interval_ms {
+                manager.save_checkpoint(timestamp, self.sequence)?;
+                self.last_persist_millis = timestamp;
+                self.dirty = false;
+                self.save_count += 1;
+            }
+        }
+        
+        Ok(timestamp)
+    }
+
+    /// 生成带类型标签的ID，用于在同一个ID流中区分用户、订单、会话等实体
+    /// 
+    /// 类型标签占用序列号的高 `TYPE_TAG_BITS` 位，因此每毫秒可生成的带类型ID更少；
+    /// 解析时通过 `SnowflakeInfo::kind` 读取。同一生成器可以混用带类型与不带类型的ID：
+    /// 某一毫秒签发过带类型ID后，不带类型的ID从下一毫秒开始，两者的序列号空间不会重叠；
+    /// 计数器模式下不可用。
+    pub fn next_id_of_type(&mut self, kind: u8) -> Result<u64, WorkerError> {
+        let result = self.generate_id_of_type(kind);
+        self.record_outcome(result, 1)
+    }
+
+    fn generate_id_of_type(&mut self, kind: u8) -> Result<u64, WorkerError> {
+        if kind as u64 > MAX_TYPE_TAG {
+            return Err(WorkerError::InvalidArgument(format!(
+                "type tag {} exceeds maximum {}", kind, MAX_TYPE_TAG
+            )));
+        }
+        if self.counter_mode {
+            return Err(WorkerError::InvalidArgument(
+                "typed ids are not supported in counter mode".to_string()
+            ));
+        }
+        if self.layout != SnowflakeLayout::DEFAULT {
+            return Err(WorkerError::InvalidArgument(format!(
+                "typed ids require the default layout, not {:?}", self.layout
+            )));
+        }
+
+        if let Some(quota) = self.quota {
+            self.check_quota(quota, 1)?;
+        }
+
+        let timestamp = self.current_millis();
+        let timestamp = self.advance(timestamp, TYPED_SEQUENCE_MASK)?;
+        self.typed_timestamp = Some(timestamp);
+        let sequence = ((kind as u64) << TYPE_TAG_SHIFT) | self.sequence;
+        Ok(self.build_id(timestamp, sequence))
+    }
+
+    fn record_audit(&mut self, id: u64) {
+        if self.audit_log.len() == self.audit_capacity {
+            self.audit_log.pop_front();
+        }
+        self.audit_log.push_back(id);
+    }
+
+    /// 启用审计日志，保留最近签发的 `capacity` 个ID（传0关闭并清空）
+    /// 
+    /// 下游报告ID冲突时，可据此核对该ID是否确实由本生成器签发；
+    /// 时间戳可从ID中解析，内存占用固定为 `capacity` 个 `u64`。
+    pub fn enable_audit_log(&mut self, capacity: usize) {
+        self.audit_capacity = capacity;
+        self.audit_log.clear();
+        self.audit_log.shrink_to(capacity);
+        self.audit_log.reserve_exact(capacity);
+    }
+
+    /// 按签发顺序返回审计日志中记录的最近ID
+    pub fn recent_ids(&self) -> Vec<u64> {
+        self.audit_log.iter().copied().collect()
+    }
+    
+    /// 计数器模式下生成ID：序列号耗尽时计数器加一，不读取系统时钟
+    fn next_counter_id(&mut self) -> Result<u64, WorkerError> {
+        self.sequence = (self.sequence + 1) & self.sequence_mask();
+        if self.sequence == 0 {
+            // 配置文件中的检查点只前进不后退，计数器须从两者中较大的一个继续
+            let persisted = self.worker_manager.as_ref().map_or(0, |manager| manager.get_worker_info().last_timestamp);
+            let counter = self.last_timestamp.max(persisted).max(self.epoch_millis) + 1;
+            // 先持久化再使用，重启后从更大的计数器继续，保证不重复
+            if let Some(ref mut manager) = self.worker_manager {
+                manager.save_last_timestamp(counter)?;
+                self.save_count += 1;
+            }
+            self.last_timestamp = counter;
+        }
+        Ok(self.build_id(self.last_timestamp, self.sequence))
+    }
+
+    /// 启用计数器模式，适用于没有可靠系统时钟的环境（如部分容器）
+    /// 
+    /// 启用后时间戳字段不再是毫秒时间，而是从配置文件中恢复的单调计数器，
+    /// 每耗尽一轮序列号加一并立即持久化。ID 仍为64位且可用 `parse_id` 解析
+    /// （时间戳字段应理解为计数器），以牺牲按时间排序换取不依赖时钟的唯一性。
+    pub fn enable_counter_mode(&mut self) {
+        self.counter_mode = true;
+        // 下一次生成时立即推进计数器，避免与已签发的ID重叠
+        self.sequence = self.sequence_mask();
+    }
+
+    pub fn is_counter_mode(&self) -> bool {
+        self.counter_mode
+    }
+
+    /// 在运行时切换 datacenter ID，无需重启
+    /// 
+    /// 切换后的第一个ID必然落在新的毫秒、序列号从0开始，
+    /// 新旧配置签发的ID不会落在同一毫秒内；配置文件（如有）同步更新。
+    pub fn set_datacenter_id(&mut self, new_id: u64) -> Result<(), WorkerError> {
+        validate_layout_ids(self.layout, self.worker_id, new_id)?;
+
+        if let Some(ref mut manager) = self.worker_manager {
+            manager.set_datacenter_id(new_id)?;
+        }
+        self.fence_after(self.last_timestamp);
+        self.datacenter_id = new_id;
+        Ok(())
+    }
+
+    /// 用完当前毫秒剩余的全部序列号并返回这些ID
+    /// 
+    /// 在切换 worker ID 等配置前调用，保证不会留下一个“用了一半”的毫秒；
+    /// 之后生成的ID必然落在新的毫秒、序列号从0开始。尚未生成过ID时返回空列表。
+    /// 
+    /// 与批量生成一样计入配额、累计签发数与审计日志，并按持久化间隔写入检查点。
+    /// 当前毫秒签发过带类型标签的ID时返回 `InvalidArgument`：剩余序列号按不带类型的ID签发
+    /// 会与已签发的带类型ID重复。
+    pub fn drain_remaining_sequence(&mut self) -> Result<Vec<u64>, WorkerError> {
+        if self.last_timestamp == 0 {
+            return Ok(Vec::new());
+        }
+        if self.typed_timestamp == Some(self.last_timestamp) {
+            return Err(WorkerError::InvalidArgument(format!(
+                "typed ids were issued at timestamp {}; draining would reissue their sequence numbers",
+                self.last_timestamp
+            )));
+        }
+
+        let count = self.sequence_mask().saturating_sub(self.sequence);
+        let mut ids = Vec::with_capacity(count as usize);
+        if count == 0 {
+            return Ok(ids);
+        }
+        if let Some(quota) = self.quota {
+            if let Err(err) = self.check_quota(quota, count) {
+                return self.record_outcome(Err(err), count).map(|_| ids);
+            }
+        }
+        self.claim_sequences(count, &mut ids)?;
+        Ok(ids)
+    }
+
+    /// 原子地预留 `count` 个连续ID，之后不会再签发其中任何一个
+    /// 
+    /// 预留范围必须落在同一毫秒内，因此 `count` 不能超过每毫秒的序列号容量；
+    /// 当前毫秒剩余序列号不足时自动跳到下一毫秒。
+    pub fn reserve_ids(&mut self, count: usize) -> Result<IdReservation, WorkerError> {
+        let count = count as u64;
+        if count == 0 || count > SEQUENCE_MASK + 1 {
+            return Err(WorkerError::InvalidArgument(format!(
+                "reservation count {} must be within 1..={}", count, SEQUENCE_MASK + 1
+            )));
+        }
+        if self.layout != SnowflakeLayout::DEFAULT {
+            return Err(WorkerError::InvalidArgument(format!(
+                "reservations require the default layout, not {:?}", self.layout
+            )));
+        }
+
+        // 整段预留按数量计入配额与累计签发数；跳过的旧毫秒不签发任何ID
+        let result = self.generate_reservation(count);
+        let first = self.record_outcome(result, count)?;
+
+        let reservation = IdReservation {
+            timestamp: extract_timestamp_with_epoch(first, self.epoch_millis),
+            datacenter_id: self.datacenter_id,
+            worker_id: self.worker_id,
+            start_sequence: extract_sequence(first),
+            count,
+            epoch_millis: self.epoch_millis,
+        };
+        // 预留的ID可能在持久化间隔内就被使用，立即写入预留末尾，重启后不会重复签发
+        self.dirty = true;
+        self.flush()?;
+        Ok(reservation)
+    }
+
+    fn generate_reservation(&mut self, count: u64) -> Result<u64, WorkerError> {
+        if let Some(quota) = self.quota {
+            self.check_quota(quota, count)?;
+        }
+        let mut first = self.generate_id()?;
+        if extract_sequence(first) + count - 1 > SEQUENCE_MASK {
+            // 本毫秒剩余序列号不足，从下一毫秒的0号序列开始
+            self.fence_after(extract_timestamp_with_epoch(first, self.epoch_millis));
+            first = self.generate_id()?;
+        }
+        self.sequence = extract_sequence(first) + count - 1;
+        Ok(first)
+    }
+
+    /// 生成下一个雪花ID，并返回本次调用耗费的时间
+    /// 
+    /// 用于SLO监控：序列号耗尽时的自旋等待会体现为明显更长的耗时。
+    pub fn next_id_timed(&mut self) -> Result<(u64, Duration), WorkerError> {
+        let start = Instant::now();
+        let id = self.next_id()?;
+        Ok((id, start.elapsed()))
+    }
+
+    /// 生成下一个ID，并返回是否发生序列号耗尽等待、等待耗时以及是否进入新毫秒
+    pub fn next_id_detailed(&mut self) -> Result<GenerationResult, WorkerError> {
+        let previous_timestamp = self.last_timestamp;
+        self.last_spin = None;
+        let id = self.next_id()?;
+
+        let spin = self.last_spin.take();
+        Ok(GenerationResult {
+            id,
+            spun: spin.is_some(),
+            spin_duration: spin.unwrap_or_default(),
+            new_millisecond: self.last_timestamp != previous_timestamp,
+        })
+    }
+
+    /// 生成下一个ID并同时返回其解析结果
+    pub fn next_id_with_meta(&mut self) -> Result<SnowflakeInfo, WorkerError> {
+        let (layout, epoch_millis) = (self.layout, self.epoch_millis);
+        self.next_id().map(|id| Snowflake::parse_id_with_layout(id, layout, epoch_millis))
+    }
+
+    /// 生成下一个ID，以 `(id, timestamp, datacenter_id, worker_id, sequence)` 元组返回各组成部分
+    /// 
+    /// 与 `next_id_with_meta` 等价，但不构造 `SnowflakeInfo`，适合热路径上的日志记录。
+    pub fn next_id_tuple(&mut self) -> Result<(u64, u64, u64, u64, u64), WorkerError> {
+        let id = self.next_id()?;
+        let (timestamp, datacenter_id, worker_id, sequence) = self.layout.decode(id, self.epoch_millis);
+        Ok((id, timestamp, datacenter_id, worker_id, sequence))
+    }
+
+    /// 批量生成 `count` 个雪花ID
+    /// 
+    /// 返回的 `Vec` 按 `max(count, capacity_hint)` 一次性分配容量。
+    pub fn next_ids(&mut self, count: usize) -> Result<Vec<u64>, WorkerError> {
+        let mut ids = Vec::with_capacity(count.max(self.capacity_hint));
+        self.generate_into(count, &mut ids)?;
+        Ok(ids)
+    }
+
+    /// 返回持续生成ID的迭代器，迭代期间独占借用生成器
+    /// 
+    /// 每次 `next` 调用一次 `next_id`；产出第一个错误后迭代结束，
+    /// 可写成 `sf.iter().take(1000).collect::<Result<Vec<_>, _>>()`。
+    pub fn iter(&mut self) -> SnowflakeIter<'_, T> {
+        SnowflakeIter { snowflake: self, failed: false }
+    }
+
+    /// 生成 `n` 个ID并直接写入任意实现 `Extend<u64>` 的集合（`Vec`、`HashSet` 等）
+    /// 
+    /// 每个毫秒只走一次完整的生成路径（回拨检查、序列号耗尽等待、持久化），
+    /// 之后一次性占用该毫秒剩余的序列号。出错时已生成的ID保留在 `sink` 中。
+    pub fn generate_into<E: Extend<u64>>(&mut self, n: usize, sink: &mut E) -> Result<(), WorkerError> {
+        let mut remaining = n as u64;
+        while remaining > 0 {
+            sink.extend(Some(self.next_id()?));
+            let take = (remaining - 1).min(self.batch_headroom());
+            remaining -= 1 + take;
+            if take > 0 {
+                self.claim_sequences(take, sink)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 在当前毫秒内一次性占用接下来的 `count` 个序列号，ID写入 `sink`
+    /// 
+    /// 调用方须已确认剩余序列号与配额足够。与 `next_id` 相同地更新累计签发数、配额用量与审计日志，
+    /// 持久化间隔为0时立即写入检查点。
+    fn claim_sequences<E: Extend<u64>>(&mut self, count: u64, sink: &mut E) -> Result<(), WorkerError> {
+        let timestamp = self.last_timestamp;
+        let first_sequence = self.sequence + 1;
+        self.sequence += count;
+        self.dirty = true;
+        sink.extend((first_sequence..=self.sequence).map(|sequence| self.build_id(timestamp, sequence)));
+
+        self.total_generated.fetch_add(count, Ordering::Relaxed);
+        if self.quota.is_some() {
+            self.quota_used += count;
+        }
+        if self.audit_capacity > 0 {
+            for sequence in first_sequence..=self.sequence {
+                let id = self.build_id(timestamp, sequence);
+                self.record_audit(id);
+            }
+        }
+        if self.persist_interval_ms == 0 {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 刚签发一个ID后，当前毫秒内无需再走完整生成路径即可连续占用的序列号个数
+    /// 
+    /// 受序列号容量、防突发平滑阈值与剩余配额限制；计数器模式下为0。
+    fn batch_headroom(&self) -> u64 {
+        if self.counter_mode {
+            return 0;
+        }
+        let mut headroom = self.sequence_mask() - self.sequence;
+        if let Some(smoothing) = self.smoothing {
+            headroom = headroom.min(smoothing.threshold.saturating_sub(self.sequence));
+        }
+        if let Some(quota) = self.quota {
+            headroom = headroom.min(quota.limit.saturating_sub(self.quota_used));
+        }
+        headroom
     }
     
     pub fn get_worker_id(&self) -> u64 {
@@ -137,99 +1351,2355 @@ impl Snowflake {
         self.last_timestamp
     }
 
-    /// 解析雪花ID，返回其各个组成部分的信息
+    /// 当前系统时间与最近签发ID内嵌时间戳之差（毫秒），尚未签发过ID时返回 `None`
     /// 
-    /// # 参数
-    /// - `id`: 要解析的雪花ID
+    /// 直接读取系统时钟而不经过时间提供者，持续签发ID时该值应接近0：
+    /// 不断增大说明时间提供者卡住，突变说明系统时钟发生了跳变。空闲期间该值自然增长；
+    /// 计数器模式下时间戳是逻辑计数，该值没有意义。负值表示ID时间戳领先于系统时钟。
+    pub fn last_id_timestamp_drift_ms(&self) -> Option<i64> {
+        if self.last_timestamp == 0 {
+            return None;
+        }
+        Some(system_time_millis(SystemTime::now()) as i64 - self.last_timestamp as i64)
+    }
+
+    /// 设置 worker 配置文件的持久化间隔（毫秒）
     /// 
-    /// # 返回值
-    /// 返回包含时间戳、数据中心ID、工作ID和序列号的元组
-    pub fn parse_id(id: u64) -> SnowflakeInfo {
-        SnowflakeInfo {
-            id,
-            timestamp: extract_timestamp(id),
-            datacenter_id: extract_datacenter_id(id),
-            worker_id: extract_worker_id(id),
-            sequence: extract_sequence(id),
+    /// 持久化内容为 (时间戳, 序列号) 检查点。间隔越大IO越少，但重启时恢复的检查点越旧；
+    /// 设为0表示每次生成都持久化，即使重启发生在同一毫秒内也能接着递增序列号。
+    pub fn set_persist_interval_ms(&mut self, interval_ms: u64) {
+        self.persist_interval_ms = interval_ms;
+    }
+
+    pub fn get_persist_interval_ms(&self) -> u64 {
+        self.persist_interval_ms
+    }
+
+    /// 立即持久化尚未写入配置文件的进度（没有待写入的进度时不做任何IO）
+    pub fn flush(&mut self) -> Result<(), WorkerError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(ref mut manager) = self.worker_manager {
+            manager.save_checkpoint(self.last_timestamp, self.sequence)?;
+            self.save_count += 1;
         }
+        self.last_persist_millis = self.last_timestamp;
+        self.dirty = false;
+        Ok(())
     }
-}
 
-/// 雪花ID解析信息结构体
-#[derive(Debug, Clone)]
-pub struct SnowflakeInfo {
-    pub id: u64,
-    pub timestamp: u64,
-    pub datacenter_id: u64,
-    pub worker_id: u64,
-    pub sequence: u64,
-}
+    /// 累计写入配置文件的次数
+    pub fn get_save_count(&self) -> u64 {
+        self.save_count
+    }
 
-impl SnowflakeInfo {
-    /// 获取可读的时间戳字符串
-    pub fn timestamp_as_string(&self) -> String {
-        use std::time::{SystemTime, Duration};
-        
-        let timestamp_secs = self.timestamp / 1000;
-        let timestamp_millis = self.timestamp % 1000;
-        
-        match SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(timestamp_secs)) {
-            Some(time) => {
-                format!("{:?}.{:03}", time, timestamp_millis)
-            }
-            None => format!("Invalid timestamp: {}", self.timestamp)
+    /// 启用或关闭防突发平滑模式（默认关闭）
+    pub fn set_smoothing(&mut self, smoothing: Option<SmoothingConfig>) {
+        self.smoothing = smoothing;
+    }
+
+    /// 启用或关闭时钟停滞回退（默认关闭）
+    /// 
+    /// 序列号耗尽后等待下一毫秒超过 `threshold` 仍未等到时（如虚拟机被暂停、时钟冻结），
+    /// 不再继续自旋，而是把时间戳当作计数器加一继续签发，保证ID严格递增且不报错。
+    /// 代价是时间戳字段可能领先于真实时间，解析出的时间不再准确；
+    /// 时钟恢复后，在其追上借用的时间戳之前不会被视为时钟回拨。
+    /// 借用的时间戳与普通时间戳一样按间隔持久化到配置文件。
+    pub fn set_stall_fallback(&mut self, threshold: Option<Duration>) {
+        self.stall_fallback = threshold;
+    }
+
+    /// 设置序列号耗尽后等待下一毫秒的方式（默认 `SpinStrategy::Busy`）
+    pub fn set_spin_strategy(&mut self, strategy: SpinStrategy) {
+        self.spin_strategy = strategy;
+    }
+
+    /// 设置时钟回拨处理策略（默认 `ClockBackwardsPolicy::Error`）
+    pub fn set_clock_policy(&mut self, policy: ClockBackwardsPolicy) {
+        self.clock_policy = policy;
+    }
+
+    /// 设置上次时间戳领先于时钟、序列号又已用完时等待时钟越过它的最长时间（默认 `DEFAULT_MAX_BACKWARDS_WAIT`）
+    /// 
+    /// `RefuseAndReuseLast` 沿用的时间戳可能远远领先于时钟，超时返回 `ClockBackwardsError`
+    /// 而不是持锁自旋到时钟追上；设置了停滞兜底时改为按兜底时长借用下一个逻辑毫秒。
+    pub fn set_max_backwards_wait(&mut self, max_wait: Duration) {
+        self.max_backwards_wait = max_wait;
+    }
+
+    /// 在 `window` 采样窗口内测量时间戳的实际分辨率（最小非零增量，毫秒）
+    /// 
+    /// 正常情况下应为1；明显偏大说明缓存时间更新不及时，`None` 表示时钟停滞。
+    pub fn estimate_timestamp_resolution_gap(&self, window: Duration) -> Option<u64> {
+        measure_resolution_gap(self.time_provider.as_ref(), window)
+    }
+
+    /// 本生成器每毫秒最多可生成的ID数
+    pub fn max_sequence_per_ms(&self) -> u64 {
+        self.layout().sequence_capacity()
+    }
+
+    /// 时间戳字段在本生成器纪元下能表示的最晚时刻（Unix 毫秒时间戳）
+    pub fn max_representable_timestamp(&self) -> u64 {
+        self.epoch_millis.saturating_add(self.layout.lifetime_millis() - 1)
+    }
+
+    /// 从现在起到时间戳字段溢出前的剩余时长
+    /// 
+    /// worker ID 唯一且时钟单调时，这段时间内签发的ID保证不重复；
+    /// 已签发ID的时间戳领先时钟时从该时间戳算起，超出范围后返回0。
+    pub fn collision_free_window(&self) -> Duration {
+        let now = self.current_millis().max(self.last_timestamp);
+        Duration::from_millis(self.max_representable_timestamp().saturating_sub(now))
+    }
+
+    /// 预估接下来 `n` 个ID将落入的毫秒时间戳，用于预建按时间分区的存储
+    /// 
+    /// 按满负荷签发估算：当前毫秒若已签发过ID，只计入其剩余的序列号，之后每毫秒按满额计算。
+    /// 返回去重后的升序时间戳；实际签发慢于满负荷时ID会分布到更晚、更多的毫秒中。
+    pub fn projected_timestamps(&self, n: usize) -> Vec<u64> {
+        let capacity = self.max_sequence_per_ms();
+        let mut timestamp = self.current_millis().max(self.last_timestamp);
+        let mut free = if timestamp == self.last_timestamp {
+            capacity.saturating_sub(self.sequence + 1)
+        } else {
+            capacity
+        };
+        if free == 0 {
+            timestamp += 1;
+            free = capacity;
+        }
+
+        let mut buckets = Vec::new();
+        let mut remaining = n as u64;
+        while remaining > 0 {
+            buckets.push(timestamp);
+            remaining = remaining.saturating_sub(free);
+            timestamp += 1;
+            free = capacity;
         }
+        buckets
     }
-    
-    /// 获取ID的十六进制表示
-    pub fn id_as_hex(&self) -> String {
-        format!("0x{:016x}", self.id)
+
+    /// 本生成器每秒最多可生成的ID数
+    pub fn max_ids_per_second(&self) -> u64 {
+        self.layout().max_ids_per_second()
     }
-    
-    /// 获取ID的二进制表示（带分隔符）
-    pub fn id_as_binary(&self) -> String {
-        format!("{:064b}", self.id)
+
+    /// 本生成器使用的位布局
+    pub fn layout(&self) -> SnowflakeLayout {
+        self.layout
     }
-    
-    /// 获取详细的格式化信息
-    pub fn format_details(&self) -> String {
-        format!(
-            "Snowflake ID: {}\n\
-             Hex: {}\n\
-             Binary: {}\n\
-             Timestamp: {} ({})\n\
-             Datacenter ID: {}\n\
-             Worker ID: {}\n\
-             Sequence: {}",
-            self.id,
-            self.id_as_hex(),
-            self.id_as_binary(),
-            self.timestamp,
-            self.timestamp_as_string(),
-            self.datacenter_id,
-            self.worker_id,
-            self.sequence
-        )
+
+    /// 序列号耗尽后等待下一毫秒的累计自旋次数
+    pub fn get_spin_count(&self) -> u64 {
+        self.spin_count
     }
-}
 
-// 示例用法和测试模块
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_snowflake_creation() {
-        let sf = Snowflake::new(1, 1);
-        assert_eq!(sf.get_worker_id(), 1);
-        assert_eq!(sf.get_datacenter_id(), 1);
+    /// 累计成功签发的ID数（含带类型标签的ID；预留的整段ID只计一次）
+    pub fn total_generated(&self) -> u64 {
+        self.total_generated.load(Ordering::Relaxed)
     }
-    
-    #[test]
-    fn test_id_generation() {
-        let mut sf = Snowflake::new(1, 1);
-        let id1 = sf.next_id().unwrap();
-        let id2 = sf.next_id().unwrap();
-        assert_ne!(id1, id2);
+
+    /// 取得累计签发数的只读句柄，之后无需锁住生成器即可读取
+    pub fn generation_counter(&self) -> GenerationCounter {
+        GenerationCounter(self.total_generated.clone())
+    }
+
+    /// 序列号耗尽后推进到下一毫秒（等待时钟或借用逻辑毫秒）的累计次数
+    /// 
+    /// 耗尽后因等待超时或 `next_id_at` 指定的时间戳已用完而返回错误的调用不计入。
+    pub fn get_rollover_count(&self) -> u64 {
+        self.rollover_count
+    }
+
+    /// 设置签发配额，`None` 表示不限制；设置后从零开始计数并开启新窗口
+    /// 
+    /// 配额用完后 `next_id`、`next_id_of_type` 及批量接口返回 `QuotaExceeded`，直到窗口重置；
+    /// `reserve_ids` 按预留数量占用配额，剩余配额不足整段时直接拒绝。
+    pub fn set_quota(&mut self, quota: Option<IdQuota>) {
+        self.quota = quota;
+        self.quota_used = 0;
+        self.quota_window_start = Instant::now();
+    }
+
+    /// 当前配额窗口内剩余可签发的ID数，未设置配额时返回 `None`
+    pub fn remaining_quota(&self) -> Option<u64> {
+        self.quota.map(|quota| quota.limit.saturating_sub(self.quota_used))
+    }
+
+    fn check_quota(&mut self, quota: IdQuota, count: u64) -> Result<(), WorkerError> {
+        if let Some(window) = quota.window {
+            if self.quota_window_start.elapsed() >= window {
+                self.quota_used = 0;
+                self.quota_window_start = Instant::now();
+            }
+        }
+        if self.quota_used + count > quota.limit {
+            return Err(WorkerError::QuotaExceeded(format!(
+                "{} ids already issued in the current window (limit {}), cannot issue {} more",
+                self.quota_used, quota.limit, count
+            )));
+        }
+        Ok(())
+    }
+
+    /// 设置 `health_report` 推导整体状态使用的阈值
+    pub fn set_health_thresholds(&mut self, thresholds: HealthThresholds) {
+        self.health_thresholds = thresholds;
+    }
+
+    /// 汇总序列号耗尽次数、时钟漂移、饱和度与最近错误，并按阈值推导整体状态
+    /// 
+    /// 最近一次生成失败或时间提供者滞后超过 `unhealthy_clock_lag_ms` 为 `Unhealthy`；
+    /// 饱和度达到阈值、时钟滞后超过 `degraded_clock_lag_ms` 或
+    /// 正在借用逻辑时间戳为 `Degraded`。最近ID的漂移在空闲时自然增长，只作参考不参与推导。
+    pub fn health_report(&self) -> HealthReport {
+        let now = self.current_millis();
+        let clock_lag_ms = (!self.counter_mode)
+            .then(|| system_time_millis(SystemTime::now()) as i64 - now as i64);
+        let saturation = if self.last_timestamp != 0 && self.last_timestamp >= now {
+            (self.sequence + 1) as f64 / self.max_sequence_per_ms() as f64
+        } else {
+            0.0
+        };
+
+        let thresholds = &self.health_thresholds;
+        let lag = clock_lag_ms.map_or(0, |lag| lag.unsigned_abs());
+        let status = if self.last_call_failed || lag > thresholds.unhealthy_clock_lag_ms {
+            HealthStatus::Unhealthy
+        } else if saturation >= thresholds.saturation
+            || lag > thresholds.degraded_clock_lag_ms
+            || self.stall_borrowed
+        {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+
+        HealthReport {
+            status,
+            rollover_count: self.rollover_count,
+            last_id_drift_ms: self.last_id_timestamp_drift_ms(),
+            clock_lag_ms,
+            saturation,
+            last_error: self.last_error.clone(),
+        }
+    }
+
+    /// 确保之后生成的ID时间戳严格大于 `timestamp`
+    /// 
+    /// 若当前时钟尚未越过该时间戳，下一次生成会按回拨策略处理。
+    pub(crate) fn fence_after(&mut self, timestamp: u64) {
+        if timestamp >= self.last_timestamp {
+            self.last_timestamp = timestamp;
+            self.sequence = self.sequence_mask();
+        }
+    }
+
+    /// 供异步调用方使用、不会阻塞线程的 `next_id`
+    /// 
+    /// 同步路径需要等待时返回 `Ok(None)`，由调用方异步等待后带上本次累计等待时长 `waited` 重试：
+    /// - `WaitUpTo` 策略下时钟回拨（无论序列号是否用完）：最多等待给定时长，超时返回 `ClockBackwardsError`；
+    /// - 序列号用完而时钟未越过上次时间戳（时钟停滞、沿用或借用的时间戳领先于时钟）：
+    ///   最多等待 `stall_fallback`，到时借用下一毫秒；未设置时最多等待 `max_backwards_wait`，超时返回错误。
+    /// 
+    /// 其余情况（包括 `Error` 策略下的回拨）直接交给 `next_id`，它不会阻塞。失败同样计入健康报告。
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_next_id(&mut self, waited: Duration) -> Result<Option<u64>, WorkerError> {
+        if self.counter_mode {
+            return self.next_id().map(Some);
+        }
+        let timestamp = self.current_millis();
+        let exhausted = self.sequence >= self.sequence_mask();
+
+        // 借用过的时间戳领先于时钟属于预期，不算回拨
+        if timestamp < self.last_timestamp && !self.stall_borrowed {
+            match self.clock_policy {
+                ClockBackwardsPolicy::Error => return self.next_id().map(Some),
+                ClockBackwardsPolicy::WaitUpTo(max_wait) if waited < max_wait => return Ok(None),
+                ClockBackwardsPolicy::WaitUpTo(max_wait) => {
+                    let err = WorkerError::ClockBackwardsError(format!(
+                        "Clock did not catch up within {:?}. Last: {}, Current: {}",
+                        max_wait, self.last_timestamp, timestamp
+                    ));
+                    return self.record_outcome(Err(err), 1).map(Some);
+                }
+                // 沿用上次时间戳；序列号已用完时与时钟停滞一样等待
+                ClockBackwardsPolicy::RefuseAndReuseLast if !exhausted => return self.next_id().map(Some),
+                ClockBackwardsPolicy::RefuseAndReuseLast => {}
+            }
+        } else if timestamp > self.last_timestamp || !exhausted {
+            return self.next_id().map(Some);
+        }
+
+        let max_wait = self.stall_fallback.unwrap_or(self.max_backwards_wait);
+        if waited < max_wait {
+            return Ok(None);
+        }
+        match self.stall_fallback {
+            Some(fallback) => {
+                // 已经异步等够了，同步路径不必再等，直接借用下一毫秒
+                self.stall_fallback = Some(Duration::ZERO);
+                let result = self.next_id();
+                self.stall_fallback = Some(fallback);
+                result.map(Some)
+            }
+            None => {
+                let err = WorkerError::ClockBackwardsError(format!(
+                    "Clock did not pass {} within {:?} after the sequence ran out. Current: {}",
+                    self.last_timestamp, max_wait, timestamp
+                ));
+                self.record_outcome(Err(err), 1).map(Some)
+            }
+        }
+    }
+
+    /// 预检：确认本生成器不会与另一个生成器签发的ID范围重叠
+    /// 
+    /// 若两者的 (worker ID, 数据中心ID) 相同，且对方的最后时间戳不早于本生成器将要恢复的
+    /// `last_timestamp`，则双方可能在同一毫秒签发相同的ID，返回 `WorkerIdConflict`。
+    /// worker ID 相同但数据中心不同的生成器签发的ID互不相同，不算冲突。
+    pub fn assert_no_overlap_with(
+        &self,
+        other_last_timestamp: u64,
+        other_worker_id: u64,
+        other_datacenter_id: u64,
+    ) -> Result<(), WorkerError> {
+        if other_worker_id == self.worker_id
+            && other_datacenter_id == self.datacenter_id
+            && other_last_timestamp >= self.last_timestamp
+        {
+            return Err(WorkerError::WorkerIdConflict(format!(
+                "worker_id {} in datacenter {} is shared with a sibling whose last timestamp {} is not before ours {}",
+                self.worker_id, self.datacenter_id, other_last_timestamp, self.last_timestamp
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Snowflake {
+    /// 解析雪花ID，返回其各个组成部分的信息
+    /// 
+    /// # 参数
+    /// - `id`: 要解析的雪花ID
+    /// 
+    /// # 返回值
+    /// 返回包含时间戳、数据中心ID、工作ID和序列号的元组
+    pub fn parse_id(id: u64) -> SnowflakeInfo {
+        Snowflake::parse_id_with_epoch(id, EPOCH)
+    }
+
+    /// 比较候选位布局能否支撑目标吞吐
+    /// 
+    /// 将 `target_rps` 均摊到 `nodes` 个节点，逐毫秒模拟每个节点的签发：
+    /// 当毫秒内需求超过序列号容量时，剩余请求积压到下一毫秒（对应实际的自旋等待）。
+    /// 节点数放得下、耗尽毫秒占比不超过 `MAX_ROLLOVER_RATIO` 且没有持续积压的布局视为可支撑。
+    /// 未通过 `is_valid` 的候选同样返回一条结果，其 `sustains` 为 false。
+    pub fn benchmark_layouts(target_rps: u64, nodes: u64, candidates: &[SnowflakeLayout]) -> Vec<LayoutBenchmark> {
+        const MILLIS_PER_YEAR: f64 = 365.25 * 24.0 * 3600.0 * 1000.0;
+
+        let demand_per_ms = target_rps as f64 / nodes.max(1) as f64 / 1000.0;
+        candidates
+            .iter()
+            .map(|&layout| {
+                let capacity = layout.sequence_capacity();
+                let mut arrived = 0.0;
+                let mut issued = 0u64;
+                let mut rollovers = 0u64;
+                for _ in 0..LAYOUT_SIMULATION_MILLIS {
+                    arrived += demand_per_ms;
+                    let pending = arrived as u64 - issued;
+                    if pending > capacity {
+                        rollovers += 1;
+                    }
+                    issued += pending.min(capacity);
+                }
+
+                let backlog = arrived as u64 - issued;
+                let rollover_ratio = rollovers as f64 / LAYOUT_SIMULATION_MILLIS as f64;
+                let fits_nodes = nodes <= layout.max_nodes();
+                LayoutBenchmark {
+                    layout,
+                    fits_nodes,
+                    demand_per_ms,
+                    rollover_ratio,
+                    backlog,
+                    lifetime_years: layout.lifetime_millis() as f64 / MILLIS_PER_YEAR,
+                    sustains: layout.is_valid()
+                        && fits_nodes
+                        && rollover_ratio <= MAX_ROLLOVER_RATIO
+                        && backlog <= capacity,
+                }
+            })
+            .collect()
+    }
+
+    /// 估算 `row_count` 行数据使用雪花ID与UUID作主键时的存储差异，估算方式见 `StorageComparison`
+    pub fn storage_comparison(row_count: u64) -> StorageComparison {
+        // 向上取整，超出 u64 时饱和
+        let index_bytes = |key_bytes: u64, fill_percent: u64| {
+            let raw = row_count as u128 * (key_bytes + INDEX_ENTRY_OVERHEAD_BYTES) as u128 * 100;
+            u64::try_from(raw.div_ceil(fill_percent as u128)).unwrap_or(u64::MAX)
+        };
+
+        StorageComparison {
+            row_count,
+            snowflake_key_bytes: row_count.saturating_mul(SNOWFLAKE_KEY_BYTES),
+            uuid_key_bytes: row_count.saturating_mul(UUID_KEY_BYTES),
+            snowflake_index_bytes: index_bytes(SNOWFLAKE_KEY_BYTES, SEQUENTIAL_FILL_PERCENT),
+            uuid_index_bytes: index_bytes(UUID_KEY_BYTES, RANDOM_FILL_PERCENT),
+        }
+    }
+
+    /// 解析可能为负数的十进制ID字符串
+    /// 
+    /// Java 的 `long` 在最高位为1时会以负数输出，这里按二进制补码还原为
+    /// 相同位模式的 `u64` 后再解析；非负数按 `u64` 直接解析。
+    pub fn parse_signed_str(s: &str) -> Result<SnowflakeInfo, WorkerError> {
+        let s = s.trim();
+        let id = if s.starts_with('-') {
+            s.parse::<i64>().map(|signed| signed as u64)
+                .map_err(|e| WorkerError::ParseError(format!("invalid signed id '{}': {}", s, e)))?
+        } else {
+            s.parse::<u64>()
+                .map_err(|e| WorkerError::ParseError(format!("invalid id '{}': {}", s, e)))?
+        };
+        Ok(Snowflake::parse_id(id))
+    }
+
+    /// 校验并解析来自不可信来源的ID，来源须通过 `filter`
+    /// 
+    /// ID本身不合法时返回 `InvalidId`（见 `validate_snowflake_id`），来源被拒绝时返回 `RejectedId`。
+    pub fn try_parse_id(id: u64, filter: &IdFilter) -> Result<SnowflakeInfo, WorkerError> {
+        Snowflake::try_parse_id_with_epoch(id, EPOCH, filter)
+    }
+
+    /// 按指定纪元校验并解析ID，见 `try_parse_id`
+    pub fn try_parse_id_with_epoch(id: u64, epoch: u64, filter: &IdFilter) -> Result<SnowflakeInfo, WorkerError> {
+        validate_snowflake_id(id, epoch)?;
+        let info = Snowflake::parse_id_with_epoch(id, epoch);
+        filter.check(&info)?;
+        Ok(info)
+    }
+
+    /// 按指定纪元解析雪花ID，用于解码其他服务签发的ID
+    pub fn parse_id_with_epoch(id: u64, epoch: u64) -> SnowflakeInfo {
+        SnowflakeInfo {
+            id,
+            timestamp: extract_timestamp_with_epoch(id, epoch),
+            datacenter_id: extract_datacenter_id(id),
+            worker_id: extract_worker_id(id),
+            sequence: extract_sequence(id),
+        }
+    }
+
+    /// 按指定位布局与纪元解析ID，用于解码非默认 41/5/5/12 划分签发的ID
+    /// 
+    /// 保留位被跳过；只有字段值按 `layout` 解码，`SnowflakeInfo` 的分组二进制等格式化方法仍按默认布局分段。
+    pub fn parse_id_with_layout(id: u64, layout: SnowflakeLayout, epoch: u64) -> SnowflakeInfo {
+        let (timestamp, datacenter_id, worker_id, sequence) = layout.decode(id, epoch);
+        SnowflakeInfo { id, timestamp, datacenter_id, worker_id, sequence }
+    }
+
+    /// 返回时间范围内第 `p` 分位处时间戳对应的最小ID，用于按时间均分扫描区间
+    /// 
+    /// # Panics
+    /// `p` 不在 `[0, 1]` 区间内时 panic
+    pub fn id_at_percentile(start_ms: u64, end_ms: u64, p: f64) -> u64 {
+        assert!((0.0..=1.0).contains(&p), "percentile {} must be within [0, 1]", p);
+        let span = end_ms.saturating_sub(start_ms);
+        let offset = (span as f64 * p).round() as u64;
+        min_id_for_timestamp(start_ms + offset.min(span))
+    }
+
+    /// 计算各 worker 在 `[start_ms, end_ms]` 时间窗口内可能签发的ID边界，供协调者预先划分键空间
+    /// 
+    /// 返回按 worker ID 升序排列的 `(worker_id, min_id, max_id)`（均包含在内）。
+    /// 时间戳位于高位，因此只有窗口落在同一毫秒内时各区间才互不重叠；
+    /// 跨多个毫秒时区间会交错，需结合 `extract_worker_id` 判断归属。
+    pub fn split_id_range_for_workers(
+        start_ms: u64,
+        end_ms: u64,
+        datacenter_id: u64,
+        worker_ids: &[u64],
+    ) -> Result<Vec<(u64, u64, u64)>, WorkerError> {
+        let start_ms = start_ms.max(EPOCH);
+        let end_ms = end_ms.max(start_ms);
+
+        let mut worker_ids = worker_ids.to_vec();
+        worker_ids.sort_unstable();
+        worker_ids.dedup();
+        worker_ids
+            .into_iter()
+            .map(|worker_id| {
+                validate_ids(worker_id, datacenter_id)?;
+                Ok((
+                    worker_id,
+                    build_snowflake_id(start_ms, datacenter_id, worker_id, 0),
+                    build_snowflake_id(end_ms, datacenter_id, worker_id, SEQUENCE_MASK),
+                ))
+            })
+            .collect()
+    }
+
+    /// 由预留范围与偏移量构造ID，供分片客户端在预留范围内自行签发指定的ID
+    /// 
+    /// 结果等于 `reservation.start() + offset`；`offset` 超出预留数量时返回 `InvalidArgument`。
+    pub fn compose_from_reservation(reservation: &IdReservation, offset: u64) -> Result<u64, WorkerError> {
+        if offset >= reservation.count {
+            return Err(WorkerError::InvalidArgument(format!(
+                "offset {} is outside the reservation of {} ids", offset, reservation.count
+            )));
+        }
+        Ok(build_snowflake_id_with_epoch(
+            reservation.timestamp,
+            reservation.epoch_millis,
+            reservation.datacenter_id,
+            reservation.worker_id,
+            reservation.start_sequence + offset,
+        ))
+    }
+
+    /// 将ID中的时间戳换算为近似的 `Instant`
+    /// 
+    /// `anchor_instant` 与 `anchor_time` 应在同一时刻采集，作为墙上时钟与单调时钟的对应点。
+    /// 结果只是近似值：ID时间戳来自墙上时钟，精度为毫秒，锚点之后的NTP调整不会反映在
+    /// 单调时钟上，两者间隔越久偏差可能越大。超出 `Instant` 可表示范围时返回 `None`。
+    pub fn id_to_instant(id: u64, anchor_instant: Instant, anchor_time: SystemTime) -> Option<Instant> {
+        let id_millis = extract_timestamp(id);
+        let anchor_millis = system_time_millis(anchor_time);
+        if id_millis >= anchor_millis {
+            anchor_instant.checked_add(Duration::from_millis(id_millis - anchor_millis))
+        } else {
+            anchor_instant.checked_sub(Duration::from_millis(anchor_millis - id_millis))
+        }
+    }
+
+    /// 返回 `instant` 对应毫秒内可能出现的最小ID，可用作按ID排序调度的下界
+    /// 
+    /// 锚点含义与近似性同 `id_to_instant`；早于纪元的时间按纪元计算。
+    pub fn instant_to_id_bound(instant: Instant, anchor_instant: Instant, anchor_time: SystemTime) -> u64 {
+        let anchor_millis = system_time_millis(anchor_time);
+        let millis = if instant >= anchor_instant {
+            anchor_millis + (instant - anchor_instant).as_millis() as u64
+        } else {
+            anchor_millis.saturating_sub((anchor_instant - instant).as_millis() as u64)
+        };
+        min_id_for_timestamp(millis)
+    }
+
+    /// ID是否超出 JavaScript 的安全整数范围
+    /// 
+    /// 超出时以JSON数字返回给JS客户端会被静默截断，应改用字符串序列化。
+    /// 默认纪元下时间戳位移后即超过 2^53，实际签发的ID几乎总是返回 `true`。
+    pub fn exceeds_js_safe_integer(id: u64) -> bool {
+        id > JS_MAX_SAFE_INTEGER
+    }
+
+    /// 按指定纪元批量解析雪花ID，结果与输入一一对应、顺序一致
+    pub fn parse_ids_with_epoch(ids: &[u64], epoch: u64) -> Vec<SnowflakeInfo> {
+        ids.iter().map(|&id| Self::parse_id_with_epoch(id, epoch)).collect()
+    }
+
+    /// 按指定纪元并行解析大批量ID，结果与输入一一对应、顺序一致
+    /// 
+    /// 输入按 `chunk_size` 切块分给 rayon 线程池，块越大调度开销越小、并行度越低；
+    /// 百万级以上的输入取数千到数万较合适。`chunk_size` 为0时按1处理。
+    #[cfg(feature = "rayon")]
+    pub fn batch_decode_par(ids: &[u64], epoch: u64, chunk_size: usize) -> Vec<SnowflakeInfo> {
+        use rayon::prelude::*;
+
+        ids.par_chunks(chunk_size.max(1))
+            .flat_map_iter(|chunk| chunk.iter().map(move |&id| Self::parse_id_with_epoch(id, epoch)))
+            .collect()
+    }
+
+    /// 批量解析雪花ID，构建以ID为键的 `HashMap`
+    pub fn parse_into_map(ids: &[u64]) -> HashMap<u64, SnowflakeInfo> {
+        ids.iter().map(|&id| (id, Self::parse_id(id))).collect()
+    }
+
+    /// 批量解析雪花ID，构建按ID（即按时间）有序的 `BTreeMap`
+    pub fn parse_into_btree_map(ids: &[u64]) -> BTreeMap<u64, SnowflakeInfo> {
+        ids.iter().map(|&id| (id, Self::parse_id(id))).collect()
+    }
+}
+
+impl<T: TimeProvider> Drop for Snowflake<T> {
+    /// 退出前补写最后一个持久化窗口内的进度
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("Failed to persist worker config on drop: {}", e);
+        }
+    }
+}
+
+impl TryFrom<WorkerInfo> for Snowflake {
+    type Error = WorkerError;
+
+    /// 由自定义代码加载的 `WorkerInfo` 直接构造生成器，不读写配置文件
+    /// 
+    /// 校验 worker/数据中心ID，并沿用 `last_timestamp` 与 `last_sequence`；
+    /// 时间戳略微领先本机时钟时等待追上，领先过多返回 `ClockBackwardsError`。
+    fn try_from(info: WorkerInfo) -> Result<Self, Self::Error> {
+        validate_ids(info.worker_id, info.datacenter_id)?;
+
+        let mut snowflake = Snowflake::new(info.worker_id, info.datacenter_id);
+        snowflake.last_timestamp = info.last_timestamp;
+        snowflake.sequence = info.last_sequence;
+        snowflake.rewind_safe_check()?;
+        Ok(snowflake)
+    }
+}
+
+/// 雪花ID解析信息结构体
+/// 
+/// 启用 `serde` feature 后可序列化，字段名与HTTP服务的解析响应一致，
+/// 并附带 `id_hex`、`timestamp_formatted`、`details` 计算字段（反序列化时忽略）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct SnowflakeInfo {
+    pub id: u64,
+    pub timestamp: u64,
+    pub datacenter_id: u64,
+    pub worker_id: u64,
+    pub sequence: u64,
+}
+
+impl SnowflakeInfo {
+    /// 获取 ISO-8601 格式的时间戳字符串（UTC，毫秒精度），如 `2022-01-01T00:00:00.100Z`
+    /// 
+    /// 不依赖 chrono；年份超过9999时按 ISO-8601 扩展格式输出带 `+` 号的年份。
+    pub fn timestamp_as_string(&self) -> String {
+        format_iso8601_millis(self.timestamp)
+    }
+
+    /// 时间戳对应的 UTC 时间，超出 chrono 可表示的范围时返回 `None`
+    #[cfg(feature = "chrono")]
+    pub fn datetime_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let millis = i64::try_from(self.timestamp).ok()?;
+        chrono::DateTime::from_timestamp_millis(millis)
+    }
+    
+    /// 假定 `self` 与 `later` 按生成顺序出现，检查两者的时间戳与序列号是否符合单调生成
+    /// 
+    /// 后者时间戳不得早于前者；时间戳相同时序列号必须严格递增。
+    /// 仅对同一生成器签发的ID有意义，不同 worker 同一毫秒内的ID本就无序。
+    pub fn clock_consistency_check(&self, later: &SnowflakeInfo) -> ClockConsistency {
+        if later.timestamp < self.timestamp {
+            ClockConsistency::TimestampReversed {
+                earlier_timestamp: self.timestamp,
+                later_timestamp: later.timestamp,
+            }
+        } else if later.timestamp == self.timestamp && later.sequence <= self.sequence {
+            ClockConsistency::SequenceNotIncreasing {
+                timestamp: self.timestamp,
+                earlier_sequence: self.sequence,
+                later_sequence: later.sequence,
+            }
+        } else {
+            ClockConsistency::Consistent
+        }
+    }
+
+    /// 类型标签（仅对 `next_id_of_type` 生成的ID有意义）
+    pub fn kind(&self) -> u8 {
+        extract_type_tag(self.id) as u8
+    }
+    
+    /// 获取ID的十六进制表示
+    pub fn id_as_hex(&self) -> String {
+        format!("0x{:016x}", self.id)
+    }
+    
+    /// 获取ID的 base62 表示，适合嵌入URL
+    pub fn id_as_base62(&self) -> String {
+        encode_base62(self.id)
+    }
+    
+    /// 获取ID的二进制表示（带分隔符）
+    pub fn id_as_binary(&self) -> String {
+        format!("{:064b}", self.id)
+    }
+    
+    /// 获取按字段分组的二进制表示：符号位|时间戳|数据中心ID|工作ID|序列号
+    pub fn id_as_binary_grouped(&self) -> String {
+        let bits = self.id_as_binary();
+        let boundaries = [
+            1,
+            64 - TIMESTAMP_SHIFT as usize,
+            64 - DATACENTER_ID_SHIFT as usize,
+            64 - WORKER_ID_SHIFT as usize,
+        ];
+
+        let mut grouped = String::with_capacity(bits.len() + boundaries.len());
+        let mut start = 0;
+        for end in boundaries {
+            grouped.push_str(&bits[start..end]);
+            grouped.push('|');
+            start = end;
+        }
+        grouped.push_str(&bits[start..]);
+        grouped
+    }
+    
+    /// 获取详细的格式化信息
+    pub fn format_details(&self) -> String {
+        format!(
+            "Snowflake ID: {}\n\
+             Hex: {}\n\
+             Binary: {}\n\
+             Timestamp: {} ({})\n\
+             Datacenter ID: {}\n\
+             Worker ID: {}\n\
+             Sequence: {}",
+            self.id,
+            self.id_as_hex(),
+            self.id_as_binary(),
+            self.timestamp,
+            self.timestamp_as_string(),
+            self.datacenter_id,
+            self.worker_id,
+            self.sequence
+        )
+    }
+}
+
+impl fmt::Display for SnowflakeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.format_details())
+    }
+}
+
+impl FromStr for SnowflakeInfo {
+    type Err = WorkerError;
+
+    /// 解析十进制、`0x` 前缀十六进制或二进制表示的ID，并按默认纪元解码
+    /// 
+    /// 二进制需带 `0b` 前缀，或为 `id_as_binary`/`id_as_binary_grouped` 输出的64位数字串。
+    /// 超出 `u64` 范围时返回 `ParseError`。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let binary_digits: String = s.chars().filter(|&c| c != '|').collect();
+        let parsed = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            u64::from_str_radix(hex, 16)
+        } else if let Some(binary) = s.strip_prefix("0b") {
+            u64::from_str_radix(binary, 2)
+        } else if binary_digits.len() == 64 && binary_digits.chars().all(|c| c == '0' || c == '1') {
+            u64::from_str_radix(&binary_digits, 2)
+        } else {
+            s.parse::<u64>()
+        };
+        let id = parsed.map_err(|e| WorkerError::ParseError(format!("invalid id '{}': {}", s, e)))?;
+        Ok(Snowflake::parse_id(id))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SnowflakeInfo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("SnowflakeInfo", 8)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("id_hex", &self.id_as_hex())?;
+        state.serialize_field("timestamp", &self.timestamp)?;
+        state.serialize_field("timestamp_formatted", &self.timestamp_as_string())?;
+        state.serialize_field("datacenter_id", &self.datacenter_id)?;
+        state.serialize_field("worker_id", &self.worker_id)?;
+        state.serialize_field("sequence", &self.sequence)?;
+        state.serialize_field("details", &self.format_details())?;
+        state.end()
+    }
+}
+
+impl From<SnowflakeParts> for SnowflakeInfo {
+    fn from(parts: SnowflakeParts) -> Self {
+        SnowflakeInfo {
+            id: parts.id,
+            timestamp: parts.timestamp,
+            datacenter_id: parts.datacenter_id,
+            worker_id: parts.worker_id,
+            sequence: parts.sequence,
+        }
+    }
+}
+
+/// `SnowflakeInfo::clock_consistency_check` 的检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockConsistency {
+    /// 符合单调生成
+    Consistent,
+    /// 后一个ID的时间戳早于前一个
+    TimestampReversed { earlier_timestamp: u64, later_timestamp: u64 },
+    /// 同一毫秒内后一个ID的序列号没有增大
+    SequenceNotIncreasing { timestamp: u64, earlier_sequence: u64, later_sequence: u64 },
+}
+
+impl ClockConsistency {
+    pub fn is_consistent(&self) -> bool {
+        matches!(self, ClockConsistency::Consistent)
+    }
+}
+
+/// 时间窗口对应的雪花ID范围
+/// 
+/// 由两个 `SystemTime` 构造，起止毫秒均包含在内，可直接作为 `Range<u64>`
+/// 交给数据库查询构建器或用于 `contains` 判断。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnowflakeTimeRange {
+    start_id: u64,
+    end_id: u64,
+}
+
+impl SnowflakeTimeRange {
+    pub fn new(start: SystemTime, end: SystemTime) -> Self {
+        let start_ms = system_time_millis(start);
+        let end_ms = system_time_millis(end).max(start_ms);
+        SnowflakeTimeRange {
+            start_id: min_id_for_timestamp(start_ms),
+            end_id: max_id_for_timestamp(end_ms).saturating_add(1),
+        }
+    }
+
+    /// 转换为半开区间 `min_id..max_id + 1`
+    pub fn as_range(&self) -> Range<u64> {
+        self.start_id..self.end_id
+    }
+}
+
+impl RangeBounds<u64> for SnowflakeTimeRange {
+    fn start_bound(&self) -> Bound<&u64> {
+        Bound::Included(&self.start_id)
+    }
+
+    fn end_bound(&self) -> Bound<&u64> {
+        Bound::Excluded(&self.end_id)
+    }
+}
+
+impl From<SnowflakeTimeRange> for Range<u64> {
+    fn from(range: SnowflakeTimeRange) -> Self {
+        range.as_range()
+    }
+}
+
+/// 将 Unix 毫秒时间戳格式化为 ISO-8601 UTC 字符串
+fn format_iso8601_millis(timestamp: u64) -> String {
+    let millis = timestamp % 1000;
+    let total_secs = timestamp / 1000;
+    let days = (total_secs / 86_400) as i64;
+    let secs_of_day = total_secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    let year = if year > 9999 {
+        format!("+{}", year)
+    } else {
+        format!("{:04}", year)
+    };
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day,
+        secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60,
+        millis
+    )
+}
+
+/// 将自 1970-01-01 起的天数换算为公历 (年, 月, 日)
+/// 
+/// 采用 Howard Hinnant 的 `civil_from_days` 算法，适用于任意范围的天数。
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn system_time_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// 示例用法和测试模块
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_snowflake_creation() {
+        let sf = Snowflake::new(1, 1);
+        assert_eq!(sf.get_worker_id(), 1);
+        assert_eq!(sf.get_datacenter_id(), 1);
+    }
+    
+    #[test]
+    fn test_id_generation() {
+        let mut sf = Snowflake::new(1, 1);
+        let id1 = sf.next_id().unwrap();
+        let id2 = sf.next_id().unwrap();
+        assert_ne!(id1, id2);
+    }
+
+    #[test]
+    fn test_from_existing_id() {
+        let provider = CachedTimeProvider::new(1);
+        let now = provider.current_millis();
+
+        for sequence in [0, 100, SEQUENCE_MASK] {
+            let last_id = build_snowflake_id(now, 3, 7, sequence);
+            let mut sf = Snowflake::from_existing_id(last_id, provider.clone()).unwrap();
+            assert_eq!(sf.get_worker_id(), 7);
+            assert_eq!(sf.get_datacenter_id(), 3);
+
+            let next = sf.next_id().unwrap();
+            assert!(next > last_id, "{} should exceed {}", next, last_id);
+        }
+    }
+
+    #[test]
+    fn test_fast_path_sequence_continuity() {
+        let mut sf = Snowflake::new(1, 1);
+        let mut prev = Snowflake::parse_id(sf.next_id().unwrap());
+
+        for _ in 0..20_000 {
+            let info = Snowflake::parse_id(sf.next_id().unwrap());
+            assert!(info.id > prev.id);
+            if info.timestamp == prev.timestamp {
+                assert_eq!(info.sequence, prev.sequence + 1);
+            } else {
+                assert_eq!(info.sequence, 0);
+            }
+            prev = info;
+        }
+    }
+
+    #[test]
+    fn test_next_id_tuple() {
+        let mut sf = Snowflake::new(5, 9);
+        for _ in 0..100 {
+            let (id, timestamp, datacenter_id, worker_id, sequence) = sf.next_id_tuple().unwrap();
+            let info = Snowflake::parse_id(id);
+            assert_eq!(timestamp, info.timestamp);
+            assert_eq!(datacenter_id, info.datacenter_id);
+            assert_eq!(worker_id, info.worker_id);
+            assert_eq!(sequence, info.sequence);
+        }
+
+        let info = sf.next_id_with_meta().unwrap();
+        assert_eq!((info.worker_id, info.datacenter_id), (5, 9));
+    }
+
+    #[test]
+    fn test_shutdown_saves_and_stops_time_provider() {
+        let test_file = "test_snowflake_shutdown.conf";
+        let _ = std::fs::remove_file(test_file);
+
+        let mut sf = Snowflake::new_with_config(test_file, 1).unwrap();
+        sf.set_persist_interval_ms(60_000);
+        let mut last_id = 0;
+        for _ in 0..1000 {
+            last_id = sf.next_id().unwrap();
+        }
+        let provider = Arc::downgrade(&sf.time_provider);
+
+        sf.shutdown().unwrap();
+
+        let content = std::fs::read_to_string(test_file).unwrap();
+        let info = WorkerInfo::from_file_content(&content).unwrap();
+        assert!(info.last_timestamp >= extract_timestamp(last_id));
+        // 后台线程已退出并释放了它持有的引用
+        assert!(provider.upgrade().is_none());
+
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_shutdown_keeps_shared_time_provider() {
+        let provider = CachedTimeProvider::new(1);
+        let sf = Snowflake::from_existing_id(build_snowflake_id(EPOCH, 1, 1, 0), provider.clone()).unwrap();
+        sf.shutdown().unwrap();
+
+        let before = provider.current_millis();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(provider.current_millis() > before);
+    }
+
+    #[test]
+    fn test_parse_id_with_layout() {
+        let mut sf = Snowflake::new(1, 1);
+        let id = sf.next_id().unwrap();
+        assert_eq!(Snowflake::parse_id_with_layout(id, SnowflakeLayout::DEFAULT, EPOCH), Snowflake::parse_id(id));
+
+        // 43/4/4/12：时间戳多2位，节点位各少1位
+        let layout = SnowflakeLayout::new(43, 4, 4, 12);
+        let id = (1234 << 20) | (9 << 16) | (3 << 12) | 77;
+        let info = Snowflake::parse_id_with_layout(id, layout, EPOCH);
+        assert_eq!(info.timestamp, EPOCH + 1234);
+        assert_eq!((info.datacenter_id, info.worker_id, info.sequence), (9, 3, 77));
+
+        // 按核心移位常量手工拼装的ID（基准测试里的生成器即如此）按默认布局解码回原字段
+        let id = (1234 << TIMESTAMP_SHIFT) | (1 << DATACENTER_ID_SHIFT) | (1 << WORKER_ID_SHIFT) | 5;
+        let info = Snowflake::parse_id_with_layout(id, SnowflakeLayout::DEFAULT, EPOCH);
+        assert_eq!((info.timestamp, info.datacenter_id, info.worker_id, info.sequence), (EPOCH + 1234, 1, 1, 5));
+    }
+
+    #[test]
+    fn test_benchmark_layouts() {
+        let tiny_sequence = SnowflakeLayout::new(51, 3, 5, 4);
+        let too_few_nodes = SnowflakeLayout::new(41, 1, 1, 20);
+        let results = Snowflake::benchmark_layouts(
+            2_000_000,
+            16,
+            &[SnowflakeLayout::DEFAULT, tiny_sequence, too_few_nodes],
+        );
+
+        // 每节点 125 个/毫秒，12位序列号绰绰有余
+        assert!(results[0].sustains);
+        assert_eq!(results[0].rollover_ratio, 0.0);
+        assert!(results[0].lifetime_years > 69.0);
+
+        // 4位序列号每毫秒只有16个，持续积压
+        assert!(!results[1].sustains);
+        assert!(results[1].rollover_ratio > 0.9);
+        assert!(results[1].backlog > 0);
+
+        assert!(!results[2].fits_nodes);
+        assert!(!results[2].sustains);
+
+        // 位数超出63位的候选不会在计算容量时 panic，只是不可支撑
+        let invalid = Snowflake::benchmark_layouts(2_000_000, 16, &[SnowflakeLayout::new(41, 5, 5, 64)]);
+        assert_eq!(invalid.len(), 1);
+        assert!(!invalid[0].sustains);
+    }
+
+    #[test]
+    fn test_total_generated_across_threads() {
+        let shared = crate::shared::SharedSnowflake::new(1, 1);
+        let counter = shared.lock().generation_counter();
+        assert_eq!(counter.get(), 0);
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    (0..500).filter(|_| shared.next_id().is_ok()).count() + shared.next_ids(100).unwrap().len()
+                })
+            })
+            .collect();
+        let successes: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(successes, 8 * 600);
+        assert_eq!(counter.get(), successes as u64);
+        assert_eq!(shared.lock().total_generated(), successes as u64);
+
+        // 失败的调用不计数
+        let mut snowflake = Snowflake::new(1, 1);
+        snowflake.next_id_at(EPOCH + 10).unwrap();
+        assert!(snowflake.next_id_at(EPOCH + 5).is_err());
+        snowflake.next_id_of_type(2).unwrap();
+        assert_eq!(snowflake.total_generated(), 2);
+    }
+
+    #[test]
+    fn test_storage_comparison() {
+        let comparison = Snowflake::storage_comparison(1_000_000);
+        assert_eq!(comparison.snowflake_key_bytes, 8_000_000);
+        assert_eq!(comparison.uuid_key_bytes, 16_000_000);
+        assert_eq!(comparison.key_bytes_saved(), 8_000_000);
+        // 16字节条目 / 90% 填充 与 24字节条目 / 69% 填充
+        assert_eq!(comparison.snowflake_index_bytes, 17_777_778);
+        assert_eq!(comparison.uuid_index_bytes, 34_782_609);
+        assert_eq!(comparison.index_bytes_saved(), 17_004_831);
+        assert_eq!(comparison.total_bytes_saved(), 25_004_831);
+
+        assert_eq!(Snowflake::storage_comparison(0).total_bytes_saved(), 0);
+        let huge = Snowflake::storage_comparison(u64::MAX);
+        assert_eq!(huge.uuid_key_bytes, u64::MAX);
+        assert_eq!(huge.uuid_index_bytes, u64::MAX);
+    }
+
+    #[test]
+    fn test_discord_ids() {
+        // Discord 开发者文档中的示例ID
+        let info = Snowflake::parse_discord_id(175928847299117063);
+        assert_eq!(info.timestamp, 1462015105796);
+        assert_eq!(info.datacenter_id, 1);
+        assert_eq!(info.worker_id, 0);
+        assert_eq!(info.sequence, 7);
+
+        let mut sf = Snowflake::discord(1, 0);
+        let id = sf.next_id().unwrap();
+        assert!(id > 175928847299117063);
+        let info = Snowflake::parse_discord_id(id);
+        assert!(info.timestamp.abs_diff(sf.current_millis()) < 1000);
+        assert_eq!((info.datacenter_id, info.worker_id), (1, 0));
+
+        let reservation = sf.reserve_ids(10).unwrap();
+        assert_eq!(Snowflake::parse_discord_id(reservation.start()).timestamp, reservation.timestamp);
+    }
+
+    #[test]
+    fn test_new_with_epoch() {
+        let mut sf = Snowflake::new_with_epoch(2, 3, DISCORD_EPOCH).unwrap();
+        assert_eq!(sf.get_epoch_millis(), DISCORD_EPOCH);
+
+        let id = sf.next_id().unwrap();
+        let info = Snowflake::parse_id_with_epoch(id, DISCORD_EPOCH);
+        assert!(info.timestamp.abs_diff(sf.current_millis()) < 1000);
+        assert_eq!((info.datacenter_id, info.worker_id), (3, 2));
+
+        let future = system_time_millis(SystemTime::now()) + 60_000;
+        assert!(matches!(Snowflake::new_with_epoch(2, 3, future), Err(WorkerError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_from_hostname_ordinal() {
+        let mut sf = Snowflake::from_hostname_ordinal("app-7", 2).unwrap();
+        assert_eq!(sf.get_worker_id(), 7);
+        assert_eq!(sf.get_datacenter_id(), 2);
+        assert_eq!(extract_worker_id(sf.next_id().unwrap()), 7);
+
+        assert!(Snowflake::from_hostname_ordinal("app-7", 32).is_err());
+        assert!(Snowflake::from_hostname_ordinal("app-40", 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_signed_str() {
+        // 最高位为1的ID在 Java 中输出为负数
+        let info = Snowflake::parse_signed_str("-9223372036854108118").unwrap();
+        assert_eq!(info.id, 9223372036855443498);
+        assert_eq!(info.id, (1 << 63) | (5 << DATACENTER_ID_SHIFT) | (3 << WORKER_ID_SHIFT) | 42);
+        assert_eq!(info.timestamp, (1 << 41) + EPOCH);
+        assert_eq!(info.datacenter_id, 5);
+        assert_eq!(info.worker_id, 3);
+        assert_eq!(info.sequence, 42);
+
+        let id = build_snowflake_id(1640995200100, 1, 2, 3);
+        assert_eq!(Snowflake::parse_signed_str(&id.to_string()).unwrap().id, id);
+
+        assert!(matches!(Snowflake::parse_signed_str("-"), Err(WorkerError::ParseError(_))));
+        assert!(Snowflake::parse_signed_str("-9223372036854775809").is_err());
+        assert!(Snowflake::parse_signed_str("abc").is_err());
+    }
+
+    #[test]
+    fn test_from_profile() {
+        let mut snowflake = Snowflake::from_profile("discord", 3, 1).unwrap();
+        let profile = FormatProfile::load("discord").unwrap();
+        assert_eq!(snowflake.get_epoch_millis(), profile.epoch_millis);
+        assert_eq!(snowflake.layout(), profile.layout);
+
+        let id = snowflake.next_id().unwrap();
+        assert_eq!(profile.parse_id(id), Snowflake::parse_discord_id(id));
+        assert_eq!((profile.parse_id(id).worker_id, profile.parse_id(id).datacenter_id), (3, 1));
+
+        assert!(matches!(Snowflake::from_profile("discord", 32, 0), Err(WorkerError::InvalidId(_))));
+        assert!(matches!(Snowflake::from_profile("no_such_profile", 1, 1), Err(WorkerError::IoError(_))));
+    }
+
+    #[test]
+    fn test_from_format_profile_uses_profile_layout() {
+        let wide_workers = FormatProfile::from_toml_str(
+            "wide_workers",
+            &format!("epoch_millis = {}\ndatacenter_bits = 3\nworker_bits = 7", EPOCH),
+        ).unwrap();
+        let mut snowflake = Snowflake::from_format_profile(&wide_workers, 100, 5).unwrap();
+        assert_eq!(snowflake.layout(), wide_workers.layout);
+        assert_ne!(snowflake.layout(), SnowflakeLayout::DEFAULT);
+
+        let info = snowflake.next_id_with_meta().unwrap();
+        assert_eq!(wide_workers.parse_id(info.id), info);
+        assert_eq!((info.worker_id, info.datacenter_id), (100, 5));
+        let (id, _, datacenter_id, worker_id, _) = snowflake.next_id_tuple().unwrap();
+        assert_eq!((worker_id, datacenter_id), (100, 5));
+        assert_eq!(wide_workers.parse_id(id).worker_id, 100);
+
+        assert!(matches!(Snowflake::from_format_profile(&wide_workers, 128, 0), Err(WorkerError::InvalidId(_))));
+        assert!(matches!(Snowflake::from_format_profile(&wide_workers, 0, 8), Err(WorkerError::InvalidId(_))));
+        assert!(matches!(snowflake.set_datacenter_id(8), Err(WorkerError::InvalidId(_))));
+        assert!(matches!(snowflake.next_id_of_type(1), Err(WorkerError::InvalidArgument(_))));
+        assert!(matches!(snowflake.reserve_ids(10), Err(WorkerError::InvalidArgument(_))));
+
+        // 较短的序列号字段：每毫秒只有256个序列号，用完后进入下一毫秒
+        let short_sequence = FormatProfile::from_toml_str(
+            "short_sequence",
+            &format!("epoch_millis = {}\nsequence_bits = 8\nreserved_bits = 4", EPOCH),
+        ).unwrap();
+        let mut snowflake = Snowflake::from_format_profile(&short_sequence, 3, 2).unwrap();
+        assert_eq!(snowflake.max_sequence_per_ms(), 256);
+        let ids = snowflake.next_ids(1_000).unwrap();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        for id in ids {
+            let info = short_sequence.parse_id(id);
+            assert_eq!((info.worker_id, info.datacenter_id), (3, 2));
+            assert_eq!(id & short_sequence.layout.reserved_mask(), 0);
+        }
+    }
+
+    #[test]
+    fn test_next_id_at_uses_given_timestamp() {
+        let mut snowflake = Snowflake::new(3, 2);
+        let base = EPOCH + 1_000_000;
+
+        let first = snowflake.next_id_at(base).unwrap();
+        let second = snowflake.next_id_at(base).unwrap();
+        let third = snowflake.next_id_at(base + 5).unwrap();
+        assert_eq!(first, build_snowflake_id(base, 2, 3, 0));
+        assert_eq!(second, build_snowflake_id(base, 2, 3, 1));
+        assert_eq!(third, build_snowflake_id(base + 5, 2, 3, 0));
+
+        // 同样的调用序列得到同样的ID
+        let mut replay = Snowflake::new(3, 2);
+        assert_eq!(replay.next_id_at(base).unwrap(), first);
+
+        assert!(matches!(snowflake.next_id_at(base + 4), Err(WorkerError::ClockBackwardsError(_))));
+        assert!(matches!(snowflake.next_id_at(EPOCH - 1), Err(WorkerError::InvalidArgument(_))));
+        assert!(matches!(
+            snowflake.next_id_at(EPOCH + MAX_TIMESTAMP_OFFSET + 1),
+            Err(WorkerError::InvalidArgument(_))
+        ));
+        assert!(snowflake.health_report().last_error.unwrap().contains("exceeds"));
+    }
+
+    #[test]
+    fn test_next_id_at_sequence_exhaustion() {
+        let mut snowflake = Snowflake::new(1, 1);
+        let ts = EPOCH + MAX_TIMESTAMP_OFFSET;
+        for expected in 0..=SEQUENCE_MASK {
+            assert_eq!(extract_sequence(snowflake.next_id_at(ts).unwrap()), expected);
+        }
+        assert!(matches!(snowflake.next_id_at(ts), Err(WorkerError::InvalidArgument(_))));
+        // 失败的调用没有推进到下一毫秒，不是一次翻转
+        assert_eq!(snowflake.health_report().rollover_count, 0);
+    }
+
+    #[test]
+    fn test_rollover_count_skips_timed_out_waits() {
+        let clock = Arc::new(ManualClock::new(EPOCH + 1_000));
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::clone(&clock));
+        sf.set_max_backwards_wait(Duration::from_millis(5));
+        sf.next_ids(SEQUENCE_MASK as usize + 1).unwrap();
+
+        // 时钟停住，等待超时失败，不算翻转
+        assert!(matches!(sf.next_id(), Err(WorkerError::ClockBackwardsError(_))));
+        assert_eq!(sf.get_rollover_count(), 0);
+
+        // 同一毫秒用完后推进到下一毫秒才算一次
+        let waiter = std::thread::spawn({
+            let clock = Arc::clone(&clock);
+            move || {
+                std::thread::sleep(Duration::from_millis(2));
+                clock.set(EPOCH + 1_001);
+            }
+        });
+        sf.set_max_backwards_wait(Duration::from_secs(1));
+        assert_eq!(extract_timestamp(sf.next_id().unwrap()), EPOCH + 1_001);
+        waiter.join().unwrap();
+        assert_eq!(sf.get_rollover_count(), 1);
+    }
+
+    #[test]
+    fn test_snowflake_info_from_parts() {
+        let id = build_snowflake_id_with_epoch(EPOCH + 12_345, DISCORD_EPOCH, 4, 9, 77);
+        assert_eq!(SnowflakeInfo::from(SnowflakeParts::decode_with_epoch(id, DISCORD_EPOCH)), Snowflake::parse_discord_id(id));
+    }
+
+    #[test]
+    fn test_clock_consistency_check() {
+        let earlier = Snowflake::parse_id(build_snowflake_id(EPOCH + 1000, 1, 1, 5));
+        let later = Snowflake::parse_id(build_snowflake_id(EPOCH + 1000, 1, 1, 6));
+        let next_ms = Snowflake::parse_id(build_snowflake_id(EPOCH + 1001, 1, 1, 0));
+        assert_eq!(earlier.clock_consistency_check(&later), ClockConsistency::Consistent);
+        assert!(later.clock_consistency_check(&next_ms).is_consistent());
+
+        assert_eq!(
+            next_ms.clock_consistency_check(&earlier),
+            ClockConsistency::TimestampReversed { earlier_timestamp: EPOCH + 1001, later_timestamp: EPOCH + 1000 }
+        );
+        assert_eq!(
+            later.clock_consistency_check(&earlier),
+            ClockConsistency::SequenceNotIncreasing { timestamp: EPOCH + 1000, earlier_sequence: 6, later_sequence: 5 }
+        );
+        assert!(!earlier.clock_consistency_check(&earlier).is_consistent());
+    }
+
+    #[test]
+    fn test_snowflake_info_from_str_round_trip() {
+        let id = build_snowflake_id(1640995200100, 1, 2, 3);
+        let info = Snowflake::parse_id(id);
+
+        for text in [id.to_string(), info.id_as_hex(), info.id_as_binary(), info.id_as_binary_grouped()] {
+            assert_eq!(text.parse::<SnowflakeInfo>().unwrap(), info, "failed to parse {}", text);
+        }
+        assert_eq!(format!("0b{:b}", id).parse::<SnowflakeInfo>().unwrap(), info);
+        assert_eq!(info.to_string(), info.format_details());
+
+        assert!(matches!("18446744073709551616".parse::<SnowflakeInfo>(), Err(WorkerError::ParseError(_))));
+        assert!("0x1ffffffffffffffff".parse::<SnowflakeInfo>().is_err());
+        assert!("0xzz".parse::<SnowflakeInfo>().is_err());
+    }
+
+    #[test]
+    fn test_with_initial_timestamp() {
+        let start_ms = 1640995200000;
+        let mut sf = Snowflake::with_initial_timestamp(1, 2, start_ms);
+
+        let first = Snowflake::parse_id(sf.next_id().unwrap());
+        assert_eq!(first.timestamp, start_ms);
+        assert_eq!(first.sequence, 0);
+        assert_eq!(extract_timestamp(sf.next_id().unwrap()), start_ms + 1);
+
+        // 相同参数总是得到相同的ID序列
+        let mut a = Snowflake::with_initial_timestamp(1, 2, start_ms);
+        let mut b = Snowflake::with_initial_timestamp(1, 2, start_ms);
+        assert_eq!(a.next_ids(10).unwrap(), b.next_ids(10).unwrap());
+    }
+
+    #[test]
+    fn test_new_after_blocks_until_barrier() {
+        let started = Instant::now();
+        let barrier_ms = system_time_millis(SystemTime::now()) + 50;
+        let mut sf = Snowflake::new_after(1, 2, barrier_ms).unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(40));
+        assert!(system_time_millis(SystemTime::now()) >= barrier_ms);
+        assert!(extract_timestamp(sf.next_id().unwrap()) >= barrier_ms);
+
+        // 屏障已过时立即返回
+        assert!(Snowflake::new_after(1, 2, EPOCH).is_ok());
+        assert!(matches!(Snowflake::new_after(32, 2, EPOCH), Err(WorkerError::InvalidId(_))));
+        let far = system_time_millis(SystemTime::now()) + MAX_BARRIER_WAIT.as_millis() as u64 + 60_000;
+        assert!(matches!(Snowflake::new_after(1, 2, far), Err(WorkerError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_try_parse_id_with_allowlist() {
+        let filter = IdFilter::default().allow_datacenters([1, 2]);
+        let allowed = Snowflake::new(3, 2).next_id().unwrap();
+        let foreign = Snowflake::new(3, 7).next_id().unwrap();
+
+        assert_eq!(Snowflake::try_parse_id(allowed, &filter).unwrap().datacenter_id, 2);
+        assert!(matches!(Snowflake::try_parse_id(foreign, &filter), Err(WorkerError::RejectedId(_))));
+        assert!(Snowflake::try_parse_id(foreign, &IdFilter::default()).is_ok());
+        assert!(matches!(Snowflake::try_parse_id(1 << 63, &IdFilter::default()), Err(WorkerError::InvalidId(_))));
+
+        let filter = filter.allow_workers([3]).with_predicate(|info| info.sequence == 0);
+        assert!(Snowflake::try_parse_id(allowed, &filter).is_ok());
+        assert!(matches!(Snowflake::try_parse_id(allowed | 1, &filter), Err(WorkerError::RejectedId(_))));
+        assert!(!filter.is_allow_all());
+    }
+
+    #[test]
+    fn test_projected_timestamps_span_milliseconds() {
+        let frozen = 1640995200000;
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(FixedClock(frozen)));
+        let capacity = sf.max_sequence_per_ms() as usize;
+
+        assert!(sf.projected_timestamps(0).is_empty());
+        assert_eq!(sf.projected_timestamps(capacity), vec![frozen]);
+        assert_eq!(sf.projected_timestamps(2 * capacity + 1), vec![frozen, frozen + 1, frozen + 2]);
+
+        // 当前毫秒已用掉10个序列号
+        sf.next_ids(10).unwrap();
+        assert_eq!(sf.projected_timestamps(capacity - 10), vec![frozen]);
+        assert_eq!(sf.projected_timestamps(capacity - 9), vec![frozen, frozen + 1]);
+    }
+
+    #[test]
+    fn test_new_relative_stays_monotonic() {
+        let wall = Arc::new(ManualClock::new(system_time_millis(SystemTime::now())));
+        let mut direct = Snowflake::with_time_provider(1, 2, wall.clone());
+        let mut relative = Snowflake::with_time_provider(1, 2, Arc::new(RelativeTimeProvider::from_base(wall.as_ref())));
+        let mut ids = relative.next_ids(1000).unwrap();
+        direct.next_id().unwrap();
+
+        // 墙上时钟被回拨一秒：直接读取它的生成器检测到回拨，单调时钟不受影响
+        wall.set(wall.current_millis() - 1_000);
+        assert!(matches!(direct.next_id(), Err(WorkerError::ClockBackwardsError(_))));
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(2));
+            ids.extend(relative.next_ids(1000).unwrap());
+        }
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        let info = Snowflake::parse_id(Snowflake::new_relative(1, 2).next_id().unwrap());
+        assert_eq!((info.worker_id, info.datacenter_id), (1, 2));
+    }
+
+    #[test]
+    fn test_collision_free_window_shrinks() {
+        let window_at = |millis| Snowflake::with_time_provider(1, 1, Arc::new(FixedClock(millis))).collision_free_window();
+        assert_eq!(Snowflake::new(1, 1).max_representable_timestamp(), EPOCH + MAX_TIMESTAMP_OFFSET);
+
+        assert_eq!(window_at(EPOCH), Duration::from_millis(MAX_TIMESTAMP_OFFSET));
+
+        let now = system_time_millis(SystemTime::now());
+        let window = window_at(now);
+        assert!(window > Duration::ZERO);
+        assert_eq!(window_at(now + 60_000), window - Duration::from_secs(60));
+        assert_eq!(window_at(EPOCH + MAX_TIMESTAMP_OFFSET + 1), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_capacity_constants() {
+        assert_eq!(Snowflake::MAX_SEQUENCE_PER_MS, 4096);
+        assert_eq!(Snowflake::MAX_IDS_PER_SECOND, 4_096_000);
+
+        let sf = Snowflake::new(1, 1);
+        assert_eq!(sf.max_sequence_per_ms(), Snowflake::MAX_SEQUENCE_PER_MS);
+        assert_eq!(sf.max_ids_per_second(), Snowflake::MAX_IDS_PER_SECOND);
+    }
+
+    #[test]
+    fn test_restore_slightly_ahead_timestamp() {
+        let test_file = "test_restore_ahead.conf";
+        let mut info = WorkerInfo::new(4, 1);
+        let restored = system_time_millis(SystemTime::now()) + 200;
+        info.last_timestamp = restored;
+        std::fs::write(test_file, info.to_file_content()).unwrap();
+
+        let mut sf = Snowflake::new_with_config(test_file, 1).unwrap();
+        let id = sf.next_id().unwrap();
+        assert!(extract_timestamp(id) > restored);
+        drop(sf);
+
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_next_id_detailed_flags() {
+        let start = EPOCH + 1_000;
+        let mut sf = Snowflake::with_initial_timestamp(1, 1, start);
+
+        let first = sf.next_id_detailed().unwrap();
+        assert!(first.new_millisecond);
+        assert!(!first.spun);
+        assert_eq!(first.spin_duration, Duration::ZERO);
+
+        // 步进时钟下一次读取为 start + 1，预先占用该毫秒
+        sf.last_timestamp = start + 1;
+        sf.sequence = 0;
+        let same_ms = sf.next_id_detailed().unwrap();
+        assert!(!same_ms.new_millisecond);
+        assert!(!same_ms.spun);
+        assert_eq!(extract_sequence(same_ms.id), 1);
+
+        sf.last_timestamp = start + 2;
+        sf.sequence = SEQUENCE_MASK;
+        let rollover = sf.next_id_detailed().unwrap();
+        assert!(rollover.spun);
+        assert!(rollover.new_millisecond);
+        assert_eq!(extract_timestamp(rollover.id), start + 3);
+        assert_eq!(extract_sequence(rollover.id), 0);
+    }
+
+    #[test]
+    fn test_stall_fallback_with_frozen_clock() {
+        let frozen = EPOCH + 10_000;
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(FixedClock(frozen)));
+        sf.set_stall_fallback(Some(Duration::from_millis(1)));
+
+        let count = 3 * Snowflake::MAX_SEQUENCE_PER_MS as usize + 10;
+        let ids = sf.next_ids(count).unwrap();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(extract_timestamp(ids[0]), frozen);
+        assert_eq!(extract_timestamp(ids[count - 1]), frozen + 3);
+    }
+
+    #[test]
+    fn test_batch_claims_each_millisecond_at_once() {
+        let test_file = "test_batch_claims_ms.conf";
+        let _ = std::fs::remove_file(test_file);
+        let clock = Arc::new(ManualClock::new(system_time_millis(SystemTime::now()) + 1_000));
+        let mut sf = Snowflake::with_config_and_time_provider(test_file, 1, clock).unwrap();
+        sf.set_persist_interval_ms(0);
+        sf.set_stall_fallback(Some(Duration::from_millis(1)));
+        let saves_before = sf.get_save_count();
+
+        let count = 2 * Snowflake::MAX_SEQUENCE_PER_MS as usize + 10;
+        let ids = sf.next_ids(count).unwrap();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sf.total_generated(), count as u64);
+        // 每个毫秒只在完整生成路径与补写剩余序列号时各写一次，而不是每个ID写一次
+        assert_eq!(sf.get_save_count() - saves_before, 6);
+
+        drop(sf);
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    /// 返回固定时间的模拟时钟
+    struct FixedClock(u64);
+
+    impl TimeProvider for FixedClock {
+        fn current_millis(&self) -> u64 {
+            self.0
+        }
+    }
+
+    /// 每读取 `reads_per_ms` 次前进1毫秒的模拟时钟，推进只取决于读取次数而与真实耗时无关
+    struct TickingClock {
+        start: u64,
+        reads_per_ms: u64,
+        reads: AtomicU64,
+    }
+
+    impl TickingClock {
+        fn new(start: u64, reads_per_ms: u64) -> Self {
+            TickingClock { start, reads_per_ms, reads: AtomicU64::new(0) }
+        }
+    }
+
+    impl TimeProvider for TickingClock {
+        fn current_millis(&self) -> u64 {
+            self.start + self.reads.fetch_add(1, Ordering::Relaxed) / self.reads_per_ms
+        }
+    }
+
+    /// 只在测试显式调整时才变化的模拟时钟
+    struct ManualClock(AtomicU64);
+
+    impl ManualClock {
+        fn new(millis: u64) -> Self {
+            ManualClock(AtomicU64::new(millis))
+        }
+
+        fn set(&self, millis: u64) {
+            self.0.store(millis, Ordering::Relaxed);
+        }
+    }
+
+    impl TimeProvider for ManualClock {
+        fn current_millis(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn test_with_time_provider() {
+        let fixed = EPOCH + 42_000;
+        let mut sf = Snowflake::with_time_provider(5, 6, Arc::new(FixedClock(fixed)));
+        let ids = sf.next_ids(100).unwrap();
+        assert!(ids.iter().all(|&id| extract_timestamp(id) == fixed));
+        assert_eq!(extract_sequence(ids[99]), 99);
+
+        let mut system = Snowflake::with_time_provider(1, 1, Arc::new(crate::SystemTimeProvider));
+        let id = system.next_id().unwrap();
+        assert!(extract_timestamp(id).abs_diff(system_time_millis(SystemTime::now())) < 1000);
+
+        let default: DefaultSnowflake = Snowflake::new(1, 1);
+        assert_eq!(default.get_worker_id(), 1);
+    }
+
+    #[test]
+    fn test_drift_grows_with_stalled_clock() {
+        let stalled = system_time_millis(SystemTime::now());
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(FixedClock(stalled)));
+        assert_eq!(sf.last_id_timestamp_drift_ms(), None);
+
+        sf.next_id().unwrap();
+        let before = sf.last_id_timestamp_drift_ms().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        sf.next_id().unwrap();
+        let after = sf.last_id_timestamp_drift_ms().unwrap();
+        assert!(after >= before + 50, "drift went from {} to {}", before, after);
+    }
+
+    #[test]
+    fn test_health_report_degraded_when_saturated() {
+        let now = system_time_millis(SystemTime::now());
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(FixedClock(now)));
+        sf.next_ids(10).unwrap();
+        let report = sf.health_report();
+        assert_eq!(report.status, HealthStatus::Healthy);
+        assert_eq!(report.rollover_count, 0);
+        assert!(report.saturation < 0.01);
+
+        // 时钟停在同一毫秒，用完整个序列号空间
+        sf.next_ids(Snowflake::MAX_SEQUENCE_PER_MS as usize - 10).unwrap();
+        let report = sf.health_report();
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(report.saturation, 1.0);
+        assert_eq!(report.last_error, None);
+    }
+
+    #[test]
+    fn test_health_report_unhealthy_after_error() {
+        let provider = CachedTimeProvider::new(1);
+        let future = build_snowflake_id(provider.current_millis() + 3_600_000, 1, 1, 0);
+        let mut sf = Snowflake::from_existing_id(future, provider).unwrap();
+        assert!(sf.next_id().is_err());
+
+        let report = sf.health_report();
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        assert!(report.last_error.unwrap().starts_with("Clock backwards error"));
+    }
+
+    #[test]
+    fn test_quota_resets_after_window() {
+        let mut sf = Snowflake::new(1, 1);
+        sf.set_quota(Some(IdQuota::per_window(5, Duration::from_millis(50))));
+        for _ in 0..5 {
+            sf.next_id().unwrap();
+        }
+        assert_eq!(sf.remaining_quota(), Some(0));
+        assert!(matches!(sf.next_id(), Err(WorkerError::QuotaExceeded(_))));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(sf.next_id().is_ok());
+        assert_eq!(sf.remaining_quota(), Some(4));
+
+        sf.set_quota(Some(IdQuota::lifetime(2)));
+        assert!(sf.next_ids(3).is_err());
+        assert_eq!(sf.remaining_quota(), Some(0));
+    }
+
+    #[test]
+    fn test_quota_covers_reservations_and_typed_ids() {
+        let mut sf = Snowflake::new(1, 1);
+        sf.set_quota(Some(IdQuota::lifetime(100)));
+        sf.reserve_ids(60).unwrap();
+        assert_eq!(sf.remaining_quota(), Some(40));
+        assert!(matches!(sf.reserve_ids(41), Err(WorkerError::QuotaExceeded(_))));
+        assert_eq!(sf.remaining_quota(), Some(40));
+
+        sf.reserve_ids(39).unwrap();
+        sf.next_id_of_type(1).unwrap();
+        assert_eq!(sf.remaining_quota(), Some(0));
+        assert!(matches!(sf.next_id_of_type(1), Err(WorkerError::QuotaExceeded(_))));
+        assert!(sf.health_report().last_error.unwrap().starts_with("Quota exceeded"));
+    }
+
+    #[test]
+    fn test_iter_take_collect() {
+        let mut sf = Snowflake::new(1, 1);
+        let ids = sf.iter().take(1000).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(ids.len(), 1000);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        // 迭代器释放借用后生成器可继续使用
+        assert!(sf.next_id().unwrap() > ids[999]);
+    }
+
+    #[test]
+    fn test_iter_stops_after_first_error() {
+        let mut sf = Snowflake::new(1, 1);
+        sf.set_quota(Some(IdQuota::lifetime(3)));
+        let mut iter = sf.iter();
+        assert!(iter.by_ref().take(3).all(|r| r.is_ok()));
+        assert!(matches!(iter.next(), Some(Err(WorkerError::QuotaExceeded(_)))));
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_batch_decode_par_preserves_order() {
+        // 乱序且跨越多个毫秒、worker 的ID
+        let ids: Vec<u64> = (0..1_000_000u64)
+            .map(|i| build_snowflake_id(EPOCH + (i * 7919) % 100_000, i % 32, (i / 32) % 32, i % 4096))
+            .collect();
+        let serial = Snowflake::parse_ids_with_epoch(&ids, EPOCH);
+
+        for chunk_size in [1000, 65_536, ids.len() * 2] {
+            assert_eq!(Snowflake::batch_decode_par(&ids, EPOCH, chunk_size), serial);
+        }
+        // chunk_size 为0时按1处理
+        assert_eq!(Snowflake::batch_decode_par(&ids[..10_000], EPOCH, 0), serial[..10_000]);
+    }
+
+    #[test]
+    fn test_generate_into_hash_set() {
+        let mut sf = Snowflake::new(1, 1);
+        let mut ids = std::collections::HashSet::new();
+        sf.generate_into(10_000, &mut ids).unwrap();
+        assert_eq!(ids.len(), 10_000);
+    }
+
+    #[test]
+    fn test_audit_log_keeps_last_ids() {
+        let mut sf = Snowflake::new(1, 1);
+        assert!(sf.recent_ids().is_empty());
+
+        sf.enable_audit_log(100);
+        let ids = sf.next_ids(250).unwrap();
+        assert_eq!(sf.recent_ids(), ids[150..]);
+
+        sf.enable_audit_log(0);
+        sf.next_id().unwrap();
+        assert!(sf.recent_ids().is_empty());
+    }
+
+    #[test]
+    fn test_try_from_worker_info() {
+        let mut info = WorkerInfo::new(7, 2);
+        info.last_timestamp = system_time_millis(SystemTime::now()) + 50;
+
+        let mut sf = Snowflake::try_from(info.clone()).unwrap();
+        assert_eq!(sf.get_worker_id(), 7);
+        assert_eq!(sf.get_datacenter_id(), 2);
+        assert!(extract_timestamp(sf.next_id().unwrap()) > info.last_timestamp);
+
+        info.worker_id = MAX_WORKER_ID + 1;
+        assert!(matches!(Snowflake::try_from(info), Err(WorkerError::InvalidId(_))));
+    }
+
+    #[test]
+    fn test_next_ids_across_rollover() {
+        let count = 3 * Snowflake::MAX_SEQUENCE_PER_MS as usize + 100;
+        let mut sf = Snowflake::new(1, 1);
+        let ids = sf.next_ids(count).unwrap();
+
+        assert_eq!(ids.len(), count);
+        assert_eq!(ids.capacity(), count);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(ids.iter().collect::<std::collections::HashSet<_>>().len(), count);
+    }
+
+    #[test]
+    fn test_capacity_hint_batches() {
+        let hint = 5000;
+        let mut sf = Snowflake::new(1, 1).with_capacity_hint(hint);
+
+        for size in [1000, 2500, 5000, 4999] {
+            let ids = sf.next_ids(size).unwrap();
+            assert_eq!(ids.len(), size);
+            // 容量等于提示值，说明只分配了一次且没有扩容
+            assert_eq!(ids.capacity(), hint);
+        }
+    }
+
+    #[test]
+    fn test_parse_into_map() {
+        let mut sf = Snowflake::new(3, 2);
+        let ids = sf.next_ids(100).unwrap();
+
+        let map = Snowflake::parse_into_map(&ids);
+        assert_eq!(map.len(), ids.len());
+        for id in &ids {
+            let info = &map[id];
+            assert_eq!(info.id, *id);
+            assert_eq!(info.worker_id, 3);
+            assert_eq!(info.datacenter_id, 2);
+            assert_eq!(info.timestamp, extract_timestamp(*id));
+            assert_eq!(info.sequence, extract_sequence(*id));
+        }
+
+        let mut reversed = ids.clone();
+        reversed.reverse();
+        let btree = Snowflake::parse_into_btree_map(&reversed);
+        assert!(btree.keys().copied().eq(ids.iter().copied()));
+        assert!(btree.values().zip(btree.values().skip(1)).all(|(a, b)| a.timestamp <= b.timestamp));
+
+        let infos = Snowflake::parse_ids_with_epoch(&reversed, DISCORD_EPOCH);
+        assert!(infos.iter().map(|info| info.id).eq(reversed.iter().copied()));
+        assert_eq!(infos[0].timestamp, extract_timestamp_with_epoch(reversed[0], DISCORD_EPOCH));
+    }
+
+    #[test]
+    fn test_smoothing_reduces_spins() {
+        let burst = 20_000;
+
+        let mut default_sf = Snowflake::with_time_provider(1, 1, Arc::new(TickingClock::new(EPOCH + 1_000, 1_000)));
+        default_sf.next_ids(burst).unwrap();
+
+        let mut smooth_sf = Snowflake::with_time_provider(2, 1, Arc::new(TickingClock::new(EPOCH + 1_000, 1_000)));
+        smooth_sf.set_smoothing(Some(SmoothingConfig {
+            threshold: 2048,
+            pause: Duration::ZERO,
+        }));
+        let ids = smooth_sf.next_ids(burst).unwrap();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        assert!(default_sf.get_spin_count() > 0);
+        assert!(smooth_sf.get_spin_count() < default_sf.get_spin_count());
+    }
+
+    #[test]
+    fn test_id_at_percentile() {
+        let start = 1640995200000;
+        let end = start + 10_000;
+
+        assert_eq!(Snowflake::id_at_percentile(start, end, 0.0), min_id_for_timestamp(start));
+        assert_eq!(Snowflake::id_at_percentile(start, end, 1.0), min_id_for_timestamp(end));
+        assert_eq!(Snowflake::id_at_percentile(start, end, 0.5), min_id_for_timestamp(start + 5_000));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_id_at_percentile_rejects_out_of_range() {
+        Snowflake::id_at_percentile(0, 1000, 1.5);
+    }
+
+    #[test]
+    fn test_split_id_range_for_workers() {
+        let ms = EPOCH + 5_000;
+        let brackets = Snowflake::split_id_range_for_workers(ms, ms, 1, &[4, 2]).unwrap();
+        assert_eq!(brackets.len(), 2);
+        let (w_low, low_min, low_max) = brackets[0];
+        let (w_high, high_min, high_max) = brackets[1];
+        assert_eq!((w_low, w_high), (2, 4));
+        assert!(low_min <= low_max && low_max < high_min && high_min <= high_max);
+
+        let mut sf = Snowflake::with_initial_timestamp(4, 1, ms);
+        let id = sf.next_id().unwrap();
+        assert!((high_min..=high_max).contains(&id));
+
+        let wide = Snowflake::split_id_range_for_workers(ms, ms + 10, 1, &[2]).unwrap();
+        assert_eq!(extract_timestamp(wide[0].2), ms + 10);
+        assert!(Snowflake::split_id_range_for_workers(ms, ms, 1, &[MAX_WORKER_ID + 1]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snowflake_info_serde_roundtrip() {
+        let info = Snowflake::parse_id(596623079686410240);
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["id"], 596623079686410240u64);
+        assert_eq!(json["id_hex"], info.id_as_hex());
+        assert_eq!(json["timestamp_formatted"], info.timestamp_as_string());
+        assert_eq!(json["datacenter_id"], info.datacenter_id);
+
+        let decoded: SnowflakeInfo = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.id, info.id);
+        assert_eq!(decoded.timestamp, info.timestamp);
+        assert_eq!((decoded.datacenter_id, decoded.worker_id, decoded.sequence),
+            (info.datacenter_id, info.worker_id, info.sequence));
+    }
+
+    #[test]
+    fn test_id_instant_round_trip() {
+        let anchor_instant = Instant::now();
+        let anchor_time = SystemTime::now();
+
+        for offset in [Duration::ZERO, Duration::from_millis(1234), Duration::from_secs(3600)] {
+            let instant = anchor_instant + offset;
+            let bound = Snowflake::instant_to_id_bound(instant, anchor_instant, anchor_time);
+            let back = Snowflake::id_to_instant(bound, anchor_instant, anchor_time).unwrap();
+            let diff = if back > instant { back - instant } else { instant - back };
+            assert!(diff < Duration::from_millis(2), "offset {:?} drifted by {:?}", offset, diff);
+        }
+
+        let mut sf = Snowflake::new(1, 1);
+        let id = sf.next_id().unwrap();
+        let approx = Snowflake::id_to_instant(id, anchor_instant, anchor_time).unwrap();
+        assert!(approx.duration_since(anchor_instant) < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_time_range_contains_generated_ids() {
+        let start = SystemTime::now() - Duration::from_millis(10);
+        let mut sf = Snowflake::new(1, 1);
+        let ids = sf.next_ids(100).unwrap();
+        let end = SystemTime::now() + Duration::from_millis(10);
+
+        let range = SnowflakeTimeRange::new(start, end);
+        for id in &ids {
+            assert!(range.contains(id));
+            assert!(range.as_range().contains(id));
+        }
+
+        let earlier = SnowflakeTimeRange::new(start - Duration::from_secs(60), start - Duration::from_secs(30));
+        assert!(!earlier.contains(&ids[0]));
+    }
+
+    #[test]
+    fn test_next_id_timed_rollover() {
+        // 每10次读取前进1毫秒；等待下一毫秒时每次自旋休眠100µs
+        let nap = Duration::from_micros(100);
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(TickingClock::new(EPOCH + 1_000, 10)));
+        sf.set_spin_strategy(SpinStrategy::Sleep(nap));
+        let first = sf.next_id().unwrap();
+        let (id, _) = sf.next_id_timed().unwrap();
+        assert_eq!(extract_sequence(id), 1);
+        assert_eq!(sf.get_spin_count(), 0);
+
+        // 将序列号置满，迫使下一次调用等待下一毫秒
+        sf.sequence = SEQUENCE_MASK;
+        let (id, rollover) = sf.next_id_timed().unwrap();
+        assert_eq!(extract_sequence(id), 0);
+        assert_eq!(extract_timestamp(id), extract_timestamp(first) + 1);
+
+        // 第4次读取开始等待，此后每次休眠后重读，直到第11次读取进入下一毫秒
+        assert_eq!(sf.get_spin_count(), 7);
+        assert!(rollover >= nap * 7, "rollover {:?} should include every spin", rollover);
+    }
+
+    #[test]
+    fn test_sleep_spin_strategy_terminates() {
+        let mut sf = Snowflake::new(1, 1);
+        sf.set_spin_strategy(SpinStrategy::Sleep(Duration::from_micros(100)));
+
+        let target = sf.current_millis() + 3;
+        let (ts, spins) = sf.til_next_millis(target, None);
+        assert!(ts > target);
+        // 休眠期间不应像忙等那样产生海量自旋
+        assert!(spins < 1000, "too many spins under Sleep: {}", spins);
+
+        sf.set_spin_strategy(SpinStrategy::Hybrid);
+        let target = sf.current_millis() + 3;
+        let (ts, _) = sf.til_next_millis(target, None);
+        assert!(ts > target);
+    }
+
+    #[test]
+    fn test_clock_backwards_wait_times_out() {
+        let mut sf = Snowflake::new(1, 1);
+        sf.set_clock_policy(ClockBackwardsPolicy::WaitUpTo(Duration::from_millis(20)));
+        // 模拟时钟被回拨了一小时
+        sf.last_timestamp = sf.current_millis() + 3_600_000;
+
+        let start = Instant::now();
+        let result = sf.next_id();
+        let elapsed = start.elapsed();
+
+        assert!(matches!(result, Err(WorkerError::ClockBackwardsError(_))));
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_clock_backwards_wait_catches_up() {
+        let mut sf = Snowflake::new(1, 1);
+        sf.set_clock_policy(ClockBackwardsPolicy::WaitUpTo(DEFAULT_MAX_BACKWARDS_WAIT));
+        let ahead = sf.current_millis() + 5;
+        sf.last_timestamp = ahead;
+
+        let id = sf.next_id().unwrap();
+        assert!(extract_timestamp(id) >= ahead);
+    }
+
+    #[test]
+    fn test_clock_backwards_refuse_and_reuse_last() {
+        let start = EPOCH + 1_000;
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(SteppingTimeProvider::new(start, 1)));
+        // 时钟比上次时间戳落后10毫秒
+        sf.last_timestamp = start + 10;
+        sf.sequence = 0;
+        assert!(matches!(sf.next_id(), Err(WorkerError::ClockBackwardsError(_))));
+
+        sf.set_clock_policy(ClockBackwardsPolicy::RefuseAndReuseLast);
+        let ids = sf.next_ids(5).unwrap();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.iter().all(|&id| extract_timestamp(id) == start + 10));
+
+        // 沿用的毫秒序列号耗尽后等待时钟追上
+        sf.sequence = SEQUENCE_MASK;
+        let id = sf.next_id().unwrap();
+        assert!(id > ids[4]);
+        assert!(extract_timestamp(id) > start + 10);
+    }
+
+    #[test]
+    fn test_reused_timestamp_exhaustion_is_bounded() {
+        let clock = Arc::new(ManualClock::new(EPOCH + 3_600_000));
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::clone(&clock));
+        sf.set_clock_policy(ClockBackwardsPolicy::RefuseAndReuseLast);
+        sf.set_max_backwards_wait(Duration::from_millis(20));
+        let last = extract_timestamp(sf.next_id().unwrap());
+
+        // 时钟回拨一小时后沿用上次时间戳，把那一毫秒剩余的序列号全部用完
+        clock.set(EPOCH);
+        for _ in 0..SEQUENCE_MASK {
+            assert_eq!(extract_timestamp(sf.next_id().unwrap()), last);
+        }
+
+        let start = Instant::now();
+        let result = sf.next_id();
+        let elapsed = start.elapsed();
+        assert!(matches!(result, Err(WorkerError::ClockBackwardsError(_))), "{:?}", result);
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_secs(1), "waited {:?} for a clock an hour behind", elapsed);
+
+        // 时钟追上后恢复生成
+        clock.set(last + 1);
+        assert_eq!(extract_timestamp(sf.next_id().unwrap()), last + 1);
+    }
+
+    #[test]
+    fn test_id_as_binary_grouped() {
+        let id = build_snowflake_id(EPOCH + 1, 1, 1, 1);
+        let grouped = Snowflake::parse_id(id).id_as_binary_grouped();
+
+        let groups: Vec<&str> = grouped.split('|').collect();
+        assert_eq!(groups.len(), 5);
+        assert_eq!(groups[0], "0");
+        assert_eq!(groups[1].len(), 41);
+        assert_eq!(groups[1], format!("{:041b}", 1));
+        assert_eq!(groups[2], "00001");
+        assert_eq!(groups[3], "00001");
+        assert_eq!(groups[4], format!("{:012b}", 1));
+        assert_eq!(grouped.replace('|', ""), format!("{:064b}", id));
+    }
+
+    #[test]
+    fn test_exceeds_js_safe_integer() {
+        let mut sf = Snowflake::new(1, 1);
+        let id = sf.next_id().unwrap();
+        assert!(Snowflake::exceeds_js_safe_integer(id));
+
+        assert!(!Snowflake::exceeds_js_safe_integer(JS_MAX_SAFE_INTEGER));
+        assert!(Snowflake::exceeds_js_safe_integer(JS_MAX_SAFE_INTEGER + 1));
+    }
+
+    #[test]
+    fn test_assert_no_overlap_with() {
+        let mut sf = Snowflake::new(4, 1);
+        sf.next_id().unwrap();
+        let last = sf.get_last_timestamp();
+
+        // 相同 (worker ID, 数据中心ID)，对方时间窗口与我们重叠
+        assert!(matches!(sf.assert_no_overlap_with(last, 4, 1), Err(WorkerError::WorkerIdConflict(_))));
+        assert!(sf.assert_no_overlap_with(last + 100, 4, 1).is_err());
+        // 相同 (worker ID, 数据中心ID)，但对方的范围完全在我们之前
+        assert!(sf.assert_no_overlap_with(last - 1, 4, 1).is_ok());
+        // 不同 worker ID 不会冲突
+        assert!(sf.assert_no_overlap_with(last + 100, 5, 1).is_ok());
+        // worker ID 相同但数据中心不同，同样不会冲突
+        assert!(sf.assert_no_overlap_with(last + 100, 4, 2).is_ok());
+    }
+
+    #[test]
+    fn test_counter_mode_ignores_clock() {
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(FixedClock(EPOCH + 10_000)));
+        // 系统时钟远远落后于记录的时间戳，普通模式下会报时钟回拨
+        sf.last_timestamp = sf.current_millis() + 86_400_000;
+        assert!(sf.next_id().is_err());
+
+        sf.enable_counter_mode();
+        let ids = sf.next_ids(10_000).unwrap();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(extract_timestamp(ids[0]) > sf.current_millis() + 86_400_000);
+    }
+
+    #[test]
+    fn test_counter_mode_persists_counter() {
+        let test_file = "test_counter_mode.conf";
+        let _ = std::fs::remove_file(test_file);
+
+        let mut sf = Snowflake::new_with_config(test_file, 1).unwrap();
+        sf.enable_counter_mode();
+        sf.next_ids(5_000).unwrap();
+        let counter = sf.get_last_timestamp();
+
+        let content = std::fs::read_to_string(test_file).unwrap();
+        let info = WorkerInfo::from_file_content(&content).unwrap();
+        assert_eq!(info.last_timestamp, counter);
+
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_reserve_ids() {
+        let mut sf = Snowflake::new(2, 3);
+        let first = sf.reserve_ids(3000).unwrap();
+        let second = sf.reserve_ids(3000).unwrap();
+        // 按预留数量计入累计签发数；第二次预留跳到下一毫秒，跳过的序列号不计入
+        assert_eq!(sf.total_generated(), 6000);
+
+        assert_eq!(first.end() - first.start() + 1, 3000);
+        assert!(second.start() > first.end());
+        assert_eq!(Snowflake::parse_id(first.end()).worker_id, 2);
+        assert_eq!(Snowflake::parse_id(first.end()).datacenter_id, 3);
+
+        let next = sf.next_id().unwrap();
+        assert!(next > second.end());
+
+        assert!(matches!(sf.reserve_ids(0), Err(WorkerError::InvalidArgument(_))));
+        assert!(sf.reserve_ids(4097).is_err());
+    }
+
+    #[test]
+    fn test_compose_from_reservation() {
+        let mut sf = Snowflake::new(2, 3);
+        sf.next_ids(10).unwrap();
+        let reservation = sf.reserve_ids(100).unwrap();
+
+        let ids: Vec<u64> = [0, 1, 42, 99]
+            .iter()
+            .map(|&offset| Snowflake::compose_from_reservation(&reservation, offset).unwrap())
+            .collect();
+        assert_eq!(ids[0], reservation.start());
+        assert_eq!(ids[3], reservation.end());
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.iter().all(|&id| reservation.contains(id)));
+
+        assert!(matches!(
+            Snowflake::compose_from_reservation(&reservation, 100),
+            Err(WorkerError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_drain_remaining_sequence() {
+        let mut sf = Snowflake::new(1, 1);
+        assert!(sf.drain_remaining_sequence().unwrap().is_empty());
+
+        let first = Snowflake::parse_id(sf.next_id().unwrap());
+        let drained = sf.drain_remaining_sequence().unwrap();
+        assert_eq!(drained.len() as u64, SEQUENCE_MASK - first.sequence);
+        assert!(drained.iter().all(|&id| extract_timestamp(id) == first.timestamp));
+        assert_eq!(extract_sequence(*drained.last().unwrap()), SEQUENCE_MASK);
+
+        let next = Snowflake::parse_id(sf.next_id().unwrap());
+        assert!(next.timestamp > first.timestamp);
+        assert_eq!(next.sequence, 0);
+        assert!(next.id > *drained.last().unwrap());
+    }
+
+    #[test]
+    fn test_drain_counts_like_generation() {
+        let clock = Arc::new(ManualClock::new(EPOCH + 1_000));
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::clone(&clock));
+        sf.enable_audit_log(8);
+        sf.set_quota(Some(IdQuota::lifetime(5_000)));
+        sf.next_ids(10).unwrap();
+
+        let drained = sf.drain_remaining_sequence().unwrap();
+        assert_eq!(drained.len() as u64, SEQUENCE_MASK - 9);
+        assert_eq!(sf.total_generated(), SEQUENCE_MASK + 1);
+        assert_eq!(sf.remaining_quota(), Some(5_000 - (SEQUENCE_MASK + 1)));
+        assert_eq!(sf.recent_ids(), drained[drained.len() - 8..]);
+
+        // 剩余配额不足以用完下一毫秒时拒绝，不签发任何ID
+        clock.set(EPOCH + 1_001);
+        sf.next_id().unwrap();
+        assert!(matches!(sf.drain_remaining_sequence(), Err(WorkerError::QuotaExceeded(_))));
+        assert!(sf.health_report().last_error.unwrap().starts_with("Quota exceeded"));
+        assert_eq!(extract_sequence(sf.next_id().unwrap()), 1);
+    }
+
+    #[test]
+    fn test_drain_refuses_after_typed_id() {
+        let clock = Arc::new(ManualClock::new(EPOCH + 1_000));
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::clone(&clock));
+        let typed = sf.next_id_of_type(3).unwrap();
+        assert!(matches!(sf.drain_remaining_sequence(), Err(WorkerError::InvalidArgument(_))));
+
+        // 下一毫秒没有带类型ID，可以正常用完，且不会与之前的带类型ID重复
+        clock.set(EPOCH + 1_001);
+        sf.next_id().unwrap();
+        let drained = sf.drain_remaining_sequence().unwrap();
+        assert!(!drained.contains(&typed));
+        assert_eq!(extract_sequence(*drained.last().unwrap()), SEQUENCE_MASK);
+    }
+
+    #[test]
+    fn test_drain_persisted_before_restart() {
+        let test_file = "test_drain_persisted.conf";
+        let _ = std::fs::remove_file(test_file);
+        // 起始时刻略领先，保证不早于新建配置文件时写入的时间戳
+        let clock = Arc::new(ManualClock::new(system_time_millis(SystemTime::now()) + 1_000));
+        let mut sf = Snowflake::with_config_and_time_provider(test_file, 1, clock.clone()).unwrap();
+        sf.set_persist_interval_ms(0);
+        clock.set(sf.worker_manager.as_ref().unwrap().get_worker_info().last_timestamp);
+        sf.next_id().unwrap();
+        let drained = sf.drain_remaining_sequence().unwrap();
+        // 模拟进程崩溃：不经过 drop 时的补写
+        drop(sf.worker_manager.take());
+        std::mem::forget(sf);
+
+        // 检查点记录了用完的整个毫秒，重启后不会从中途接着签发
+        let last = Snowflake::parse_id(*drained.last().unwrap());
+        let content = std::fs::read_to_string(test_file).unwrap();
+        let info = WorkerInfo::from_file_content(&content).unwrap();
+        assert_eq!((info.last_timestamp, info.last_sequence), (last.timestamp, SEQUENCE_MASK));
+
+        clock.set(last.timestamp + 1);
+        let mut restarted = Snowflake::with_config_and_time_provider(test_file, 1, clock).unwrap();
+        assert!(restarted.next_id().unwrap() > last.id);
+
+        drop(restarted);
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_set_datacenter_id() {
+        let mut sf = Snowflake::new(1, 1);
+        let before = sf.next_ids(100).unwrap();
+
+        sf.set_datacenter_id(7).unwrap();
+        let after = sf.next_ids(100).unwrap();
+
+        let first_after = Snowflake::parse_id(after[0]);
+        assert_eq!(first_after.sequence, 0);
+        assert!(first_after.timestamp > extract_timestamp(*before.last().unwrap()));
+        assert!(before.iter().all(|&id| extract_datacenter_id(id) == 1));
+        assert!(after.iter().all(|&id| extract_datacenter_id(id) == 7));
+        assert!(after[0] > *before.last().unwrap());
+
+        assert!(matches!(sf.set_datacenter_id(32), Err(WorkerError::InvalidId(_))));
+        assert_eq!(sf.get_datacenter_id(), 7);
+    }
+
+    #[test]
+    fn test_next_id_of_type() {
+        let mut sf = Snowflake::new(1, 1);
+        let mut ids = Vec::new();
+        for i in 0..2000 {
+            let kind = if i % 2 == 0 { 2 } else { 5 };
+            let id = sf.next_id_of_type(kind).unwrap();
+            assert_eq!(Snowflake::parse_id(id).kind(), kind);
+            ids.push(id);
+        }
+
+        let mut unique = ids.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), ids.len());
+
+        assert!(matches!(sf.next_id_of_type(MAX_TYPE_TAG as u8 + 1), Err(WorkerError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_untyped_after_typed_moves_to_next_millisecond() {
+        let mut sf = Snowflake::with_time_provider(1, 1, Arc::new(TickingClock::new(EPOCH + 1_000, 10_000)));
+        let mut ids = Vec::new();
+        for i in 0..1_000 {
+            ids.push(if i % 3 == 0 { sf.next_id_of_type(1).unwrap() } else { sf.next_id().unwrap() });
+        }
+
+        let mut unique = ids.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), ids.len());
+        // 带类型ID之后的不带类型ID落在更晚的毫秒
+        assert!(extract_timestamp(ids[1]) > extract_timestamp(ids[0]));
+    }
+
+    #[test]
+    fn test_timestamp_as_string() {
+        let mut info = Snowflake::parse_id(build_snowflake_id(1640995200100, 1, 1, 0));
+        assert_eq!(info.timestamp_as_string(), "2022-01-01T00:00:00.100Z");
+
+        info.timestamp = 951_782_400_000 + 86_399_999; // 2000-02-29 闰日最后一毫秒
+        assert_eq!(info.timestamp_as_string(), "2000-02-29T23:59:59.999Z");
+
+        info.timestamp = 0;
+        assert_eq!(info.timestamp_as_string(), "1970-01-01T00:00:00.000Z");
+
+        // 41位时间戳能表示的最大时间
+        info.timestamp = extract_timestamp(u64::MAX >> 1);
+        assert_eq!(info.timestamp_as_string(), "2090-09-07T15:47:35.551Z");
+
+        info.timestamp = 253_402_300_800_000; // 10000-01-01
+        assert_eq!(info.timestamp_as_string(), "+10000-01-01T00:00:00.000Z");
+
+        info.timestamp = u64::MAX;
+        assert!(info.timestamp_as_string().starts_with("+584556019-"));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_datetime_utc() {
+        use chrono::SecondsFormat;
+
+        let mut info = Snowflake::parse_id(build_snowflake_id(1640995200100, 1, 1, 0));
+        let datetime = info.datetime_utc().unwrap();
+        assert_eq!(datetime.timestamp_millis(), 1640995200100);
+        assert_eq!(datetime.to_rfc3339_opts(SecondsFormat::Millis, true), info.timestamp_as_string());
+
+        info.timestamp = u64::MAX;
+        assert_eq!(info.datetime_utc(), None);
+    }
+
+    #[test]
+    fn test_persist_interval() {
+        let test_file = "test_persist_interval.conf";
+        let _ = std::fs::remove_file(test_file);
+
+        let mut sf = Snowflake::new_with_config(test_file, 1).unwrap();
+        assert_eq!(sf.get_persist_interval_ms(), DEFAULT_PERSIST_INTERVAL_MS);
+
+        sf.set_persist_interval_ms(0);
+        assert_eq!(sf.get_persist_interval_ms(), 0);
+        assert!(sf.next_id().is_ok());
+
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_restart_mid_millisecond_resumes_sequence() {
+        let test_file = "test_restart_mid_ms.conf";
+        let _ = std::fs::remove_file(test_file);
+        // 起始时刻略领先，保证不早于新建配置文件时写入的时间戳
+        let clock = Arc::new(ManualClock::new(system_time_millis(SystemTime::now()) + 1_000));
+        let mut sf = Snowflake::with_config_and_time_provider(test_file, 1, clock.clone()).unwrap();
+        sf.set_persist_interval_ms(0);
+        // 冻结在启动时持久化的时间戳上，之后的检查点都落在同一毫秒
+        let frozen = sf.worker_manager.as_ref().unwrap().get_worker_info().last_timestamp;
+        clock.set(frozen);
+        let mut ids = sf.next_ids(100).unwrap();
+        // 模拟崩溃：不经过 drop 时的补写，但与进程退出一样释放配置文件锁
+        drop(sf.worker_manager.take());
+        std::mem::forget(sf);
+
+        let mut restarted = Snowflake::with_config_and_time_provider(test_file, 1, clock).unwrap();
+        let resumed = restarted.next_ids(100).unwrap();
+        assert!(resumed.iter().all(|&id| extract_timestamp(id) == frozen));
+        assert_eq!(extract_sequence(resumed[0]), extract_sequence(ids[99]) + 1);
+
+        ids.extend(resumed);
+        let unique: std::collections::HashSet<u64> = ids.iter().copied().collect();
+        assert_eq!(unique.len(), ids.len());
+
+        drop(restarted);
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_reservation_persisted_before_return() {
+        let test_file = "test_reservation_persisted.conf";
+        let _ = std::fs::remove_file(test_file);
+        // 起始时刻略领先，保证不早于新建配置文件时写入的时间戳
+        let clock = Arc::new(ManualClock::new(system_time_millis(SystemTime::now()) + 1_000));
+        let mut sf = Snowflake::with_config_and_time_provider(test_file, 1, clock.clone()).unwrap();
+        // 持久化间隔足够长，只有预留本身会写入检查点
+        sf.set_persist_interval_ms(60_000);
+        clock.set(sf.worker_manager.as_ref().unwrap().get_worker_info().last_timestamp);
+        let reservation = sf.reserve_ids(50).unwrap();
+        drop(sf.worker_manager.take());
+        std::mem::forget(sf);
+
+        let mut restarted = Snowflake::with_config_and_time_provider(test_file, 1, clock).unwrap();
+        assert!(restarted.next_id().unwrap() > reservation.end());
+
+        drop(restarted);
+        let _ = std::fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_persist_debounced_under_burst() {
+        let test_file = "test_persist_debounce.conf";
+        let _ = std::fs::remove_file(test_file);
+
+        let mut sf = Snowflake::new_with_config(test_file, 1).unwrap();
+        sf.set_persist_interval_ms(20);
+        let start = Instant::now();
+        let mut last_id = 0;
+        while start.elapsed() < Duration::from_millis(200) {
+            last_id = sf.next_id().unwrap();
+        }
+
+        // 每个20ms窗口最多写一次
+        let budget = start.elapsed().as_millis() as u64 / 20 + 1;
+        assert!(sf.get_save_count() >= 1);
+        assert!(sf.get_save_count() <= budget, "{} saves exceed budget {}", sf.get_save_count(), budget);
+
+        // drop 时补写最后一个窗口内的进度
+        drop(sf);
+        let content = std::fs::read_to_string(test_file).unwrap();
+        let info = WorkerInfo::from_file_content(&content).unwrap();
+        assert!(info.last_timestamp >= extract_timestamp(last_id));
+
+        let _ = std::fs::remove_file(test_file);
     }
 }
\ No newline at end of file
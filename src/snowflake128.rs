@@ -0,0 +1,442 @@
+//! 128位雪花ID
+//!
+//! 与64位的 `Snowflake` 并行的实现：时间戳以微秒计，字段更宽，
+//! 适合需要远超41位毫秒时间戳（约69年）寿命或更高时间精度的系统。
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::snowflake::{resolve_clock_backwards, wait_past, ClockBackwardsPolicy, SpinStrategy, DEFAULT_MAX_BACKWARDS_WAIT};
+use crate::snowflake_core::EPOCH;
+use crate::worker_manager::WorkerError;
+
+/// 128位ID默认使用的纪元（微秒），与64位ID的 `EPOCH` 为同一时刻
+pub const EPOCH_MICROS: u64 = EPOCH * 1000;
+
+/// 128位ID各字段的位数划分，从高到低依次为：时间戳 | 数据中心ID | 工作ID | 序列号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout128 {
+    pub timestamp_bits: u32,
+    pub datacenter_bits: u32,
+    pub worker_bits: u32,
+    pub sequence_bits: u32,
+}
+
+impl Layout128 {
+    /// 默认的 64/16/16/32 划分：微秒时间戳可用约58万年
+    pub const DEFAULT: Layout128 = Layout128::new(64, 16, 16, 32);
+
+    pub const fn new(timestamp_bits: u32, datacenter_bits: u32, worker_bits: u32, sequence_bits: u32) -> Self {
+        Layout128 { timestamp_bits, datacenter_bits, worker_bits, sequence_bits }
+    }
+
+    /// 各字段合计正好128位，单个字段不超过64位且时间戳至少占1位
+    pub fn is_valid(&self) -> bool {
+        let fields = [self.timestamp_bits, self.datacenter_bits, self.worker_bits, self.sequence_bits];
+        self.timestamp_bits > 0 && fields.iter().all(|&bits| bits <= 64) && fields.iter().sum::<u32>() == 128
+    }
+
+    pub fn worker_shift(&self) -> u32 {
+        self.sequence_bits
+    }
+
+    pub fn datacenter_shift(&self) -> u32 {
+        self.worker_shift() + self.worker_bits
+    }
+
+    pub fn timestamp_shift(&self) -> u32 {
+        self.datacenter_shift() + self.datacenter_bits
+    }
+
+    pub fn max_timestamp(&self) -> u64 {
+        low_bits(self.timestamp_bits)
+    }
+
+    pub fn max_datacenter_id(&self) -> u64 {
+        low_bits(self.datacenter_bits)
+    }
+
+    pub fn max_worker_id(&self) -> u64 {
+        low_bits(self.worker_bits)
+    }
+
+    pub fn max_sequence(&self) -> u64 {
+        low_bits(self.sequence_bits)
+    }
+
+    /// 按本布局拼装ID，`timestamp` 为相对纪元的偏移
+    pub fn build_id(&self, timestamp: u64, datacenter_id: u64, worker_id: u64, sequence: u64) -> u128 {
+        ((timestamp as u128) << self.timestamp_shift())
+            | ((datacenter_id as u128) << self.datacenter_shift())
+            | ((worker_id as u128) << self.worker_shift())
+            | sequence as u128
+    }
+}
+
+impl Default for Layout128 {
+    fn default() -> Self {
+        Layout128::DEFAULT
+    }
+}
+
+/// 低 `bits` 位全为1的掩码（`bits` 不超过64）
+fn low_bits(bits: u32) -> u64 {
+    if bits >= 64 { u64::MAX } else { (1 << bits) - 1 }
+}
+
+/// 128位雪花ID解析信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnowflakeInfo128 {
+    pub id: u128,
+    /// Unix 微秒时间戳
+    pub timestamp_micros: u64,
+    pub datacenter_id: u64,
+    pub worker_id: u64,
+    pub sequence: u64,
+}
+
+impl SnowflakeInfo128 {
+    /// 毫秒时间戳，便于与64位ID比较
+    pub fn timestamp_millis(&self) -> u64 {
+        self.timestamp_micros / 1000
+    }
+}
+
+/// 生成128位ID的时钟（返回Unix微秒时间戳）
+type MicrosClock = Box<dyn Fn() -> u64 + Send + Sync>;
+
+fn system_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+/// 128位雪花ID生成器
+///
+/// 单调性与时钟回拨处理与 `Snowflake` 相同：同一微秒内递增序列号，
+/// 序列号耗尽时按 `SpinStrategy` 等待下一微秒，回拨按 `ClockBackwardsPolicy` 处理。
+/// 等待下一微秒最长 `max_backwards_wait`（设置了停滞兜底时为兜底时长）。
+/// 不支持配置文件持久化。
+pub struct Snowflake128 {
+    worker_id: u64,
+    datacenter_id: u64,
+    layout: Layout128,
+    epoch_micros: u64,
+    last_timestamp: u64,
+    sequence: u64,
+    clock_policy: ClockBackwardsPolicy,
+    max_backwards_wait: Duration,
+    spin_strategy: SpinStrategy,
+    stall_fallback: Option<Duration>,
+    /// 当前时间戳是否为停滞兜底借用的逻辑微秒（领先于时钟）
+    stall_borrowed: bool,
+    clock: MicrosClock,
+}
+
+impl Snowflake128 {
+    /// 使用默认布局和纪元创建生成器
+    ///
+    /// # Panics
+    /// `worker_id` 或 `datacenter_id` 超出默认布局的范围时 panic
+    pub fn new(worker_id: u64, datacenter_id: u64) -> Self {
+        Self::with_layout(worker_id, datacenter_id, Layout128::DEFAULT).expect("Invalid worker_id or datacenter_id")
+    }
+
+    /// 使用自定义位布局创建生成器
+    ///
+    /// 布局不合法时返回 `InvalidArgument`，ID超出布局范围时返回 `InvalidId`。
+    pub fn with_layout(worker_id: u64, datacenter_id: u64, layout: Layout128) -> Result<Self, WorkerError> {
+        if !layout.is_valid() {
+            return Err(WorkerError::InvalidArgument(format!(
+                "layout {:?} must add up to 128 bits with a non-empty timestamp and no field wider than 64", layout
+            )));
+        }
+        if worker_id > layout.max_worker_id() {
+            return Err(WorkerError::InvalidId(format!(
+                "worker_id {} exceeds maximum {}", worker_id, layout.max_worker_id()
+            )));
+        }
+        if datacenter_id > layout.max_datacenter_id() {
+            return Err(WorkerError::InvalidId(format!(
+                "datacenter_id {} exceeds maximum {}", datacenter_id, layout.max_datacenter_id()
+            )));
+        }
+
+        Ok(Snowflake128 {
+            worker_id,
+            datacenter_id,
+            layout,
+            epoch_micros: EPOCH_MICROS,
+            last_timestamp: 0,
+            sequence: 0,
+            clock_policy: ClockBackwardsPolicy::default(),
+            max_backwards_wait: DEFAULT_MAX_BACKWARDS_WAIT,
+            spin_strategy: SpinStrategy::default(),
+            stall_fallback: None,
+            stall_borrowed: false,
+            clock: Box::new(system_micros),
+        })
+    }
+
+    /// 替换时钟（返回Unix微秒时间戳），用于测试或接入外部时间源
+    pub fn with_clock(mut self, clock: impl Fn() -> u64 + Send + Sync + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// 使用自定义纪元（Unix微秒时间戳）
+    pub fn with_epoch_micros(mut self, epoch_micros: u64) -> Self {
+        self.epoch_micros = epoch_micros;
+        self
+    }
+
+    /// 设置时钟回拨处理策略（默认 `ClockBackwardsPolicy::Error`）
+    pub fn set_clock_policy(&mut self, policy: ClockBackwardsPolicy) {
+        self.clock_policy = policy;
+    }
+
+    /// 设置序列号耗尽后等待下一微秒的上限（未设置停滞兜底时）
+    pub fn set_max_backwards_wait(&mut self, max_wait: Duration) {
+        self.max_backwards_wait = max_wait;
+    }
+
+    /// 设置序列号耗尽后等待下一微秒的方式（默认 `SpinStrategy::Busy`）
+    pub fn set_spin_strategy(&mut self, strategy: SpinStrategy) {
+        self.spin_strategy = strategy;
+    }
+
+    /// 设置时钟停滞兜底：等待下一微秒超过 `fallback` 时借用下一个逻辑微秒，`None` 表示关闭
+    /// 
+    /// 关闭时等待超过 `max_backwards_wait` 返回 `ClockBackwardsError`。
+    pub fn set_stall_fallback(&mut self, fallback: Option<Duration>) {
+        self.stall_fallback = fallback;
+    }
+
+    pub fn layout(&self) -> Layout128 {
+        self.layout
+    }
+
+    pub fn get_worker_id(&self) -> u64 {
+        self.worker_id
+    }
+
+    pub fn get_datacenter_id(&self) -> u64 {
+        self.datacenter_id
+    }
+
+    /// 生成下一个128位ID
+    ///
+    /// 时间戳早于纪元或超出时间戳位宽时返回 `InvalidArgument`。
+    pub fn next_id(&mut self) -> Result<u128, WorkerError> {
+        let mut timestamp = self.current_micros();
+
+        // 借用过的时间戳领先于时钟属于预期，沿用上次时间戳
+        if timestamp < self.last_timestamp {
+            timestamp = if self.stall_borrowed {
+                self.last_timestamp
+            } else {
+                self.handle_clock_backwards(timestamp)?
+            };
+        }
+
+        let sequence = if timestamp != self.last_timestamp {
+            self.stall_borrowed = false;
+            0
+        } else if self.sequence < self.layout.max_sequence() {
+            self.sequence + 1
+        } else {
+            timestamp = self.til_next_micros(self.last_timestamp)?;
+            0
+        };
+
+        let offset = timestamp.checked_sub(self.epoch_micros).ok_or_else(|| WorkerError::InvalidArgument(format!(
+            "timestamp {} us is before epoch {} us", timestamp, self.epoch_micros
+        )))?;
+        if offset > self.layout.max_timestamp() {
+            return Err(WorkerError::InvalidArgument(format!(
+                "timestamp {} us exceeds the {}-bit range after epoch {} us",
+                timestamp, self.layout.timestamp_bits, self.epoch_micros
+            )));
+        }
+        self.last_timestamp = timestamp;
+        self.sequence = sequence;
+        Ok(self.layout.build_id(offset, self.datacenter_id, self.worker_id, sequence))
+    }
+
+    /// 按默认布局和纪元解析128位ID
+    pub fn parse_id_128(id: u128) -> SnowflakeInfo128 {
+        Self::parse_id_128_with_layout(id, Layout128::DEFAULT, EPOCH_MICROS)
+    }
+
+    /// 按指定布局和纪元（微秒）解析128位ID
+    pub fn parse_id_128_with_layout(id: u128, layout: Layout128, epoch_micros: u64) -> SnowflakeInfo128 {
+        let field = |shift: u32, bits: u32| (id.checked_shr(shift).unwrap_or(0) as u64) & low_bits(bits);
+        SnowflakeInfo128 {
+            id,
+            timestamp_micros: field(layout.timestamp_shift(), layout.timestamp_bits).saturating_add(epoch_micros),
+            datacenter_id: field(layout.datacenter_shift(), layout.datacenter_bits),
+            worker_id: field(layout.worker_shift(), layout.worker_bits),
+            sequence: field(0, layout.sequence_bits),
+        }
+    }
+
+    /// 按本生成器的布局和纪元解析ID
+    pub fn parse(&self, id: u128) -> SnowflakeInfo128 {
+        Self::parse_id_128_with_layout(id, self.layout, self.epoch_micros)
+    }
+
+    fn current_micros(&self) -> u64 {
+        (self.clock)()
+    }
+
+    /// 等待时钟越过 `last_timestamp`；超时后按停滞兜底借用下一个逻辑微秒，未设置兜底时报错
+    /// 
+    /// `RefuseAndReuseLast` 沿用的时间戳可能远远领先于时钟，因此等待必须有上限。
+    fn til_next_micros(&mut self, last_timestamp: u64) -> Result<u64, WorkerError> {
+        let max_wait = self.stall_fallback.unwrap_or(self.max_backwards_wait);
+        let (timestamp, _) = wait_past(last_timestamp, Some(max_wait), self.spin_strategy, || self.current_micros());
+        if timestamp > last_timestamp {
+            return Ok(timestamp);
+        }
+        if self.stall_fallback.is_some() {
+            self.stall_borrowed = true;
+            return Ok(last_timestamp + 1);
+        }
+        Err(WorkerError::ClockBackwardsError(format!(
+            "Clock did not pass {} us within {:?} after the sequence ran out. Current: {} us",
+            last_timestamp, max_wait, timestamp
+        )))
+    }
+
+    /// 按回拨策略处理时钟回拨，返回可用的时间戳
+    fn handle_clock_backwards(&self, timestamp: u64) -> Result<u64, WorkerError> {
+        resolve_clock_backwards(
+            self.clock_policy,
+            self.last_timestamp,
+            timestamp,
+            Duration::from_micros(100),
+            " us",
+            || self.current_micros(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    /// 可由测试手动拨动的微秒时钟
+    fn manual_clock(start: u64) -> (Arc<AtomicU64>, impl Fn() -> u64 + Send + Sync + 'static) {
+        let now = Arc::new(AtomicU64::new(start));
+        let handle = now.clone();
+        (now, move || handle.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn test_generate_and_parse_128() {
+        let start = EPOCH_MICROS + 5_000_000_123;
+        let (now, clock) = manual_clock(start);
+        let mut snowflake = Snowflake128::new(40_000, 1_000).with_clock(clock);
+
+        let first = snowflake.next_id().unwrap();
+        let second = snowflake.next_id().unwrap();
+        now.store(start + 1, Ordering::SeqCst);
+        let third = snowflake.next_id().unwrap();
+        assert!(first < second && second < third);
+
+        let info = Snowflake128::parse_id_128(second);
+        assert_eq!(info.timestamp_micros, start);
+        assert_eq!(info.timestamp_millis(), start / 1000);
+        assert_eq!((info.datacenter_id, info.worker_id, info.sequence), (1_000, 40_000, 1));
+        assert_eq!(Snowflake128::parse_id_128(third).sequence, 0);
+        assert_eq!(snowflake.parse(third), Snowflake128::parse_id_128(third));
+    }
+
+    #[test]
+    fn test_sequence_rollover_waits_for_next_micro() {
+        // 4位序列号：每微秒16个ID
+        let layout = Layout128::new(64, 30, 30, 4);
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = calls.clone();
+        let start = EPOCH_MICROS + 1_000;
+        let mut snowflake = Snowflake128::with_layout(1, 1, layout)
+            .unwrap()
+            .with_clock(move || start + counter.fetch_add(1, Ordering::SeqCst) / 100);
+
+        let ids: Vec<u128> = (0..40).map(|_| snowflake.next_id().unwrap()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        let infos: Vec<_> = ids.iter().map(|&id| Snowflake128::parse_id_128_with_layout(id, layout, EPOCH_MICROS)).collect();
+        assert!(infos.iter().all(|info| info.sequence <= 15));
+        assert!(infos.last().unwrap().timestamp_micros > start);
+    }
+
+    #[test]
+    fn test_clock_backwards_128() {
+        let (now, clock) = manual_clock(EPOCH_MICROS + 10_000);
+        let mut snowflake = Snowflake128::new(1, 1).with_clock(clock);
+        let before = snowflake.next_id().unwrap();
+
+        now.store(EPOCH_MICROS + 9_000, Ordering::SeqCst);
+        assert!(matches!(snowflake.next_id(), Err(WorkerError::ClockBackwardsError(_))));
+
+        snowflake.set_clock_policy(ClockBackwardsPolicy::RefuseAndReuseLast);
+        let after = snowflake.next_id().unwrap();
+        assert!(after > before);
+        assert_eq!(Snowflake128::parse_id_128(after).timestamp_micros, EPOCH_MICROS + 10_000);
+    }
+
+    #[test]
+    fn test_reuse_last_wait_is_bounded() {
+        // 4位序列号：每微秒16个ID
+        let layout = Layout128::new(64, 30, 30, 4);
+        let (now, clock) = manual_clock(EPOCH_MICROS + 10_000);
+        let mut snowflake = Snowflake128::with_layout(1, 1, layout).unwrap().with_clock(clock);
+        snowflake.next_id().unwrap();
+
+        // 时钟回拨后沿用上次时间戳，序列号用完也不会无限忙等
+        now.store(EPOCH_MICROS + 9_000, Ordering::SeqCst);
+        snowflake.set_clock_policy(ClockBackwardsPolicy::RefuseAndReuseLast);
+        snowflake.set_max_backwards_wait(Duration::from_millis(5));
+        snowflake.set_spin_strategy(SpinStrategy::Sleep(Duration::from_micros(200)));
+        for _ in 0..15 {
+            snowflake.next_id().unwrap();
+        }
+        assert!(matches!(snowflake.next_id(), Err(WorkerError::ClockBackwardsError(_))));
+
+        // 开启停滞兜底后借用下一个逻辑微秒，时钟仍落后时继续沿用借用的时间戳
+        snowflake.set_stall_fallback(Some(Duration::from_millis(1)));
+        let borrowed = snowflake.next_id().unwrap();
+        let info = snowflake.parse(borrowed);
+        assert_eq!((info.timestamp_micros, info.sequence), (EPOCH_MICROS + 10_001, 0));
+        let next = snowflake.next_id().unwrap();
+        assert_eq!(snowflake.parse(next).timestamp_micros, EPOCH_MICROS + 10_001);
+    }
+
+    #[test]
+    fn test_invalid_layouts_and_ids() {
+        assert!(matches!(
+            Snowflake128::with_layout(1, 1, Layout128::new(64, 16, 16, 16)),
+            Err(WorkerError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            Snowflake128::with_layout(1, 1, Layout128::new(96, 8, 8, 16)),
+            Err(WorkerError::InvalidArgument(_))
+        ));
+        assert!(matches!(Snowflake128::with_layout(1 << 16, 1, Layout128::DEFAULT), Err(WorkerError::InvalidId(_))));
+
+        let (_, clock) = manual_clock(EPOCH_MICROS - 1);
+        let mut before_epoch = Snowflake128::new(1, 1).with_clock(clock);
+        assert!(matches!(before_epoch.next_id(), Err(WorkerError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_system_clock_ids_are_unique() {
+        let mut snowflake = Snowflake128::new(7, 3);
+        let mut ids: Vec<u128> = (0..10_000).map(|_| snowflake.next_id().unwrap()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        ids.dedup();
+        assert_eq!(ids.len(), 10_000);
+    }
+}
@@ -1,8 +1,18 @@
-/// 雪花算法核心常量和共享逻辑
-/// 
-/// 这个模块包含了雪花算法的所有常量定义和一些共享的辅助函数。
+//! 雪花算法核心常量和共享逻辑
+//! 
+//! 这个模块包含了雪花算法的所有常量定义和一些共享的辅助函数。
+//! 
+//! 位运算相关的函数与 `SnowflakeParts` 只用整数运算，关闭 `std` feature 后
+//! 可在 `no_std` 且无 `alloc` 的环境中使用，时钟由调用方自行提供。
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const EPOCH: u64 = 1609459200000; // 2021-01-01 00:00:00 UTC
+/// Discord 雪花ID使用的纪元（2015-01-01 00:00:00 UTC），位布局与本库相同
+pub const DISCORD_EPOCH: u64 = 1420070400000;
 pub const WORKER_ID_BITS: u64 = 5;
 pub const DATACENTER_ID_BITS: u64 = 5;
 pub const SEQUENCE_BITS: u64 = 12;
@@ -14,10 +24,27 @@ pub const WORKER_ID_SHIFT: u64 = SEQUENCE_BITS;
 pub const DATACENTER_ID_SHIFT: u64 = SEQUENCE_BITS + WORKER_ID_BITS;
 pub const TIMESTAMP_SHIFT: u64 = SEQUENCE_BITS + WORKER_ID_BITS + DATACENTER_ID_BITS;
 pub const SEQUENCE_MASK: u64 = (1 << SEQUENCE_BITS) - 1;
+/// 时间戳字段（相对纪元的毫秒偏移）可表示的最大值，符号位保持为0
+pub const MAX_TIMESTAMP_OFFSET: u64 = (1 << (63 - TIMESTAMP_SHIFT)) - 1;
+
+/// `validate_snowflake_id` 允许ID时间戳领先当前时间的最大毫秒数
+pub const MAX_ID_FUTURE_MS: u64 = 24 * 3600 * 1000;
+
+/// 类型标签占用序列号的高位
+pub const TYPE_TAG_BITS: u64 = 3;
+pub const MAX_TYPE_TAG: u64 = (1 << TYPE_TAG_BITS) - 1;
+pub const TYPE_TAG_SHIFT: u64 = SEQUENCE_BITS - TYPE_TAG_BITS;
+/// 带类型标签时可用的序列号掩码
+pub const TYPED_SEQUENCE_MASK: u64 = (1 << TYPE_TAG_SHIFT) - 1;
 
 /// 从雪花ID中提取时间戳
 pub fn extract_timestamp(id: u64) -> u64 {
-    (id >> TIMESTAMP_SHIFT) + EPOCH
+    extract_timestamp_with_epoch(id, EPOCH)
+}
+
+/// 按指定纪元从雪花ID中提取时间戳
+pub fn extract_timestamp_with_epoch(id: u64, epoch: u64) -> u64 {
+    (id >> TIMESTAMP_SHIFT) + epoch
 }
 
 /// 从雪花ID中提取worker_id
@@ -35,25 +62,299 @@ pub fn extract_sequence(id: u64) -> u64 {
     id & SEQUENCE_MASK
 }
 
+/// 从雪花ID中提取类型标签（序列号高位）
+pub fn extract_type_tag(id: u64) -> u64 {
+    (id >> TYPE_TAG_SHIFT) & MAX_TYPE_TAG
+}
+
 /// 构建雪花ID
 pub fn build_snowflake_id(timestamp: u64, datacenter_id: u64, worker_id: u64, sequence: u64) -> u64 {
-    ((timestamp - EPOCH) << TIMESTAMP_SHIFT)
+    build_snowflake_id_with_epoch(timestamp, EPOCH, datacenter_id, worker_id, sequence)
+}
+
+/// 按指定纪元构建雪花ID
+pub fn build_snowflake_id_with_epoch(timestamp: u64, epoch: u64, datacenter_id: u64, worker_id: u64, sequence: u64) -> u64 {
+    ((timestamp - epoch) << TIMESTAMP_SHIFT)
         | (datacenter_id << DATACENTER_ID_SHIFT)
         | (worker_id << WORKER_ID_SHIFT)
         | sequence
 }
 
-/// 验证worker_id和datacenter_id的有效性
-pub fn validate_ids(worker_id: u64, datacenter_id: u64) -> Result<(), String> {
+/// 给定毫秒时间戳可能出现的最小雪花ID（早于EPOCH的时间按EPOCH计算）
+pub fn min_id_for_timestamp(timestamp: u64) -> u64 {
+    build_snowflake_id(timestamp.max(EPOCH), 0, 0, 0)
+}
+
+/// 给定毫秒时间戳可能出现的最大雪花ID（早于EPOCH的时间按EPOCH计算）
+pub fn max_id_for_timestamp(timestamp: u64) -> u64 {
+    build_snowflake_id(timestamp.max(EPOCH), MAX_DATACENTER_ID, MAX_WORKER_ID, SEQUENCE_MASK)
+}
+
+/// 雪花ID各字段的位数划分（符号位固定为0，其余63位由各字段分配）
+/// 
+/// 从高到低依次为：时间戳 | 保留位 | 数据中心ID | 工作ID | 序列号。
+/// 保留位始终为0，供下游日后挪作他用而无需重新划分布局。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnowflakeLayout {
+    pub timestamp_bits: u64,
+    pub datacenter_bits: u64,
+    pub worker_bits: u64,
+    pub sequence_bits: u64,
+    pub reserved_bits: u64,
+}
+
+impl SnowflakeLayout {
+    /// 本库默认使用的 41/5/5/12 划分
+    pub const DEFAULT: SnowflakeLayout = SnowflakeLayout {
+        timestamp_bits: 41,
+        datacenter_bits: DATACENTER_ID_BITS,
+        worker_bits: WORKER_ID_BITS,
+        sequence_bits: SEQUENCE_BITS,
+        reserved_bits: 0,
+    };
+
+    pub const fn new(timestamp_bits: u64, datacenter_bits: u64, worker_bits: u64, sequence_bits: u64) -> Self {
+        SnowflakeLayout { timestamp_bits, datacenter_bits, worker_bits, sequence_bits, reserved_bits: 0 }
+    }
+
+    /// 在时间戳与数据中心ID之间保留 `reserved_bits` 位（调用方需相应减少其他字段的位数）
+    pub const fn with_reserved_bits(mut self, reserved_bits: u64) -> Self {
+        self.reserved_bits = reserved_bits;
+        self
+    }
+
+    /// 各字段（含保留位）位数之和是否正好为63位
+    /// 
+    /// 求和溢出（如从外部配置读到的超大位数）时同样视为无效。
+    pub fn is_valid(&self) -> bool {
+        [self.reserved_bits, self.datacenter_bits, self.worker_bits, self.sequence_bits]
+            .iter()
+            .try_fold(self.timestamp_bits, |sum, &bits| sum.checked_add(bits))
+            == Some(63)
+    }
+
+    pub fn worker_shift(&self) -> u64 {
+        self.sequence_bits
+    }
+
+    pub fn datacenter_shift(&self) -> u64 {
+        self.worker_shift() + self.worker_bits
+    }
+
+    /// 保留位区域最低位的位置
+    pub fn reserved_shift(&self) -> u64 {
+        self.datacenter_shift() + self.datacenter_bits
+    }
+
+    pub fn timestamp_shift(&self) -> u64 {
+        self.reserved_shift() + self.reserved_bits
+    }
+
+    /// 保留位区域的掩码
+    pub fn reserved_mask(&self) -> u64 {
+        low_bits(self.reserved_bits) << self.reserved_shift()
+    }
+
+    /// 按本布局构建ID，保留位为0；各字段（含相对纪元的时间戳偏移）超出位宽的高位被截掉
+    /// 
+    /// `timestamp` 早于 `epoch` 时返回 `None`。
+    pub fn build_id(&self, timestamp: u64, epoch: u64, datacenter_id: u64, worker_id: u64, sequence: u64) -> Option<u64> {
+        let offset = timestamp.checked_sub(epoch)?;
+        Some(((offset & low_bits(self.timestamp_bits)) << self.timestamp_shift())
+            | ((datacenter_id & low_bits(self.datacenter_bits)) << self.datacenter_shift())
+            | ((worker_id & low_bits(self.worker_bits)) << self.worker_shift())
+            | (sequence & low_bits(self.sequence_bits)))
+    }
+
+    /// 按本布局拆出 `(时间戳, 数据中心ID, 工作ID, 序列号)`，跳过保留位
+    /// 
+    /// 时间戳只取 `timestamp_bits` 位（忽略符号位），与纪元相加溢出时饱和为 `u64::MAX`。
+    pub fn decode(&self, id: u64, epoch: u64) -> (u64, u64, u64, u64) {
+        (
+            ((id >> self.timestamp_shift()) & low_bits(self.timestamp_bits)).saturating_add(epoch),
+            (id >> self.datacenter_shift()) & low_bits(self.datacenter_bits),
+            (id >> self.worker_shift()) & low_bits(self.worker_bits),
+            id & low_bits(self.sequence_bits),
+        )
+    }
+
+    /// 单个节点每毫秒可签发的ID数
+    /// 
+    /// 本方法与 `max_nodes`、`lifetime_millis` 在未通过 `is_valid` 的布局上也不会 panic：
+    /// 位数达到64时结果饱和为 `u64::MAX`。
+    pub fn sequence_capacity(&self) -> u64 {
+        pow2_saturating(self.sequence_bits)
+    }
+
+    /// 单个节点每秒可签发的ID数
+    pub fn max_ids_per_second(&self) -> u64 {
+        self.sequence_capacity().saturating_mul(1000)
+    }
+
+    /// 本布局下最大的 worker ID
+    pub fn max_worker_id(&self) -> u64 {
+        low_bits(self.worker_bits)
+    }
+
+    /// 本布局下最大的数据中心ID
+    pub fn max_datacenter_id(&self) -> u64 {
+        low_bits(self.datacenter_bits)
+    }
+
+    /// 可区分的节点（datacenter × worker）总数
+    pub fn max_nodes(&self) -> u64 {
+        pow2_saturating(self.datacenter_bits.saturating_add(self.worker_bits))
+    }
+
+    /// 时间戳字段可表示的总时长（毫秒）
+    pub fn lifetime_millis(&self) -> u64 {
+        pow2_saturating(self.timestamp_bits)
+    }
+}
+
+/// `2^bits`，超出 u64 范围时为 `u64::MAX`
+fn pow2_saturating(bits: u64) -> u64 {
+    u32::try_from(bits)
+        .ok()
+        .and_then(|bits| 1u64.checked_shl(bits))
+        .unwrap_or(u64::MAX)
+}
+
+/// 低 `bits` 位全为1的掩码
+const fn low_bits(bits: u64) -> u64 {
+    if bits >= 64 { u64::MAX } else { (1 << bits) - 1 }
+}
+
+impl Default for SnowflakeLayout {
+    fn default() -> Self {
+        SnowflakeLayout::DEFAULT
+    }
+}
+
+/// 雪花ID拆分出的各字段，不含任何字符串格式化，可在 `no_std` 下使用
+/// 
+/// 启用 `std` 时可通过 `SnowflakeInfo::from` 转换为带格式化方法的完整解析结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SnowflakeParts {
+    pub id: u64,
+    pub timestamp: u64,
+    pub datacenter_id: u64,
+    pub worker_id: u64,
+    pub sequence: u64,
+}
+
+impl SnowflakeParts {
+    /// 按默认纪元拆分ID
+    pub fn decode(id: u64) -> Self {
+        Self::decode_with_epoch(id, EPOCH)
+    }
+
+    /// 按指定纪元拆分ID
+    pub fn decode_with_epoch(id: u64, epoch: u64) -> Self {
+        SnowflakeParts {
+            id,
+            timestamp: extract_timestamp_with_epoch(id, epoch),
+            datacenter_id: extract_datacenter_id(id),
+            worker_id: extract_worker_id(id),
+            sequence: extract_sequence(id),
+        }
+    }
+}
+
+/// `check_ids` 发现的越界字段，附带越界的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdRangeError {
+    WorkerId(u64),
+    DatacenterId(u64),
+}
+
+impl fmt::Display for IdRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdRangeError::WorkerId(id) => write!(f, "worker_id {} exceeds maximum {}", id, MAX_WORKER_ID),
+            IdRangeError::DatacenterId(id) => write!(f, "datacenter_id {} exceeds maximum {}", id, MAX_DATACENTER_ID),
+        }
+    }
+}
+
+/// 检查worker_id和datacenter_id是否在位宽范围内（不分配内存，可在 `no_std` 下使用）
+pub fn check_ids(worker_id: u64, datacenter_id: u64) -> Result<(), IdRangeError> {
     if worker_id > MAX_WORKER_ID {
-        return Err(format!("worker_id {} exceeds maximum {}", worker_id, MAX_WORKER_ID));
+        return Err(IdRangeError::WorkerId(worker_id));
     }
     if datacenter_id > MAX_DATACENTER_ID {
-        return Err(format!("datacenter_id {} exceeds maximum {}", datacenter_id, MAX_DATACENTER_ID));
+        return Err(IdRangeError::DatacenterId(datacenter_id));
     }
     Ok(())
 }
 
+/// `validate_snowflake_id` 判定ID不合法的原因
+/// 
+/// 启用 `std` 时可经 `?` 转换为 `WorkerError::InvalidId`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnowflakeError {
+    /// 符号位被置位
+    SignBitSet { id: u64 },
+    /// 时间戳与纪元相加溢出
+    TimestampOverflow { id: u64, epoch: u64 },
+    /// 时间戳领先当前时间超过 `MAX_ID_FUTURE_MS`
+    FutureTimestamp { id: u64, timestamp: u64, now: u64 },
+    /// worker/数据中心字段超出位宽范围
+    IdRange(IdRangeError),
+}
+
+impl fmt::Display for SnowflakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnowflakeError::SignBitSet { id } => write!(f, "id {} has the sign bit set", id),
+            SnowflakeError::TimestampOverflow { id, epoch } => {
+                write!(f, "id {} timestamp overflows with epoch {}", id, epoch)
+            }
+            SnowflakeError::FutureTimestamp { id, timestamp, now } => {
+                write!(f, "id {} timestamp {} is {} ms in the future", id, timestamp, timestamp - now)
+            }
+            SnowflakeError::IdRange(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnowflakeError {}
+
+/// 检查来自不可信来源的 `id` 是否为按 `epoch` 签发的合法雪花ID
+/// 
+/// 符号位必须为0；时间戳不能领先当前时间超过 `MAX_ID_FUTURE_MS`，与纪元相加也不能溢出
+/// （时间戳按相对纪元的偏移编码，解析结果不会早于纪元）；
+/// worker/数据中心字段必须在各自位宽范围内（默认布局下总是成立，位布局可配置后才有意义）。
+/// 当前时间取自系统时钟，`no_std` 下改用 `validate_snowflake_id_at`。
+#[cfg(feature = "std")]
+pub fn validate_snowflake_id(id: u64, epoch: u64) -> Result<(), SnowflakeError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    validate_snowflake_id_at(id, epoch, now)
+}
+
+/// 与 `validate_snowflake_id` 相同，但当前时间 `now_millis` 由调用方提供，可在 `no_std` 下使用
+pub fn validate_snowflake_id_at(id: u64, epoch: u64, now_millis: u64) -> Result<(), SnowflakeError> {
+    if id >> 63 != 0 {
+        return Err(SnowflakeError::SignBitSet { id });
+    }
+
+    let timestamp = (id >> TIMESTAMP_SHIFT).checked_add(epoch)
+        .ok_or(SnowflakeError::TimestampOverflow { id, epoch })?;
+    if timestamp > now_millis.saturating_add(MAX_ID_FUTURE_MS) {
+        return Err(SnowflakeError::FutureTimestamp { id, timestamp, now: now_millis });
+    }
+
+    validate_ids(extract_worker_id(id), extract_datacenter_id(id)).map_err(SnowflakeError::IdRange)
+}
+
+/// 验证worker_id和datacenter_id的有效性，与 `check_ids` 相同，可在 `no_std` 下使用
+/// 
+/// 启用 `std` 时错误可经 `?` 转换为 `WorkerError::InvalidId`。
+pub fn validate_ids(worker_id: u64, datacenter_id: u64) -> Result<(), IdRangeError> {
+    check_ids(worker_id, datacenter_id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,10 +374,120 @@ mod tests {
         assert_eq!(extract_sequence(id), sequence);
     }
 
+    #[test]
+    fn test_min_max_id_for_timestamp() {
+        let timestamp = 1640995200000;
+        let min_id = min_id_for_timestamp(timestamp);
+        let max_id = max_id_for_timestamp(timestamp);
+
+        assert_eq!(extract_timestamp(min_id), timestamp);
+        assert_eq!(extract_timestamp(max_id), timestamp);
+        assert_eq!(max_id - min_id, (1 << TIMESTAMP_SHIFT) - 1);
+        assert_eq!(max_id + 1, min_id_for_timestamp(timestamp + 1));
+        assert_eq!(min_id_for_timestamp(0), 0);
+    }
+
+    #[test]
+    fn test_default_layout_matches_constants() {
+        let layout = SnowflakeLayout::default();
+        assert!(layout.is_valid());
+        assert_eq!(layout.timestamp_bits, 63 - TIMESTAMP_SHIFT);
+        assert_eq!(layout.sequence_capacity(), SEQUENCE_MASK + 1);
+        assert_eq!(layout.max_nodes(), (MAX_DATACENTER_ID + 1) * (MAX_WORKER_ID + 1));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_validate_snowflake_id() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        assert!(validate_snowflake_id(build_snowflake_id(now, 31, 31, SEQUENCE_MASK), EPOCH).is_ok());
+        assert!(validate_snowflake_id(0, EPOCH).is_ok());
+
+        // 符号位被置位
+        assert_eq!(validate_snowflake_id(1 << 63, EPOCH), Err(SnowflakeError::SignBitSet { id: 1 << 63 }));
+        assert!(validate_snowflake_id(u64::MAX, EPOCH).is_err());
+        // 时间戳远在未来
+        let future = build_snowflake_id(now + MAX_ID_FUTURE_MS + 60_000, 1, 1, 0);
+        assert!(matches!(validate_snowflake_id(future, EPOCH), Err(SnowflakeError::FutureTimestamp { .. })));
+        // 纪元过大导致时间戳溢出
+        let id = build_snowflake_id(now, 1, 1, 0);
+        assert!(matches!(validate_snowflake_id(id, u64::MAX - 10), Err(SnowflakeError::TimestampOverflow { .. })));
+    }
+
+    #[test]
+    fn test_validate_snowflake_id_at() {
+        let now = EPOCH + 1_000;
+        assert_eq!(validate_snowflake_id_at(build_snowflake_id(now, 31, 31, 0), EPOCH, now), Ok(()));
+
+        let future = build_snowflake_id(now + MAX_ID_FUTURE_MS + 1, 1, 1, 0);
+        let err = validate_snowflake_id_at(future, EPOCH, now).unwrap_err();
+        assert_eq!(err, SnowflakeError::FutureTimestamp { id: future, timestamp: now + MAX_ID_FUTURE_MS + 1, now });
+        assert_eq!(err.to_string(), format!("id {} timestamp {} is {} ms in the future", future, now + MAX_ID_FUTURE_MS + 1, MAX_ID_FUTURE_MS + 1));
+    }
+
+    #[test]
+    fn test_reserved_bits_stay_zero() {
+        // 39位时间戳 + 2位保留 + 5/5/12
+        let layout = SnowflakeLayout::new(39, 5, 5, 12).with_reserved_bits(2);
+        assert!(layout.is_valid());
+        assert_eq!(layout.reserved_mask(), 0b11 << 22);
+        assert_eq!(layout.timestamp_shift(), 24);
+
+        for (timestamp, datacenter_id, worker_id, sequence) in [
+            (EPOCH, 31, 31, SEQUENCE_MASK),
+            (EPOCH + (1 << 39) - 1, 0, 0, 0),
+            (1640995200000, 3, 5, 100),
+        ] {
+            let id = layout.build_id(timestamp, EPOCH, datacenter_id, worker_id, sequence).unwrap();
+            assert_eq!(id & layout.reserved_mask(), 0);
+            assert_eq!(layout.decode(id, EPOCH), (timestamp, datacenter_id, worker_id, sequence));
+            // 下游在保留位写入的值不影响解码
+            assert_eq!(layout.decode(id | layout.reserved_mask(), EPOCH), (timestamp, datacenter_id, worker_id, sequence));
+        }
+
+        // 超出位宽的字段不会溢出到保留位或符号位
+        let id = layout.build_id(EPOCH, EPOCH, u64::MAX, u64::MAX, u64::MAX).unwrap();
+        assert_eq!(id & layout.reserved_mask(), 0);
+        let id = layout.build_id(EPOCH + (1 << 40) + 7, EPOCH, 0, 0, 0).unwrap();
+        assert_eq!(id >> 63, 0);
+        assert_eq!(layout.decode(id, EPOCH).0, EPOCH + 7);
+
+        assert_eq!(layout.build_id(EPOCH - 1, EPOCH, 0, 0, 0), None);
+    }
+
+    #[test]
+    fn test_layout_decode_ignores_sign_bit_and_saturates() {
+        let layout = SnowflakeLayout::new(39, 5, 5, 12).with_reserved_bits(2);
+        let id = layout.build_id(EPOCH + 7, EPOCH, 1, 2, 3).unwrap();
+        assert_eq!(layout.decode(id | 1 << 63, EPOCH), (EPOCH + 7, 1, 2, 3));
+
+        let (timestamp, ..) = layout.decode(u64::MAX, u64::MAX - 10);
+        assert_eq!(timestamp, u64::MAX);
+    }
+
     #[test]
     fn test_validation() {
+        assert_eq!(check_ids(31, 31), Ok(()));
+        assert_eq!(check_ids(32, 31), Err(IdRangeError::WorkerId(32)));
+        assert_eq!(check_ids(31, 32), Err(IdRangeError::DatacenterId(32)));
+    }
+
+    #[test]
+    fn test_validate_ids_messages() {
         assert!(validate_ids(31, 31).is_ok());
-        assert!(validate_ids(32, 31).is_err());
-        assert!(validate_ids(31, 32).is_err());
+        assert_eq!(validate_ids(32, 31), Err(IdRangeError::WorkerId(32)));
+        assert_eq!(validate_ids(32, 31).unwrap_err().to_string(), "worker_id 32 exceeds maximum 31");
+        assert_eq!(validate_ids(31, 32).unwrap_err().to_string(), "datacenter_id 32 exceeds maximum 31");
+    }
+
+    #[test]
+    fn test_snowflake_parts() {
+        let id = build_snowflake_id(1640995200000, 3, 5, 100);
+        assert_eq!(
+            SnowflakeParts::decode(id),
+            SnowflakeParts { id, timestamp: 1640995200000, datacenter_id: 3, worker_id: 5, sequence: 100 }
+        );
+        let discord = build_snowflake_id_with_epoch(1640995200000, DISCORD_EPOCH, 1, 2, 3);
+        assert_eq!(SnowflakeParts::decode_with_epoch(discord, DISCORD_EPOCH).timestamp, 1640995200000);
     }
 }
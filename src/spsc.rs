@@ -0,0 +1,139 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::snowflake::Snowflake;
+use crate::worker_manager::WorkerError;
+
+/// 单生产者/单消费者ID管道的消费端
+/// 
+/// 由 `spawn` 启动一个生产者线程，持续将ID推入有界无锁环形缓冲区；
+/// 消费端通过 `recv` 取出ID。缓冲区满时生产者让出CPU等待消费。
+/// 调用 `shutdown` 或丢弃消费端会停止并回收生产者线程。
+/// 生成失败时生产者停止，错误可在 `recv` 返回 `None` 后通过 `producer_error` 取得。
+pub struct SpscIdReceiver {
+    consumer: Consumer<u64>,
+    stop: Arc<AtomicBool>,
+    producer: Option<JoinHandle<Result<(), WorkerError>>>,
+    error: Option<WorkerError>,
+}
+
+impl SpscIdReceiver {
+    /// 将生成器移入生产者线程，返回容量为 `capacity` 的管道消费端
+    pub fn spawn(snowflake: Snowflake, capacity: usize) -> Self {
+        let (producer, consumer) = RingBuffer::new(capacity);
+        let stop = Arc::new(AtomicBool::new(false));
+        let producer_stop = Arc::clone(&stop);
+        let handle = thread::spawn(move || run_producer(snowflake, producer, producer_stop));
+
+        SpscIdReceiver {
+            consumer,
+            stop,
+            producer: Some(handle),
+            error: None,
+        }
+    }
+
+    /// 阻塞直到取得下一个ID；生产者已停止且缓冲区已空时返回 `None`
+    pub fn recv(&mut self) -> Option<u64> {
+        loop {
+            if let Ok(id) = self.consumer.pop() {
+                return Some(id);
+            }
+            if self.consumer.is_abandoned() {
+                // 生产者已退出，回收线程以取得其错误；退出前推入的最后一批ID仍需取完
+                self.join_producer();
+                return self.consumer.pop().ok();
+            }
+            thread::yield_now();
+        }
+    }
+
+    /// 当前缓冲区中可直接取出的ID数量
+    pub fn buffered(&self) -> usize {
+        self.consumer.slots()
+    }
+
+    /// 生产者因生成失败而停止时返回该错误
+    /// 
+    /// 错误在 `recv` 返回 `None` 或调用 `shutdown` 之后才可见；正常停止时为 `None`。
+    pub fn producer_error(&self) -> Option<&WorkerError> {
+        self.error.as_ref()
+    }
+
+    /// 停止生产者并等待其退出，缓冲区中剩余的ID仍可通过 `recv` 取出
+    pub fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        self.join_producer();
+    }
+
+    fn join_producer(&mut self) {
+        if let Some(handle) = self.producer.take() {
+            if let Ok(Err(err)) = handle.join() {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+impl Drop for SpscIdReceiver {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run_producer(mut snowflake: Snowflake, mut producer: Producer<u64>, stop: Arc<AtomicBool>) -> Result<(), WorkerError> {
+    while !stop.load(Ordering::Acquire) {
+        if producer.is_full() {
+            thread::yield_now();
+            continue;
+        }
+        let id = snowflake.next_id()?;
+        // 单生产者且已确认有空位，push 不会失败
+        let _ = producer.push(id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snowflake::IdQuota;
+
+    #[test]
+    fn test_receiver_gets_unique_ordered_ids() {
+        let mut receiver = SpscIdReceiver::spawn(Snowflake::new(2, 1), 1024);
+        let ids: Vec<u64> = (0..20_000).map(|_| receiver.recv().unwrap()).collect();
+
+        assert_eq!(ids.len(), 20_000);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        receiver.shutdown();
+        let remaining = receiver.buffered();
+        for _ in 0..remaining {
+            assert!(receiver.recv().unwrap() > ids[ids.len() - 1]);
+        }
+        assert_eq!(receiver.recv(), None);
+    }
+
+    #[test]
+    fn test_generation_error_reaches_consumer() {
+        let mut snowflake = Snowflake::new(3, 1);
+        snowflake.set_quota(Some(IdQuota::lifetime(100)));
+        let mut receiver = SpscIdReceiver::spawn(snowflake, 16);
+
+        let ids: Vec<u64> = std::iter::from_fn(|| receiver.recv()).collect();
+        assert_eq!(ids.len(), 100);
+        assert!(matches!(receiver.producer_error(), Some(WorkerError::QuotaExceeded(_))));
+    }
+
+    #[test]
+    fn test_shutdown_is_not_an_error() {
+        let mut receiver = SpscIdReceiver::spawn(Snowflake::new(4, 1), 16);
+        receiver.recv().unwrap();
+        receiver.shutdown();
+        assert!(receiver.producer_error().is_none());
+    }
+}
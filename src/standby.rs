@@ -0,0 +1,104 @@
+use crate::snowflake::{ClockBackwardsPolicy, Snowflake, DEFAULT_MAX_BACKWARDS_WAIT};
+use crate::worker_manager::WorkerError;
+
+/// 提升时默认在观测水位之上额外留出的安全偏移（毫秒）
+pub const DEFAULT_STANDBY_SAFETY_OFFSET_MS: u64 = 1;
+
+/// 用于容灾的备用生成器
+/// 
+/// 备用生成器与主生成器共享同一 worker 身份。在被 `promote` 之前，
+/// 它只跟踪主生成器上报的水位（`observe_active`）并拒绝生成；
+/// 提升后从水位之上的安全偏移处开始，保证不会签发主生成器已覆盖范围内的ID。
+pub struct StandbySnowflake {
+    inner: Snowflake,
+    watermark: u64,
+    safety_offset_ms: u64,
+    promoted: bool,
+}
+
+impl StandbySnowflake {
+    pub fn new(worker_id: u64, datacenter_id: u64) -> Self {
+        let mut inner = Snowflake::new(worker_id, datacenter_id);
+        // 水位可能略领先于本机时钟，提升后等待时钟追上而不是直接报错
+        inner.set_clock_policy(ClockBackwardsPolicy::WaitUpTo(DEFAULT_MAX_BACKWARDS_WAIT));
+
+        StandbySnowflake {
+            inner,
+            watermark: 0,
+            safety_offset_ms: DEFAULT_STANDBY_SAFETY_OFFSET_MS,
+            promoted: false,
+        }
+    }
+
+    /// 设置提升时在水位之上额外留出的毫秒数
+    pub fn set_safety_offset_ms(&mut self, offset_ms: u64) {
+        self.safety_offset_ms = offset_ms;
+    }
+
+    /// 记录主生成器最新的 `last_timestamp`，水位只会前进不会后退
+    pub fn observe_active(&mut self, last_timestamp: u64) {
+        self.watermark = self.watermark.max(last_timestamp);
+    }
+
+    pub fn get_watermark(&self) -> u64 {
+        self.watermark
+    }
+
+    pub fn is_promoted(&self) -> bool {
+        self.promoted
+    }
+
+    /// 提升为主生成器，之后生成的ID时间戳严格大于 水位 + 安全偏移
+    pub fn promote(&mut self) {
+        if !self.promoted {
+            self.inner.fence_after(self.watermark + self.safety_offset_ms);
+            self.promoted = true;
+        }
+    }
+
+    /// 生成下一个ID；未提升时返回 `WorkerIdConflict`
+    pub fn next_id(&mut self) -> Result<u64, WorkerError> {
+        if !self.promoted {
+            return Err(WorkerError::WorkerIdConflict(format!(
+                "standby for worker_id {} has not been promoted (active watermark {})",
+                self.inner.get_worker_id(), self.watermark
+            )));
+        }
+        self.inner.next_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snowflake_core::{extract_timestamp, max_id_for_timestamp};
+
+    #[test]
+    fn test_standby_promotion() {
+        let mut active = Snowflake::new(3, 1);
+        let mut standby = StandbySnowflake::new(3, 1);
+
+        let active_ids = active.next_ids(100).unwrap();
+        standby.observe_active(active.get_last_timestamp());
+        let watermark = standby.get_watermark();
+
+        assert!(matches!(standby.next_id(), Err(WorkerError::WorkerIdConflict(_))));
+        assert!(!standby.is_promoted());
+
+        standby.promote();
+        for _ in 0..1000 {
+            let id = standby.next_id().unwrap();
+            assert!(id > max_id_for_timestamp(watermark));
+            assert!(extract_timestamp(id) > watermark);
+            assert!(active_ids.iter().all(|active_id| id > *active_id));
+        }
+    }
+
+    #[test]
+    fn test_watermark_only_advances() {
+        let mut standby = StandbySnowflake::new(1, 1);
+        standby.observe_active(100);
+        standby.observe_active(50);
+        assert_eq!(standby.get_watermark(), 100);
+    }
+}
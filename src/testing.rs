@@ -0,0 +1,95 @@
+//! 测试替身：用于在下游服务的集成测试中模拟生成器故障
+
+use std::time::Duration;
+
+use crate::snowflake::Snowflake;
+use crate::worker_manager::WorkerError;
+
+type ErrorFactory = Box<dyn Fn() -> WorkerError + Send + Sync>;
+
+/// 可注入故障的生成器包装
+/// 
+/// 按配置每第 N 次调用 `next_id` 返回指定错误，或在每次调用前注入固定延迟，
+/// 用于验证调用方对生成失败与慢响应的处理。仅供测试使用。
+pub struct FaultInjectingSnowflake {
+    inner: Snowflake,
+    fail_every: u64,
+    error: Option<ErrorFactory>,
+    latency: Duration,
+    calls: u64,
+}
+
+impl FaultInjectingSnowflake {
+    pub fn new(inner: Snowflake) -> Self {
+        FaultInjectingSnowflake {
+            inner,
+            fail_every: 0,
+            error: None,
+            latency: Duration::ZERO,
+            calls: 0,
+        }
+    }
+
+    /// 每第 `n` 次调用返回 `error()` 构造的错误（`n` 为0时不注入失败）
+    pub fn fail_every(mut self, n: u64, error: impl Fn() -> WorkerError + Send + Sync + 'static) -> Self {
+        self.fail_every = n;
+        self.error = Some(Box::new(error));
+        self
+    }
+
+    /// 每次调用前休眠 `latency`，模拟慢速生成
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// 按配置注入延迟或失败，否则委托给内部生成器
+    pub fn next_id(&mut self) -> Result<u64, WorkerError> {
+        self.calls += 1;
+        if !self.latency.is_zero() {
+            std::thread::sleep(self.latency);
+        }
+        if let Some(error) = &self.error {
+            if self.calls.is_multiple_of(self.fail_every) {
+                return Err(error());
+            }
+        }
+        self.inner.next_id()
+    }
+
+    /// 累计的 `next_id` 调用次数（包括被注入失败的调用）
+    pub fn get_call_count(&self) -> u64 {
+        self.calls
+    }
+
+    pub fn into_inner(self) -> Snowflake {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_fail_every_third_call() {
+        let mut generator = FaultInjectingSnowflake::new(Snowflake::new(1, 1))
+            .fail_every(3, || WorkerError::StoreError("injected".to_string()));
+
+        let results: Vec<bool> = (0..9).map(|_| generator.next_id().is_ok()).collect();
+        assert_eq!(results, [true, true, false, true, true, false, true, true, false]);
+        assert_eq!(generator.get_call_count(), 9);
+        assert!(generator.next_id().is_ok());
+    }
+
+    #[test]
+    fn test_injected_latency() {
+        let mut generator = FaultInjectingSnowflake::new(Snowflake::new(1, 1))
+            .with_latency(Duration::from_millis(5));
+
+        let start = Instant::now();
+        generator.next_id().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(5));
+    }
+}
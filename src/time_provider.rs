@@ -1,7 +1,7 @@
 use std::time::{SystemTime, UNIX_EPOCH, Instant};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::thread;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
 /// 时间提供者 trait
@@ -9,6 +9,26 @@ pub trait TimeProvider {
     fn current_millis(&self) -> u64;
 }
 
+/// 在采样窗口内反复读取时间，返回观测到的最小非零时间增量（毫秒）
+/// 
+/// 返回值即时间戳的实际分辨率；窗口内时间完全没有前进时返回 `None`，
+/// 说明时间源可能已经停滞。
+pub fn measure_resolution_gap<T: TimeProvider + ?Sized>(provider: &T, window: Duration) -> Option<u64> {
+    let start = Instant::now();
+    let mut last = provider.current_millis();
+    let mut min_gap: Option<u64> = None;
+
+    while start.elapsed() < window {
+        let now = provider.current_millis();
+        if now > last {
+            let gap = now - last;
+            min_gap = Some(min_gap.map_or(gap, |g| g.min(gap)));
+        }
+        last = now;
+    }
+    min_gap
+}
+
 /// 系统时间提供者：直接获取系统时间
 pub struct SystemTimeProvider;
 
@@ -29,16 +49,26 @@ pub struct RelativeTimeProvider {
 
 impl RelativeTimeProvider {
     pub fn new() -> Self {
+        Self::from_base(&SystemTimeProvider)
+    }
+
+    /// 以 `base` 当前的读数为起点，之后按单调时钟计时，不再读取 `base`
+    /// 
+    /// 起点之后 `base` 的任何跳变（包括回拨）都不影响本提供者。
+    pub fn from_base<T: TimeProvider + ?Sized>(base: &T) -> Self {
         Self {
             start_instant: Instant::now(),
-            start_millis: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64,
+            start_millis: base.current_millis(),
         }
     }
 }
 
+impl Default for RelativeTimeProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TimeProvider for RelativeTimeProvider {
     fn current_millis(&self) -> u64 {
         let elapsed = self.start_instant.elapsed().as_millis() as u64;
@@ -46,12 +76,40 @@ impl TimeProvider for RelativeTimeProvider {
     }
 }
 
+/// 步进时间提供者：不读取系统时钟，每次读取前进固定步长，用于需要可复现时间戳的测试
+pub struct SteppingTimeProvider {
+    next_millis: AtomicU64,
+    step_millis: u64,
+}
+
+impl SteppingTimeProvider {
+    /// 第一次读取返回 `start_millis`，之后每次读取前进 `step_millis`（至少为1）
+    pub fn new(start_millis: u64, step_millis: u64) -> Self {
+        Self {
+            next_millis: AtomicU64::new(start_millis),
+            step_millis: step_millis.max(1),
+        }
+    }
+}
+
+impl TimeProvider for SteppingTimeProvider {
+    fn current_millis(&self) -> u64 {
+        self.next_millis.fetch_add(self.step_millis, Ordering::Relaxed)
+    }
+}
+
 /// 缓存时间提供者：定期更新时间戳缓存
 pub struct CachedTimeProvider {
     /// 缓存的时间戳
     cached_millis: AtomicU64,
-    /// 是否正在运行
-    running: AtomicU64, // 使用 AtomicU64 作为布尔值 (0=false, 1=true)
+    /// 停止信号：`true` 表示后台线程应退出，配合条件变量在休眠中及时唤醒
+    stop_signal: Arc<(Mutex<bool>, Condvar)>,
+    /// 后台更新线程句柄，`stop_and_join` 时取出并等待其退出
+    worker: Mutex<Option<JoinHandle<()>>>,
+    /// 后台线程的更新间隔（毫秒），与后台线程共享，每轮休眠前在 `stop_signal` 的锁内重新读取
+    interval_ms: Arc<AtomicU64>,
+    /// `force_update` 时观测到的缓存时间落后真实时间的最大值（毫秒）
+    max_staleness_ms: AtomicU64,
 }
 
 impl TimeProvider for CachedTimeProvider {
@@ -64,31 +122,95 @@ impl CachedTimeProvider {
     pub fn new(update_interval_ms: u64) -> Arc<Self> {
         let provider = Arc::new(CachedTimeProvider {
             cached_millis: AtomicU64::new(Self::get_system_millis()),
-            running: AtomicU64::new(1),
+            stop_signal: Arc::new((Mutex::new(false), Condvar::new())),
+            worker: Mutex::new(None),
+            interval_ms: Arc::new(AtomicU64::new(update_interval_ms)),
+            max_staleness_ms: AtomicU64::new(0),
         });
         
-        // 启动后台线程定期更新时间戳
-        let provider_clone = provider.clone();
-        thread::spawn(move || {
-            while provider_clone.running.load(Ordering::Relaxed) == 1 {
-                let current_time = Self::get_system_millis();
-                provider_clone.cached_millis.store(current_time, Ordering::Relaxed);
-                thread::sleep(Duration::from_millis(update_interval_ms));
-            }
-        });
+        // 启动后台线程定期更新时间戳；线程只持有弱引用，
+        // 最后一个使用者释放后 `Drop` 即可停止并回收它
+        let weak = Arc::downgrade(&provider);
+        let signal = provider.stop_signal.clone();
+        let interval = provider.interval_ms.clone();
+        let handle = thread::spawn(move || Self::run_updater(weak, signal, interval));
+        *provider.worker.lock().unwrap() = Some(handle);
         
         provider
     }
     
-    /// 强制更新时间戳
+    /// 运行时调整后台线程的更新间隔，无需重建时间提供者
+    /// 
+    /// 会唤醒正在休眠的后台线程，新间隔立即生效，不必等旧间隔（可能很长）的休眠结束。
+    /// 间隔决定了ID时间戳最多落后真实时间多少毫秒，
+    /// 但时间戳本身只有毫秒精度，间隔低于1毫秒（即0）只会让后台线程空转，收益递减。
+    pub fn set_interval(&self, interval_ms: u64) {
+        // 持锁写入并通知：后台线程在同一把锁内读取间隔后才休眠，不会错过这次唤醒
+        let (lock, wake) = &*self.stop_signal;
+        let _guard = lock.lock().unwrap();
+        self.interval_ms.store(interval_ms, Ordering::Relaxed);
+        wake.notify_all();
+    }
+
+    /// 当前后台线程的更新间隔（毫秒），即最近一次 `new` 或 `set_interval` 设置的值
+    pub fn get_interval_ms(&self) -> u64 {
+        self.interval_ms.load(Ordering::Relaxed)
+    }
+
+    fn run_updater(provider: Weak<Self>, signal: Arc<(Mutex<bool>, Condvar)>, interval: Arc<AtomicU64>) {
+        // `new` 已写入初始时间，先休眠再刷新，缓存时间从构造时刻起算
+        let (stopped, wake) = &*signal;
+        loop {
+            let guard = stopped.lock().unwrap();
+            if *guard {
+                return;
+            }
+            let interval_ms = interval.load(Ordering::Relaxed);
+            let (guard, _) = wake.wait_timeout(guard, Duration::from_millis(interval_ms)).unwrap();
+            if *guard {
+                return;
+            }
+            drop(guard);
+
+            match provider.upgrade() {
+                Some(provider) => provider.cached_millis.store(Self::get_system_millis(), Ordering::Relaxed),
+                None => return,
+            }
+        }
+    }
+
+    /// 强制更新时间戳，并记录更新前缓存时间落后真实时间的毫秒数
     pub fn force_update(&self) {
         let current_time = Self::get_system_millis();
-        self.cached_millis.store(current_time, Ordering::Relaxed);
+        let cached = self.cached_millis.swap(current_time, Ordering::Relaxed);
+        self.max_staleness_ms.fetch_max(current_time.saturating_sub(cached), Ordering::Relaxed);
+    }
+
+    /// 历次 `force_update` 时观测到的最大时间落后（毫秒）
+    /// 
+    /// 反映ID时间戳实际可能落后真实时间的程度，正常情况下不超过更新间隔。
+    pub fn max_staleness_observed(&self) -> u64 {
+        self.max_staleness_ms.load(Ordering::Relaxed)
     }
     
     /// 停止后台更新线程
     pub fn stop(&self) {
-        self.running.store(0, Ordering::Relaxed);
+        let (stopped, wake) = &*self.stop_signal;
+        *stopped.lock().unwrap() = true;
+        wake.notify_all();
+    }
+
+    /// 停止后台更新线程并等待其退出
+    /// 
+    /// 返回后后台线程已退出，不会再更新缓存时间。
+    pub fn stop_and_join(&self) {
+        self.stop();
+        let handle = self.worker.lock().unwrap().take();
+        if let Some(handle) = handle {
+            if handle.thread().id() != thread::current().id() {
+                let _ = handle.join();
+            }
+        }
     }
     
     fn get_system_millis() -> u64 {
@@ -100,7 +222,97 @@ impl CachedTimeProvider {
 }
 
 impl Drop for CachedTimeProvider {
+    /// 停止后台线程并等待其退出，避免反复创建时累积游离线程
     fn drop(&mut self) {
-        self.stop();
+        self.stop_and_join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_resolution_gap_stepping_clock() {
+        let clock = SteppingTimeProvider::new(1_000, 7);
+        assert_eq!(measure_resolution_gap(&clock, Duration::from_millis(5)), Some(7));
+        assert_eq!(SteppingTimeProvider::new(1_000, 0).current_millis(), 1_000);
+    }
+
+    #[test]
+    fn test_measure_resolution_gap_cached_provider() {
+        let provider = CachedTimeProvider::new(1);
+        let gap = measure_resolution_gap(provider.as_ref(), Duration::from_millis(50)).unwrap();
+        assert!(gap >= 1);
+        provider.stop();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_dropped_providers_do_not_leak_threads() {
+        fn thread_count() -> usize {
+            std::fs::read_dir("/proc/self/task").unwrap().count()
+        }
+
+        let before = thread_count();
+        for _ in 0..1000 {
+            let provider = CachedTimeProvider::new(50);
+            assert!(provider.current_millis() > 0);
+        }
+        // 其他并行测试也会创建线程，只要求没有随循环次数累积
+        assert!(thread_count() < before + 50, "threads grew from {} to {}", before, thread_count());
+    }
+
+    #[test]
+    fn test_set_interval_changes_cadence() {
+        let provider = CachedTimeProvider::new(1);
+        provider.set_interval(50);
+        assert_eq!(provider.get_interval_ms(), 50);
+        // 等待旧间隔下的休眠结束
+        thread::sleep(Duration::from_millis(5));
+        let coarse = measure_resolution_gap(provider.as_ref(), Duration::from_millis(300)).unwrap();
+        assert!(coarse >= 40, "gap {} should follow the 50ms interval", coarse);
+
+        provider.set_interval(1);
+        thread::sleep(Duration::from_millis(60));
+        let fine = measure_resolution_gap(provider.as_ref(), Duration::from_millis(50)).unwrap();
+        assert!(fine < coarse);
+        provider.stop_and_join();
+    }
+
+    #[test]
+    fn test_set_interval_wakes_sleeping_updater() {
+        let provider = CachedTimeProvider::new(60_000);
+        thread::sleep(Duration::from_millis(20));
+        let stale = provider.current_millis();
+
+        // 不唤醒的话后台线程要再睡将近一分钟才会读取新间隔
+        provider.set_interval(1);
+        thread::sleep(Duration::from_millis(50));
+        assert!(provider.current_millis() > stale, "cache stayed at {}", stale);
+        provider.stop_and_join();
+    }
+
+    #[test]
+    fn test_max_staleness_bounded_by_interval() {
+        let provider = CachedTimeProvider::new(1);
+        provider.set_interval(30);
+        assert_eq!(provider.max_staleness_observed(), 0);
+
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(45));
+            provider.force_update();
+        }
+        // 留出调度延迟的余量
+        let staleness = provider.max_staleness_observed();
+        assert!(staleness <= 30 + 20, "staleness {} exceeds the 30ms interval", staleness);
+        provider.stop_and_join();
+
+        // 更新间隔远大于等待时间，缓存停在创建时刻
+        let stalled = CachedTimeProvider::new(3_600_000);
+        thread::sleep(Duration::from_millis(100));
+        stalled.force_update();
+        assert!(stalled.max_staleness_observed() >= 100);
+        stalled.stop_and_join();
     }
 }
@@ -1,15 +1,47 @@
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::Path;
+use std::fs::TryLockError;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::error::Error;
 use std::fmt;
 
+use crate::snowflake_core::{IdRangeError, SnowflakeError, MAX_WORKER_ID};
+
 #[derive(Debug)]
 pub enum WorkerError {
     IoError(std::io::Error),
     ParseError(String),
     ClockBackwardsError(String),
+    /// 锁竞争：另一个持有者占用了同一资源，消息中包含资源路径
+    LockContention(String),
+    /// 持久化存储错误，消息中包含存储路径或键
+    StoreError(String),
+    /// worker_id / datacenter_id 等参数超出有效范围
+    InvalidId(String),
+    /// 与另一个生成器使用相同 worker ID 且时间窗口重叠，可能产生重复ID
+    WorkerIdConflict(String),
+    /// 调用参数不合法（如数量超出允许范围）
+    InvalidArgument(String),
+    /// 当前配额窗口内签发的ID数已达上限
+    QuotaExceeded(String),
+    /// worker 配置文件已被另一个进程（或同一进程中的另一个 `WorkerManager`）锁定
+    AlreadyLocked(String),
+    /// ID格式合法，但来源（数据中心/worker）未通过 `IdFilter`
+    RejectedId(String),
+    /// 配置文件格式可以识别，但读取它所需的 feature 未启用（如JSON或gzip压缩的配置文件）
+    UnsupportedFormat(String),
+}
+
+impl WorkerError {
+    /// 构造锁竞争错误，`resource` 为被占用的路径或键
+    pub fn lock_contention(resource: &str, detail: impl fmt::Display) -> Self {
+        WorkerError::LockContention(format!("'{}' is held by another owner: {}", resource, detail))
+    }
+
+    /// 构造存储错误，`location` 为出错的路径或键
+    pub fn store_error(location: &str, detail: impl fmt::Display) -> Self {
+        WorkerError::StoreError(format!("failed to persist '{}': {}", location, detail))
+    }
 }
 
 impl fmt::Display for WorkerError {
@@ -18,6 +50,15 @@ impl fmt::Display for WorkerError {
             WorkerError::IoError(err) => write!(f, "IO error: {}", err),
             WorkerError::ParseError(msg) => write!(f, "Parse error: {}", msg),
             WorkerError::ClockBackwardsError(msg) => write!(f, "Clock backwards error: {}", msg),
+            WorkerError::LockContention(msg) => write!(f, "Lock contention: {}", msg),
+            WorkerError::StoreError(msg) => write!(f, "Store error: {}", msg),
+            WorkerError::InvalidId(msg) => write!(f, "Invalid id: {}", msg),
+            WorkerError::WorkerIdConflict(msg) => write!(f, "Worker id conflict: {}", msg),
+            WorkerError::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            WorkerError::QuotaExceeded(msg) => write!(f, "Quota exceeded: {}", msg),
+            WorkerError::AlreadyLocked(msg) => write!(f, "Already locked: {}", msg),
+            WorkerError::RejectedId(msg) => write!(f, "Rejected id: {}", msg),
+            WorkerError::UnsupportedFormat(msg) => write!(f, "Unsupported format: {}", msg),
         }
     }
 }
@@ -30,12 +71,103 @@ impl From<std::io::Error> for WorkerError {
     }
 }
 
-#[derive(Debug, Clone)]
+impl From<IdRangeError> for WorkerError {
+    fn from(error: IdRangeError) -> Self {
+        WorkerError::InvalidId(error.to_string())
+    }
+}
+
+impl From<SnowflakeError> for WorkerError {
+    fn from(error: SnowflakeError) -> Self {
+        WorkerError::InvalidId(error.to_string())
+    }
+}
+
+/// worker ID 派生使用的哈希函数
+/// 
+/// 必须在不同 Rust 版本间输出稳定，否则升级工具链后派生出的 worker ID 会变化。
+pub type WorkerIdHasher = fn(&[u8]) -> u64;
+
+/// 64位 FNV-1a 哈希，算法固定、跨版本稳定，是默认的 worker ID 哈希函数
+pub fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// 新建配置文件时派生 worker ID 的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorkerIdStrategy {
+    /// 机器名加当前时间的哈希（默认）：同一台机器每次派生的结果不同
+    #[default]
+    HostnameAndTime,
+    /// 第一个非回环网卡的MAC地址折叠到 worker ID 范围，同一台机器总是得到相同结果；
+    /// 找不到网卡时退化为只用机器名的哈希
+    MacAddress,
+}
+
+/// CRC-32 (IEEE 802.3) 校验和，用于检测配置文件损坏
+fn crc32(bytes: &[u8]) -> u32 {
+    const POLYNOMIAL: u32 = 0xedb88320;
+
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 == 1 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 }
+        })
+    })
+}
+
+/// 配置文件末尾校验和行的前缀
+const CHECKSUM_PREFIX: &str = "crc32:";
+
+/// 校验和行出现之前的逐行格式：worker_id、datacenter_id、last_timestamp、creation_time 各占一行
+const LEGACY_FIELD_COUNT: usize = 4;
+
+/// 恢复配置时允许保存的时间戳领先当前时钟的最大毫秒数
+/// 
+/// 领先量在此范围内视为保存时的时钟抖动而非回拨，生成器会等待时钟追上。
+pub const RESTORE_GRACE_MS: u64 = 1000;
+
+/// gzip 魔数，用于识别压缩过的配置文件
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// JSON 格式配置文件的当前版本号
+/// 
+/// 新增字段时递增；读取时忽略未知字段，旧版本程序仍能读取新版本写出的文件。
+pub const WORKER_INFO_JSON_VERSION: u32 = 3;
+
+/// JSON 格式配置文件开始带 `checksum` 字段的版本号，更早的版本允许缺少校验和
+#[cfg(feature = "json")]
+const JSON_CHECKSUM_VERSION: u32 = 3;
+
+/// JSON 格式配置文件的内容
+#[cfg(feature = "json")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WorkerInfoJson {
+    version: u32,
+    worker_id: u64,
+    datacenter_id: u64,
+    last_timestamp: u64,
+    creation_time: u64,
+    /// 版本2新增，版本1的文件缺省为0
+    #[serde(default)]
+    last_sequence: u64,
+    /// 版本3新增：`crc32:` 加上与逐行格式相同字段内容的校验和
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WorkerInfo {
     pub worker_id: u64,
     pub datacenter_id: u64,
     pub last_timestamp: u64,
     pub creation_time: u64,
+    /// `last_timestamp` 这一毫秒内可能已签发的最大序列号
+    pub last_sequence: u64,
 }
 
 impl WorkerInfo {
@@ -46,14 +178,48 @@ impl WorkerInfo {
             datacenter_id,
             last_timestamp: current_time,
             creation_time: current_time,
+            last_sequence: 0,
         }
     }
 
+    /// 解析配置文件内容
+    /// 
+    /// 以 `{` 开头的内容按JSON格式解析（需要 `json` feature，未启用时返回 `UnsupportedFormat`），
+    /// 否则按逐行格式解析：
+    /// 末尾的 `crc32:` 行覆盖其之前的全部内容，不匹配时返回 `ParseError`；
+    /// 没有 `last_sequence` 行的带校验和文件按0处理。没有校验和行的文件只有在恰好是
+    /// 校验和出现之前的4行格式（每行以换行结尾）时才作为旧格式接受，下一次保存时补上；
+    /// 其余缺少校验和行的内容（例如在校验和行之前被截断的文件）返回 `ParseError`。
     pub fn from_file_content(content: &str) -> Result<Self, WorkerError> {
+        if content.trim_start().starts_with('{') {
+            return Self::from_json_content(content);
+        }
+
+        let content = match content.find(&format!("\n{}", CHECKSUM_PREFIX)) {
+            Some(pos) => {
+                let (body, checksum_line) = content.split_at(pos + 1);
+                let expected = u32::from_str_radix(checksum_line[CHECKSUM_PREFIX.len()..].trim(), 16)
+                    .map_err(|_| WorkerError::ParseError("Invalid checksum".to_string()))?;
+                let actual = crc32(body.as_bytes());
+                if actual != expected {
+                    return Err(WorkerError::ParseError(format!(
+                        "Checksum mismatch: expected {:08x}, got {:08x}", expected, actual
+                    )));
+                }
+                body
+            }
+            None if content.ends_with('\n') && content.split_terminator('\n').count() == LEGACY_FIELD_COUNT => content,
+            None => {
+                return Err(WorkerError::ParseError(
+                    "Invalid file format: missing checksum line".to_string()
+                ));
+            }
+        };
+
         let lines: Vec<&str> = content.trim().split('\n').collect();
-        if lines.len() < 4 {
+        if lines.len() != 4 && lines.len() != 5 {
             return Err(WorkerError::ParseError(
-                "Invalid file format: missing required fields".to_string()
+                "Invalid file format: expected 4 or 5 fields".to_string()
             ));
         }
 
@@ -69,30 +235,132 @@ impl WorkerInfo {
         let creation_time = lines[3].trim().parse::<u64>()
             .map_err(|_| WorkerError::ParseError("Invalid creation_time".to_string()))?;
 
+        let last_sequence = match lines.get(4) {
+            Some(line) => line.trim().parse::<u64>()
+                .map_err(|_| WorkerError::ParseError("Invalid last_sequence".to_string()))?,
+            None => 0,
+        };
+
         Ok(WorkerInfo {
             worker_id,
             datacenter_id,
             last_timestamp,
             creation_time,
+            last_sequence,
         })
     }
 
     pub fn to_file_content(&self) -> String {
-        format!("{}\n{}\n{}\n{}\n", 
+        let body = self.checksum_body();
+        let checksum = crc32(body.as_bytes());
+        format!("{}{}{:08x}\n", body, CHECKSUM_PREFIX, checksum)
+    }
+
+    /// 校验和覆盖的内容：逐行格式中校验和行之前的部分，JSON格式的校验和也按它计算
+    fn checksum_body(&self) -> String {
+        format!("{}\n{}\n{}\n{}\n{}\n", 
             self.worker_id, 
             self.datacenter_id, 
             self.last_timestamp, 
-            self.creation_time
+            self.creation_time,
+            self.last_sequence
         )
     }
 
+    /// 序列化为带版本号的JSON格式
+    #[cfg(feature = "json")]
+    pub fn to_json_content(&self) -> String {
+        let json = WorkerInfoJson {
+            version: WORKER_INFO_JSON_VERSION,
+            worker_id: self.worker_id,
+            datacenter_id: self.datacenter_id,
+            last_timestamp: self.last_timestamp,
+            creation_time: self.creation_time,
+            last_sequence: self.last_sequence,
+            checksum: Some(format!("{}{:08x}", CHECKSUM_PREFIX, crc32(self.checksum_body().as_bytes()))),
+        };
+        let mut content = serde_json::to_string_pretty(&json).expect("WorkerInfoJson is serializable");
+        content.push('\n');
+        content
+    }
+
+    /// 解析JSON格式
+    /// 
+    /// `checksum` 与逐行格式的校验和行一样覆盖全部字段，不匹配时返回 `ParseError`；
+    /// 只有版本3之前的文件允许缺少校验和，下一次保存时补上。
+    #[cfg(feature = "json")]
+    fn from_json_content(content: &str) -> Result<Self, WorkerError> {
+        let json: WorkerInfoJson = serde_json::from_str(content)
+            .map_err(|e| WorkerError::ParseError(format!("Invalid JSON worker config: {}", e)))?;
+        let info = WorkerInfo {
+            worker_id: json.worker_id,
+            datacenter_id: json.datacenter_id,
+            last_timestamp: json.last_timestamp,
+            creation_time: json.creation_time,
+            last_sequence: json.last_sequence,
+        };
+
+        match json.checksum {
+            Some(checksum) => {
+                let expected = checksum.strip_prefix(CHECKSUM_PREFIX)
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| WorkerError::ParseError("Invalid checksum".to_string()))?;
+                let actual = crc32(info.checksum_body().as_bytes());
+                if actual != expected {
+                    return Err(WorkerError::ParseError(format!(
+                        "Checksum mismatch: expected {:08x}, got {:08x}", expected, actual
+                    )));
+                }
+            }
+            None if json.version < JSON_CHECKSUM_VERSION => {}
+            None => {
+                return Err(WorkerError::ParseError(format!(
+                    "Invalid JSON worker config: version {} requires a checksum", json.version
+                )));
+            }
+        }
+        Ok(info)
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn from_json_content(_content: &str) -> Result<Self, WorkerError> {
+        Err(WorkerError::UnsupportedFormat(
+            "JSON worker config requires the `json` feature".to_string()
+        ))
+    }
+
+    /// 按当前启用的格式序列化：启用 `json` feature 时写JSON，否则写逐行格式
+    fn to_persisted_content(&self) -> String {
+        #[cfg(feature = "json")]
+        return self.to_json_content();
+        #[cfg(not(feature = "json"))]
+        return self.to_file_content();
+    }
+
     pub fn update_timestamp(&mut self) {
-        self.last_timestamp = current_millis();
+        self.record_checkpoint(current_millis(), 0);
+    }
+
+    /// 记录已签发到的 `(timestamp, sequence)`，只前进不后退
+    /// 
+    /// 时间戳前进时序列号随之替换；同一毫秒内取较大的序列号。
+    pub fn record_checkpoint(&mut self, timestamp: u64, sequence: u64) {
+        if timestamp > self.last_timestamp {
+            self.last_timestamp = timestamp;
+            self.last_sequence = sequence;
+        } else if timestamp == self.last_timestamp {
+            self.last_sequence = self.last_sequence.max(sequence);
+        }
     }
 
     pub fn check_clock_backwards(&self) -> Result<(), WorkerError> {
+        self.check_clock_backwards_with_grace(0)
+    }
+
+    /// 与 `check_clock_backwards` 相同，但允许 `last_timestamp` 领先当前时钟不超过 `grace_ms`
+    pub fn check_clock_backwards_with_grace(&self, grace_ms: u64) -> Result<(), WorkerError> {
         let current_time = current_millis();
-        if current_time < self.last_timestamp {
+        if current_time + grace_ms < self.last_timestamp {
             let diff = self.last_timestamp - current_time;
             return Err(WorkerError::ClockBackwardsError(
                 format!("Clock moved backwards by {} milliseconds. Last: {}, Current: {}", 
@@ -103,24 +371,62 @@ impl WorkerInfo {
     }
 }
 
+/// worker 配置文件管理器
+/// 
+/// 打开配置文件时对其加排他的建议锁（Unix 上为 `flock`，Windows 上为 `LockFileEx`），
+/// 防止两个进程读到同一 worker ID 而签发重复ID；锁随 `WorkerManager` 释放而解除。
 pub struct WorkerManager {
     file_path: String,
+    /// 持有排他锁的配置文件句柄，所有写入都经由它完成
+    file: File,
     worker_info: WorkerInfo,
+    compressed: bool,
 }
 
 impl WorkerManager {
-    pub fn new(file_path: &str, default_datacenter_id: u64) -> Result<Self, WorkerError> {
-        let worker_info = if Path::new(file_path).exists() {
+    /// 打开（必要时创建）配置文件；文件不存在或已损坏时按 `strategy` 派生新的 worker ID
+    pub fn new(file_path: &str, default_datacenter_id: u64, strategy: WorkerIdStrategy) -> Result<Self, WorkerError> {
+        match strategy {
+            WorkerIdStrategy::HostnameAndTime => Self::with_hasher(file_path, default_datacenter_id, fnv1a_hash),
+            WorkerIdStrategy::MacAddress => Self::open(file_path, default_datacenter_id, mac_worker_id),
+        }
+    }
+
+    /// 使用指定的哈希函数派生新的 worker ID（仅在配置文件不存在时生效）
+    pub fn with_hasher(file_path: &str, default_datacenter_id: u64, hasher: WorkerIdHasher) -> Result<Self, WorkerError> {
+        Self::open(file_path, default_datacenter_id, || generate_worker_id(hasher))
+    }
+
+    fn open(file_path: &str, default_datacenter_id: u64, new_worker_id: impl FnOnce() -> u64) -> Result<Self, WorkerError> {
+        let file = Self::lock_file(file_path)?;
+
+        let mut compressed = false;
+        let mut bytes = Vec::new();
+        (&file).read_to_end(&mut bytes)?;
+        // 加锁时才创建的空文件与不存在的文件同样对待
+        let existing = if !bytes.is_empty() {
             // 读取现有文件
-            let mut file = File::open(file_path)?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)?;
+            compressed = bytes.starts_with(&GZIP_MAGIC);
+            let contents = decode_file_content(bytes)?;
             
             println!("Found existing worker config file: {}", file_path);
-            let info = WorkerInfo::from_file_content(&contents)?;
-            
-            // 检查时钟回拨
-            info.check_clock_backwards()?;
+            match WorkerInfo::from_file_content(&contents) {
+                Ok(info) => Some(info),
+                Err(WorkerError::ParseError(msg)) => {
+                    // 文件损坏时不信任其中的任何字段，按新文件重新派生
+                    eprintln!("Worker config file {} is corrupt ({}), deriving a new worker ID", file_path, msg);
+                    None
+                }
+                // 包括 `UnsupportedFormat`：文件完好只是当前构建读不了，里面的 worker ID 仍在使用，不能覆盖
+                Err(e) => return Err(e),
+            }
+        } else {
+            None
+        };
+
+        let worker_info = if let Some(info) = existing {
+            // 检查时钟回拨（少量领先交由生成器等待追上）
+            info.check_clock_backwards_with_grace(RESTORE_GRACE_MS)?;
             
             println!("Worker ID: {}, Datacenter ID: {}", info.worker_id, info.datacenter_id);
             println!("Creation time: {}", format_timestamp(info.creation_time));
@@ -129,7 +435,7 @@ impl WorkerManager {
             info
         } else {
             // 生成新的 worker ID
-            let worker_id = generate_worker_id();
+            let worker_id = new_worker_id();
             let info = WorkerInfo::new(worker_id, default_datacenter_id);
             
             println!("Creating new worker config file: {}", file_path);
@@ -141,7 +447,9 @@ impl WorkerManager {
 
         let manager = WorkerManager {
             file_path: file_path.to_string(),
+            file,
             worker_info,
+            compressed,
         };
 
         // 保存当前状态到文件
@@ -150,6 +458,25 @@ impl WorkerManager {
         Ok(manager)
     }
 
+    /// 打开（必要时创建）配置文件并加排他锁，已被锁定时返回 `AlreadyLocked`
+    fn lock_file(file_path: &str) -> Result<File, WorkerError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(file_path)
+            .map_err(|e| WorkerError::store_error(file_path, e))?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(file),
+            Err(TryLockError::WouldBlock) => Err(WorkerError::AlreadyLocked(format!(
+                "worker config file '{}' is in use by another process", file_path
+            ))),
+            Err(TryLockError::Error(e)) => Err(WorkerError::store_error(file_path, e)),
+        }
+    }
+
     pub fn get_worker_info(&self) -> &WorkerInfo {
         &self.worker_info
     }
@@ -167,15 +494,65 @@ impl WorkerManager {
         Ok(())
     }
 
+    /// 更新并持久化 datacenter ID
+    pub fn set_datacenter_id(&mut self, datacenter_id: u64) -> Result<(), WorkerError> {
+        self.worker_info.datacenter_id = datacenter_id;
+        self.save_to_file()
+    }
+
+    /// 直接记录并保存给定的时间戳（只前进不后退），不做时钟回拨检查
+    /// 
+    /// 用于时间戳字段不来自系统时钟的场景（如计数器模式）。
+    pub fn save_last_timestamp(&mut self, timestamp: u64) -> Result<(), WorkerError> {
+        self.save_checkpoint(timestamp, 0)
+    }
+
+    /// 记录并保存已签发到的 `(timestamp, sequence)`，重启后可在同一毫秒内接着递增序列号
+    pub fn save_checkpoint(&mut self, timestamp: u64, sequence: u64) -> Result<(), WorkerError> {
+        self.worker_info.record_checkpoint(timestamp, sequence);
+        self.save_to_file()
+    }
+
     fn save_to_file(&self) -> Result<(), WorkerError> {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.file_path)?;
-        
-        file.write_all(self.worker_info.to_file_content().as_bytes())?;
-        Ok(())
+        let content = self.encode_file_content()?;
+        let mut file = &self.file;
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(&content))
+            .map_err(|e| WorkerError::store_error(&self.file_path, e))
+    }
+
+    /// 启用或关闭配置文件的 gzip 压缩，下一次保存时生效
+    /// 
+    /// 读取时根据 gzip 魔数自动识别，无需额外配置。
+    #[cfg(feature = "flate2")]
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compressed = enabled;
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    #[cfg(feature = "flate2")]
+    fn encode_file_content(&self) -> Result<Vec<u8>, WorkerError> {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let content = self.worker_info.to_persisted_content();
+        if !self.compressed {
+            return Ok(content.into_bytes());
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(content.as_bytes())
+            .and_then(|_| encoder.finish())
+            .map_err(|e| WorkerError::store_error(&self.file_path, e))
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    fn encode_file_content(&self) -> Result<Vec<u8>, WorkerError> {
+        Ok(self.worker_info.to_persisted_content().into_bytes())
     }
 
     pub fn get_worker_id(&self) -> u64 {
@@ -187,26 +564,112 @@ impl WorkerManager {
     }
 }
 
+/// 将配置文件字节解码为文本，gzip 压缩的内容会先解压
+fn decode_file_content(bytes: Vec<u8>) -> Result<String, WorkerError> {
+    let bytes = if bytes.starts_with(&GZIP_MAGIC) {
+        decompress(&bytes)?
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes)
+        .map_err(|_| WorkerError::ParseError("Invalid file format: not valid UTF-8".to_string()))
+}
+
+#[cfg(feature = "flate2")]
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>, WorkerError> {
+    use flate2::read::GzDecoder;
+
+    let mut decoded = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut decoded)
+        .map_err(|e| WorkerError::ParseError(format!("Invalid compressed file: {}", e)))?;
+    Ok(decoded)
+}
+
+#[cfg(not(feature = "flate2"))]
+fn decompress(_bytes: &[u8]) -> Result<Vec<u8>, WorkerError> {
+    Err(WorkerError::UnsupportedFormat(
+        "Compressed worker config requires the `flate2` feature".to_string()
+    ))
+}
+
 fn current_millis() -> u64 {
     let dur = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
     dur.as_millis() as u64
 }
 
-fn generate_worker_id() -> u64 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    
+fn generate_worker_id(hasher: WorkerIdHasher) -> u64 {
     // 基于机器名和当前时间生成 worker ID
-    let hostname = std::env::var("COMPUTERNAME")
+    derive_worker_id(hasher, &current_hostname(), current_millis())
+}
+
+/// 由第一个非回环网卡的MAC地址派生 worker ID，找不到网卡时只用机器名派生
+/// 
+/// 两种来源都不含时间，同一台机器上重复调用总是得到相同结果。
+pub fn mac_worker_id() -> u64 {
+    match first_mac_address() {
+        Some(mac) => fnv1a_hash(&mac) % (MAX_WORKER_ID + 1),
+        None => fnv1a_hash(current_hostname().as_bytes()) % (MAX_WORKER_ID + 1),
+    }
+}
+
+/// 按接口名排序后第一个非回环、非全零的MAC地址（读取 `/sys/class/net`，非Linux平台返回 `None`）
+fn first_mac_address() -> Option<[u8; 6]> {
+    let mut interfaces: Vec<_> = std::fs::read_dir("/sys/class/net").ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    interfaces.sort();
+
+    interfaces.iter()
+        .filter(|path| path.file_name().is_some_and(|name| name != "lo"))
+        .filter_map(|path| std::fs::read_to_string(path.join("address")).ok())
+        .filter_map(|address| parse_mac_address(address.trim()))
+        .find(|mac| mac.iter().any(|&byte| byte != 0))
+}
+
+fn parse_mac_address(address: &str) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut parts = address.split(':');
+    for byte in mac.iter_mut() {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    parts.next().is_none().then_some(mac)
+}
+
+/// 当前机器名，取自 `COMPUTERNAME` / `HOSTNAME` 环境变量
+pub(crate) fn current_hostname() -> String {
+    std::env::var("COMPUTERNAME")
         .or_else(|_| std::env::var("HOSTNAME"))
-        .unwrap_or_else(|_| "unknown".to_string());
-    
-    let mut hasher = DefaultHasher::new();
-    hostname.hash(&mut hasher);
-    current_millis().hash(&mut hasher);
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// 从 Kubernetes StatefulSet 的 pod 名（如 `app-3`）中解析序号作为 worker ID
+pub fn parse_statefulset_ordinal(hostname: &str) -> Result<u64, WorkerError> {
+    let ordinal = hostname
+        .rsplit_once('-')
+        .map(|(_, suffix)| suffix)
+        .filter(|suffix| !suffix.is_empty() && suffix.bytes().all(|b| b.is_ascii_digit()))
+        .ok_or_else(|| WorkerError::ParseError(format!(
+            "hostname '{}' has no StatefulSet ordinal suffix", hostname
+        )))?;
+
+    let worker_id = ordinal.parse::<u64>()
+        .map_err(|_| WorkerError::InvalidId(format!("ordinal '{}' is out of range", ordinal)))?;
+    if worker_id > MAX_WORKER_ID {
+        return Err(WorkerError::InvalidId(format!(
+            "ordinal {} exceeds maximum worker_id {}", worker_id, MAX_WORKER_ID
+        )));
+    }
+    Ok(worker_id)
+}
+
+/// 由机器名和种子时间派生 worker ID，相同输入和哈希函数总是得到相同结果
+pub fn derive_worker_id(hasher: WorkerIdHasher, hostname: &str, seed_millis: u64) -> u64 {
+    let mut input = hostname.as_bytes().to_vec();
+    input.extend_from_slice(&seed_millis.to_le_bytes());
     
     // 确保 worker ID 在有效范围内 (0-31)
-    (hasher.finish() % 32) as u64
+    hasher(&input) % (MAX_WORKER_ID + 1)
 }
 
 fn format_timestamp(timestamp: u64) -> String {
@@ -220,6 +683,7 @@ fn format_timestamp(timestamp: u64) -> String {
 mod tests {
     use super::*;
     use std::fs;
+    use std::path::Path;
 
     #[test]
     fn test_worker_info_serialization() {
@@ -231,6 +695,170 @@ mod tests {
         assert_eq!(info.datacenter_id, parsed_info.datacenter_id);
     }
 
+    #[test]
+    fn test_record_checkpoint_only_advances() {
+        let mut info = WorkerInfo::new(1, 2);
+        let ts = info.last_timestamp;
+        info.record_checkpoint(ts, 40);
+        info.record_checkpoint(ts, 10);
+        assert_eq!((info.last_timestamp, info.last_sequence), (ts, 40));
+
+        info.record_checkpoint(ts - 1, 4000);
+        assert_eq!((info.last_timestamp, info.last_sequence), (ts, 40));
+
+        info.record_checkpoint(ts + 1, 3);
+        assert_eq!((info.last_timestamp, info.last_sequence), (ts + 1, 3));
+
+        let parsed = WorkerInfo::from_file_content(&info.to_file_content()).unwrap();
+        assert_eq!(parsed, info);
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+
+        let info = WorkerInfo::new(1, 2);
+        let content = info.to_file_content();
+        assert!(content.contains(CHECKSUM_PREFIX));
+
+        // 篡改 last_timestamp 中的一个数字
+        let mut bytes = content.clone().into_bytes();
+        let pos = content.find(&info.last_timestamp.to_string()).unwrap() + 5;
+        bytes[pos] = if bytes[pos] == b'9' { b'0' } else { bytes[pos] + 1 };
+        let corrupted = String::from_utf8(bytes).unwrap();
+        assert!(matches!(
+            WorkerInfo::from_file_content(&corrupted),
+            Err(WorkerError::ParseError(_))
+        ));
+
+        // 截断的文件同样无法通过校验
+        assert!(WorkerInfo::from_file_content(&content[..content.len() - 4]).is_err());
+        // 恰好在校验和行之前截断的文件不能被当作旧格式接受
+        let without_checksum = &content[..content.find(CHECKSUM_PREFIX).unwrap()];
+        assert!(matches!(
+            WorkerInfo::from_file_content(without_checksum),
+            Err(WorkerError::ParseError(_))
+        ));
+        // 旧的4行格式须完整，缺少末尾换行视为截断
+        assert!(WorkerInfo::from_file_content("5\n2\n1000\n1000\n").is_ok());
+        assert!(WorkerInfo::from_file_content("5\n2\n1000\n1000").is_err());
+        assert!(WorkerInfo::from_file_content("5\n2\n1000\n").is_err());
+    }
+
+    #[cfg(not(feature = "json"))]
+    #[test]
+    fn test_legacy_file_rewritten_with_checksum() {
+        let test_file = "test_worker_legacy.conf";
+        let legacy = format!("5\n2\n{}\n{}\n", current_millis() - 1000, current_millis() - 5000);
+        fs::write(test_file, &legacy).unwrap();
+
+        let manager = WorkerManager::new(test_file, 2, WorkerIdStrategy::default()).unwrap();
+        assert_eq!(manager.get_worker_id(), 5);
+        let rewritten = fs::read_to_string(test_file).unwrap();
+        assert!(rewritten.contains(CHECKSUM_PREFIX));
+        assert_eq!(WorkerInfo::from_file_content(&rewritten).unwrap(), *manager.get_worker_info());
+
+        let _ = fs::remove_file(test_file);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_legacy_file_migrated_to_json() {
+        let test_file = "test_worker_migrate.conf";
+        let legacy = WorkerInfo {
+            worker_id: 6,
+            datacenter_id: 2,
+            last_timestamp: current_millis() - 1000,
+            creation_time: current_millis() - 5000,
+            last_sequence: 17,
+        };
+        fs::write(test_file, legacy.to_file_content()).unwrap();
+
+        let manager = WorkerManager::new(test_file, 2, WorkerIdStrategy::default()).unwrap();
+        assert_eq!(manager.get_worker_info(), &legacy);
+        let rewritten = fs::read_to_string(test_file).unwrap();
+        assert!(rewritten.starts_with('{'));
+        assert!(rewritten.contains(&format!("\"version\": {}", WORKER_INFO_JSON_VERSION)));
+        drop(manager);
+
+        let reloaded = WorkerManager::new(test_file, 2, WorkerIdStrategy::default()).unwrap();
+        assert_eq!(reloaded.get_worker_info(), &legacy);
+
+        let _ = fs::remove_file(test_file);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_ignores_unknown_fields() {
+        let content = r#"{"version": 2, "worker_id": 3, "datacenter_id": 4,
+            "last_timestamp": 10, "creation_time": 5, "sequence_checkpoint": 99}"#;
+        let info = WorkerInfo::from_file_content(content).unwrap();
+        assert_eq!((info.worker_id, info.datacenter_id, info.last_timestamp), (3, 4, 10));
+
+        assert!(matches!(
+            WorkerInfo::from_file_content(r#"{"version": 1, "worker_id": 3}"#),
+            Err(WorkerError::ParseError(_))
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_checksum_detects_corruption() {
+        let info = WorkerInfo {
+            worker_id: 3,
+            datacenter_id: 4,
+            last_timestamp: 10,
+            creation_time: 5,
+            last_sequence: 7,
+        };
+        let content = info.to_json_content();
+        assert!(content.contains(CHECKSUM_PREFIX));
+        assert_eq!(WorkerInfo::from_file_content(&content).unwrap(), info);
+
+        let tampered = content.replace("\"worker_id\": 3", "\"worker_id\": 30");
+        assert!(matches!(WorkerInfo::from_file_content(&tampered), Err(WorkerError::ParseError(_))));
+
+        // 版本3起必须带校验和，更早版本的文件缺少校验和仍可读取
+        let v3 = r#"{"version": 3, "worker_id": 3, "datacenter_id": 4, "last_timestamp": 10, "creation_time": 5}"#;
+        assert!(matches!(WorkerInfo::from_file_content(v3), Err(WorkerError::ParseError(_))));
+        assert!(WorkerInfo::from_file_content(&v3.replace("\"version\": 3", "\"version\": 2")).is_ok());
+    }
+
+    #[cfg(not(feature = "json"))]
+    #[test]
+    fn test_json_requires_feature() {
+        assert!(matches!(
+            WorkerInfo::from_file_content(r#"{"version": 1}"#),
+            Err(WorkerError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[cfg(not(feature = "json"))]
+    #[test]
+    fn test_json_file_not_overwritten_without_feature() {
+        let test_file = "test_worker_json_unsupported.conf";
+        let content = r#"{"version": 2, "worker_id": 3, "datacenter_id": 4, "last_timestamp": 10, "creation_time": 5}"#;
+        fs::write(test_file, content).unwrap();
+
+        assert!(matches!(WorkerManager::new(test_file, 1, WorkerIdStrategy::default()), Err(WorkerError::UnsupportedFormat(_))));
+        assert_eq!(fs::read_to_string(test_file).unwrap(), content);
+
+        let _ = fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_corrupt_file_derives_fresh_worker() {
+        let test_file = "test_worker_corrupt.conf";
+        fs::write(test_file, "5\n2\n1\n1\ncrc32:00000000\n").unwrap();
+
+        let manager = WorkerManager::new(test_file, 3, WorkerIdStrategy::default()).unwrap();
+        assert_eq!(manager.get_datacenter_id(), 3);
+        let rewritten = fs::read_to_string(test_file).unwrap();
+        assert!(WorkerInfo::from_file_content(&rewritten).is_ok());
+
+        let _ = fs::remove_file(test_file);
+    }
+
     #[test]
     fn test_clock_backwards_detection() {
         let mut info = WorkerInfo::new(1, 2);
@@ -240,6 +868,17 @@ mod tests {
         assert!(info.check_clock_backwards().is_err());
     }
 
+    #[test]
+    fn test_clock_backwards_grace() {
+        let mut info = WorkerInfo::new(1, 2);
+        info.last_timestamp = current_millis() + 200;
+        assert!(info.check_clock_backwards().is_err());
+        assert!(info.check_clock_backwards_with_grace(RESTORE_GRACE_MS).is_ok());
+
+        info.last_timestamp = current_millis() + RESTORE_GRACE_MS + 10_000;
+        assert!(info.check_clock_backwards_with_grace(RESTORE_GRACE_MS).is_err());
+    }
+
     #[test]
     fn test_worker_manager_creation() {
         let test_file = "test_worker.conf";
@@ -248,10 +887,135 @@ mod tests {
         let _ = fs::remove_file(test_file);
         
         // 创建新的 WorkerManager
-        let _manager = WorkerManager::new(test_file, 1).unwrap();
+        let _manager = WorkerManager::new(test_file, 1, WorkerIdStrategy::default()).unwrap();
         assert!(Path::new(test_file).exists());
         
         // 清理测试文件
         let _ = fs::remove_file(test_file);
     }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_compressed_round_trip() {
+        let test_file = "test_worker_compressed.conf";
+        let _ = fs::remove_file(test_file);
+
+        let mut manager = WorkerManager::new(test_file, 3, WorkerIdStrategy::default()).unwrap();
+        manager.set_compression(true);
+        manager.update_and_save().unwrap();
+        let saved_info = manager.get_worker_info().clone();
+
+        let bytes = fs::read(test_file).unwrap();
+        assert!(bytes.starts_with(&GZIP_MAGIC));
+        drop(manager);
+
+        let reloaded = WorkerManager::new(test_file, 3, WorkerIdStrategy::default()).unwrap();
+        assert!(reloaded.is_compressed());
+        assert_eq!(reloaded.get_worker_info(), &saved_info);
+
+        let _ = fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_derived_worker_id_is_stable() {
+        // FNV-1a 的输出是固定的，不随 Rust 版本变化
+        assert_eq!(fnv1a_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a_hash(b"a"), 0xaf63dc4c8601ec8c);
+
+        let first = derive_worker_id(fnv1a_hash, "app-host", 1640995200000);
+        for _ in 0..10 {
+            assert_eq!(derive_worker_id(fnv1a_hash, "app-host", 1640995200000), first);
+        }
+        assert!(first <= MAX_WORKER_ID);
+
+        fn constant_hasher(_: &[u8]) -> u64 {
+            37
+        }
+        assert_eq!(derive_worker_id(constant_hasher, "app-host", 0), 5);
+    }
+
+    #[test]
+    fn test_mac_worker_id_is_stable() {
+        let first = mac_worker_id();
+        assert!(first <= MAX_WORKER_ID);
+        assert_eq!(mac_worker_id(), first);
+
+        let test_file = "test_worker_mac.conf";
+        for _ in 0..2 {
+            let _ = fs::remove_file(test_file);
+            let manager = WorkerManager::new(test_file, 1, WorkerIdStrategy::MacAddress).unwrap();
+            assert_eq!(manager.get_worker_id(), first);
+        }
+        let _ = fs::remove_file(test_file);
+
+        assert_eq!(parse_mac_address("02:fc:00:00:0a:ff"), Some([0x02, 0xfc, 0, 0, 0x0a, 0xff]));
+        assert_eq!(parse_mac_address("02:fc:00:00:0a"), None);
+        assert_eq!(parse_mac_address("02:fc:00:00:0a:ff:01"), None);
+        assert_eq!(parse_mac_address("zz:fc:00:00:0a:ff"), None);
+    }
+
+    #[test]
+    fn test_parse_statefulset_ordinal() {
+        assert_eq!(parse_statefulset_ordinal("app-7").unwrap(), 7);
+        assert_eq!(parse_statefulset_ordinal("snowflake-api-31").unwrap(), 31);
+        assert!(matches!(parse_statefulset_ordinal("app-32"), Err(WorkerError::InvalidId(_))));
+        assert!(matches!(parse_statefulset_ordinal("app"), Err(WorkerError::ParseError(_))));
+        assert!(matches!(parse_statefulset_ordinal("app-"), Err(WorkerError::ParseError(_))));
+        assert!(matches!(parse_statefulset_ordinal("app-x1"), Err(WorkerError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_with_hasher_derives_worker_id() {
+        let test_file = "test_worker_hasher.conf";
+        let _ = fs::remove_file(test_file);
+
+        fn constant_hasher(_: &[u8]) -> u64 {
+            7
+        }
+        let manager = WorkerManager::with_hasher(test_file, 1, constant_hasher).unwrap();
+        assert_eq!(manager.get_worker_id(), 7);
+
+        let _ = fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_error_display_names_path() {
+        let err = WorkerError::lock_contention("config/worker.conf", "pid 42");
+        let msg = err.to_string();
+        assert!(msg.starts_with("Lock contention:"));
+        assert!(msg.contains("config/worker.conf"));
+        assert!(msg.contains("pid 42"));
+
+        let err = WorkerError::store_error("config/worker.conf", "disk full");
+        let msg = err.to_string();
+        assert!(msg.starts_with("Store error:"));
+        assert!(msg.contains("config/worker.conf"));
+        assert!(msg.contains("disk full"));
+    }
+
+    #[test]
+    fn test_config_file_locked_while_manager_alive() {
+        let test_file = "test_worker_locked.conf";
+        let _ = fs::remove_file(test_file);
+
+        let manager = WorkerManager::new(test_file, 1, WorkerIdStrategy::default()).unwrap();
+        assert!(matches!(WorkerManager::new(test_file, 1, WorkerIdStrategy::default()), Err(WorkerError::AlreadyLocked(_))));
+
+        let worker_id = manager.get_worker_id();
+        drop(manager);
+        let reopened = WorkerManager::new(test_file, 1, WorkerIdStrategy::default()).unwrap();
+        assert_eq!(reopened.get_worker_id(), worker_id);
+
+        drop(reopened);
+        let _ = fs::remove_file(test_file);
+    }
+
+    #[test]
+    fn test_save_error_names_path() {
+        let missing_dir_file = "no_such_dir_for_test/worker.conf";
+        match WorkerManager::new(missing_dir_file, 1, WorkerIdStrategy::default()) {
+            Err(WorkerError::StoreError(msg)) => assert!(msg.contains(missing_dir_file)),
+            other => panic!("expected StoreError, got {:?}", other.err()),
+        }
+    }
 }
@@ -0,0 +1,32 @@
+use std::collections::HashSet;
+use std::process::Command;
+
+#[test]
+fn test_cli_generate_count() {
+    let output = Command::new(env!("CARGO_BIN_EXE_snowflake"))
+        .args(["generate", "--count", "3"])
+        .output()
+        .expect("failed to run snowflake cli");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let ids: Vec<u64> = stdout.lines().map(|line| line.parse().unwrap()).collect();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 3);
+}
+
+#[test]
+fn test_cli_parse_with_epoch() {
+    let id = (1000u64 << 22) | (2 << 17) | (3 << 12) | 4;
+    let output = Command::new(env!("CARGO_BIN_EXE_snowflake"))
+        .args(["parse", &id.to_string(), "--epoch", "0"])
+        .output()
+        .expect("failed to run snowflake cli");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Timestamp: 1000 "));
+    assert!(stdout.contains("Datacenter ID: 2"));
+    assert!(stdout.contains("Worker ID: 3"));
+    assert!(stdout.contains("Sequence: 4"));
+}
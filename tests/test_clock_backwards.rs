@@ -1,50 +1,50 @@
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use snowflake_generator::{Snowflake, WorkerError};
 
-fn main() -> Result<(), WorkerError> {
-    println!("=== 时钟回拨测试 ===");
-    
+/// 测试结束（包括断言失败）时删除临时配置文件
+struct TempConfig(PathBuf);
+
+impl TempConfig {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}-{}.conf", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        TempConfig(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempConfig {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn test_future_config_timestamp_is_clock_backwards() {
+    let config = TempConfig::new("snowflake-clock-backwards");
+
     // 1. 创建正常的配置文件
-    let config_file = "config/test_worker.conf";
-    let mut sf = Snowflake::new_with_config(config_file, 1)?;
-    
-    println!("✓ 生成第一个 ID");
-    let id1 = sf.next_id()?;
-    println!("ID: {}", id1);
-    
-    // 2. 手动修改配置文件，模拟时钟回拨
-    println!("\n--- 模拟时钟回拨 ---");
-    let future_timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
+    let mut sf = Snowflake::new_with_config(config.path(), 1).unwrap();
+    sf.next_id().unwrap();
+    drop(sf);
+
+    // 2. 把配置文件中的时间戳改到未来1分钟，模拟时钟回拨
+    let future_timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_millis() as u64 + 60000; // 未来1分钟
-    
-    // 修改配置文件中的时间戳
-    let fake_content = format!("18\n1\n{}\n{}\n", future_timestamp, future_timestamp);
-    let mut file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(config_file)?;
-    file.write_all(fake_content.as_bytes())?;
-    drop(file);
-    
-    println!("已修改配置文件时间戳到未来");
-    
-    // 3. 尝试重新初始化，应该检测到时钟回拨
-    println!("尝试重新初始化...");
-    match Snowflake::new_with_config(config_file, 1) {
-        Ok(_) => println!("⚠️  未检测到时钟回拨（不应该发生）"),
-        Err(WorkerError::ClockBackwardsError(msg)) => {
-            println!("✓ 成功检测到时钟回拨: {}", msg);
-        },
-        Err(e) => println!("❌ 其他错误: {}", e),
+        .as_millis() as u64 + 60000;
+    fs::write(config.path(), format!("18\n1\n{}\n{}\n", future_timestamp, future_timestamp)).unwrap();
+
+    // 3. 重新初始化应检测到时钟回拨
+    match Snowflake::new_with_config(config.path(), 1) {
+        Err(WorkerError::ClockBackwardsError(msg)) => assert!(msg.contains(&future_timestamp.to_string())),
+        Err(e) => panic!("expected ClockBackwardsError, got {}", e),
+        Ok(_) => panic!("clock backwards was not detected"),
     }
-    
-    // 清理测试文件
-    let _ = std::fs::remove_file(config_file);
-    
-    println!("\n=== 测试完成 ===");
-    Ok(())
 }
@@ -0,0 +1,106 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use snowflake_generator::{WorkerIdStrategy, WorkerManager};
+
+/// 子进程持有锁时读取的环境变量，值为配置文件路径
+const HOLDER_ENV: &str = "SNOWFLAKE_LOCK_TEST_CONFIG";
+
+/// 测试结束（包括断言失败）时删除临时配置文件
+struct TempConfig(PathBuf);
+
+impl TempConfig {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("{}-{}.conf", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        TempConfig(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempConfig {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// 子进程入口：打开配置文件后输出 `locked`，持有锁直到 stdin 关闭
+///
+/// 未设置 `HOLDER_ENV` 时（即普通的 `cargo test`）直接返回。
+#[test]
+fn lock_holder() {
+    let Ok(path) = env::var(HOLDER_ENV) else { return };
+    let manager = WorkerManager::new(&path, 1, WorkerIdStrategy::default());
+    println!("{}", if manager.is_ok() { "locked" } else { "rejected" });
+    if let Err(e) = manager {
+        println!("{}", e);
+        return;
+    }
+    let _ = std::io::stdin().read_to_end(&mut Vec::new());
+}
+
+/// 以 `lock_holder` 为入口重新启动的子进程
+struct Holder {
+    child: Child,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Holder {
+    fn spawn(config: &TempConfig) -> Self {
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args(["lock_holder", "--exact", "--nocapture", "--test-threads=1"])
+            .env(HOLDER_ENV, config.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to spawn lock holder");
+        let stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+        Holder { child, stdout }
+    }
+
+    /// 等待子进程输出 `locked` 或 `rejected`，被拒绝时一并返回错误信息
+    fn verdict(&mut self) -> (&'static str, Option<String>) {
+        let verdict = self.stdout.by_ref()
+            .map_while(Result::ok)
+            // libtest 的 `test lock_holder ... ` 前缀可能与结论在同一行
+            .find_map(|line| ["locked", "rejected"].into_iter().find(|v| line.ends_with(v)))
+            .expect("lock holder exited without a verdict");
+        let message = (verdict == "rejected").then(|| self.stdout.next().unwrap().unwrap());
+        (verdict, message)
+    }
+
+    /// 关闭 stdin 让子进程释放锁退出，返回其是否成功
+    fn release(mut self) -> bool {
+        drop(self.child.stdin.take());
+        self.stdout.by_ref().for_each(drop);
+        self.child.wait().unwrap().success()
+    }
+}
+
+#[test]
+fn test_second_process_cannot_share_worker_config() {
+    let config = TempConfig::new("snowflake-worker-lock");
+
+    let mut first = Holder::spawn(&config);
+    assert_eq!(first.verdict().0, "locked");
+
+    // 第一个进程仍持有锁：第二个进程必须失败
+    let mut second = Holder::spawn(&config);
+    let (verdict, message) = second.verdict();
+    assert!(second.release());
+    assert_eq!(verdict, "rejected");
+    let message = message.unwrap();
+    assert!(message.starts_with("Already locked:"), "unexpected error: {}", message);
+
+    // 第一个进程退出后锁被释放
+    assert!(first.release());
+    let mut third = Holder::spawn(&config);
+    assert_eq!(third.verdict().0, "locked");
+    assert!(third.release());
+}